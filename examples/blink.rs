@@ -4,7 +4,7 @@ extern crate alloc_system;
 extern crate cupi;
 extern crate cupi_shift;
 
-use cupi_shift::Shifter;
+use cupi_shift::{Shifter, Apply};
 use cupi::{delay_ms};
 
 /// This example toggles ("blinks") all output pins on a single shift register.
@@ -27,10 +27,10 @@ fn main() {
     println!("Looping {} times...", loops);
     for i in 0..loops {
         println!("Loop {}: All ON", i+1);
-        shifter.set(sr0, 0b11111111, true); // All on
+        shifter.set(sr0, 0b11111111, Apply::Now).unwrap(); // All on
         delay_ms(1000);
         println!("Loop {}: All OFF", i+1);
-        shifter.set(sr0, 0b00000000, true); // All off
+        shifter.set(sr0, 0b00000000, Apply::Now).unwrap(); // All off
         delay_ms(1000);
     }
 
@@ -4,7 +4,7 @@ extern crate alloc_system;
 extern crate cupi;
 extern crate cupi_shift;
 
-use cupi_shift::Shifter;
+use cupi_shift::{Shifter, Apply};
 use cupi::{delay_ms};
 
 /// This example toggles ("blinks") all output pins on multiple shift registers.
@@ -19,7 +19,7 @@ fn main() {
     let mut shifter = Shifter::new(data_pin, latch_pin, clock_pin);
     let pins = 8; // Number of output pins on our shift registers
     // Call .add() once for each shift register in the chain...
-    let sr0 = shifter.add(pins); // The values returned by .add() are just indexes
+    let sr0 = shifter.add(pins); // The values returned by .add() are handles to each shift register
     let sr1 = shifter.add(pins);
     // If you find that these states are inverted/backwards you can swap them with the `invert()` method...
     // shifter.invert(); // Uncomment to invert
@@ -27,15 +27,15 @@ fn main() {
     println!("Looping {} times...", loops);
     for i in 0..loops {
         println!("Loop {}: All ON", i+1);
-    // Note that the 3rd argument (false) controls whether the change should be applied immediately:
-        shifter.set(sr0, 0b11111111, false); // All on sr0
-        shifter.set(sr1, 0b11111111, true); // All on sr1
+    // Note that the 3rd argument (Apply::Later) controls whether the change should be applied immediately:
+        shifter.set(sr0, 0b11111111, Apply::Later).unwrap(); // All on sr0
+        shifter.set(sr1, 0b11111111, Apply::Now).unwrap(); // All on sr1
     // Applying the change as a final step is much more efficient and prevents flickering
         delay_ms(1000);
         println!("Loop {}: All OFF", i+1);
-        shifter.set(sr0, 0b00000000, false); // All off sr0
-        shifter.set(sr1, 0b00000000, false); // All off sr1
-        shifter.apply(); // The other way to apply changes
+        shifter.set(sr0, 0b00000000, Apply::Later).unwrap(); // All off sr0
+        shifter.set(sr1, 0b00000000, Apply::Later).unwrap(); // All off sr1
+        shifter.apply().unwrap(); // The other way to apply changes
         delay_ms(1000);
     }
 
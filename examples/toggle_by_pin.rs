@@ -4,7 +4,7 @@ extern crate alloc_system;
 extern crate cupi;
 extern crate cupi_shift;
 
-use cupi_shift::Shifter;
+use cupi_shift::{Shifter, Apply};
 use cupi::{delay_ms};
 
 /// Ever wish you could address each of your shift register pins like they were
@@ -27,10 +27,10 @@ fn main() {
     for _ in 0..2 {
         for i in 0..pins {
             println!("Setting pin {} HIGH", i);
-            shifter.set_pin_high(sr0, i, true);
+            shifter.set_pin_high(sr0, i, Apply::Now).unwrap();
             delay_ms(100);
             println!("Setting pin {} LOW", i);
-            shifter.set_pin_low(sr0, i, true);
+            shifter.set_pin_low(sr0, i, Apply::Now).unwrap();
             delay_ms(100);
         }
     }
@@ -0,0 +1,18 @@
+//! Builds cupi_shift's `register` and `protocol` modules (the pure
+//! state-tracking side of things) on their own with `#![no_std]` so that
+//! anything in them that needs `std` fails the build:
+//!
+//! ```text
+//! cargo check --manifest-path nostd-check/Cargo.toml
+//! ```
+
+#![no_std]
+#![allow(dead_code, unexpected_cfgs)]
+
+#[macro_use]
+extern crate alloc;
+
+#[path = "../../src/protocol.rs"]
+mod protocol;
+#[path = "../../src/register.rs"]
+mod register;
@@ -0,0 +1,175 @@
+//! Running a `Shifter` in its own thread and controlling it by sending it
+//! commands.  See `Shifter.spawn()` and `ShifterClient`.
+
+use std::sync::mpsc::{self, Sender, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+use {Shifter, ShifterError, RegisterHandle, Apply};
+
+// Something for the shifter's thread to do
+pub(crate) type Job = Box<dyn FnOnce(&mut Shifter) + Send>;
+
+/// A change to make to a `Shifter` that's running in its own thread (see
+/// `ShifterClient.send()`).  Each one does the same thing as the `Shifter`
+/// method of the same name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// See `Shifter.set()`.
+    Set { sr: RegisterHandle, data: usize, apply: Apply },
+    /// Sets the given pin HIGH (if *state* is `true`) or LOW (see
+    /// `Shifter.set_pin_high()` and `Shifter.set_pin_low()`).
+    SetPin { sr: RegisterHandle, pin: u8, state: bool, apply: Apply },
+    /// See `Shifter.toggle_pin()`.
+    TogglePin { sr: RegisterHandle, pin: u8, apply: Apply },
+    /// See `Shifter.set_bytes()`.
+    SetBytes { sr: RegisterHandle, bytes: Vec<u8>, apply: Apply },
+    /// See `Shifter.set_chain()`.
+    SetChain { bytes: Vec<u8>, apply: Apply },
+    /// See `Shifter.set_named()`.
+    SetNamed { name: String, state: bool, apply: Apply },
+    /// See `Shifter.set_group()`.
+    SetGroup { name: String, state: bool, apply: Apply },
+    /// See `Shifter.all_high()`.
+    AllHigh(Apply),
+    /// See `Shifter.all_low()`.
+    AllLow(Apply),
+    /// See `Shifter.apply()`.
+    Apply,
+}
+
+impl Command {
+
+    /// Carries out this command on the given *shifter*.
+    pub fn run(self, shifter: &mut Shifter) -> Result<(), ShifterError> {
+        match self {
+            Command::Set { sr, data, apply } => shifter.set(sr, data, apply),
+            Command::SetPin { sr, pin, state: true, apply } => shifter.set_pin_high(sr, pin, apply),
+            Command::SetPin { sr, pin, state: false, apply } => shifter.set_pin_low(sr, pin, apply),
+            Command::TogglePin { sr, pin, apply } => shifter.toggle_pin(sr, pin, apply),
+            Command::SetBytes { sr, bytes, apply } => shifter.set_bytes(sr, &bytes, apply),
+            Command::SetChain { bytes, apply } => shifter.set_chain(&bytes, apply),
+            Command::SetNamed { name, state, apply } => shifter.set_named(&name, state, apply),
+            Command::SetGroup { name, state, apply } => shifter.set_group(&name, state, apply),
+            Command::AllHigh(apply) => shifter.all_high(apply),
+            Command::AllLow(apply) => shifter.all_low(apply),
+            Command::Apply => shifter.apply().map(|_| ()),
+        }
+    }
+
+    /// Returns this command with its *apply* field (if it has one) replaced
+    /// with the given one.
+    pub fn with_apply(self, apply: Apply) -> Command {
+        match self {
+            Command::Set { sr, data, .. } => Command::Set { sr: sr, data: data, apply: apply },
+            Command::SetPin { sr, pin, state, .. } => Command::SetPin { sr: sr, pin: pin, state: state, apply: apply },
+            Command::TogglePin { sr, pin, .. } => Command::TogglePin { sr: sr, pin: pin, apply: apply },
+            Command::SetBytes { sr, bytes, .. } => Command::SetBytes { sr: sr, bytes: bytes, apply: apply },
+            Command::SetChain { bytes, .. } => Command::SetChain { bytes: bytes, apply: apply },
+            Command::SetNamed { name, state, .. } => Command::SetNamed { name: name, state: state, apply: apply },
+            Command::SetGroup { name, state, .. } => Command::SetGroup { name: name, state: state, apply: apply },
+            Command::AllHigh(_) => Command::AllHigh(apply),
+            Command::AllLow(_) => Command::AllLow(apply),
+            Command::Apply => Command::Apply,
+        }
+    }
+}
+
+/// A cheap, cloneable way to control a `Shifter` that's running in its own
+/// thread (see `Shifter.spawn()`).  Commands from every clone are carried out
+/// one at a time in the order in which they arrive so nothing ever sees (or
+/// shifts out) half of another command's changes.
+///
+/// ```
+/// let (client, thread) = shifter.spawn()?;
+/// let web = client.clone(); // Hand this to your web server, GUI, etc
+/// web.send(Command::SetPin { sr: sr0, pin: 3, state: true, apply: Apply::Now })?;
+/// client.post(Command::AllLow(Apply::Now))?; // Don't wait for it
+/// let state = client.execute(move |shifter| shifter.get(sr0))?;
+/// ```
+///
+/// Every method returns a `ShifterError::Disconnected` if the shifter's
+/// thread is gone (e.g. because a command panicked).
+#[derive(Clone)]
+pub struct ShifterClient {
+    jobs: Sender<Job>,
+}
+
+impl ShifterClient {
+
+    /// Sends the given *command* and waits for it to be carried out, returning
+    /// its result.
+    pub fn send(&self, command: Command) -> Result<(), ShifterError> {
+        self.execute(move |shifter| command.run(shifter))
+    }
+
+    /// Sends the given *command* without waiting for it to be carried out.
+    /// Any error it runs into is ignored.
+    pub fn post(&self, command: Command) -> Result<(), ShifterError> {
+        self.submit(Box::new(move |shifter| { let _ = command.run(shifter); }))
+    }
+
+    /// Runs *f* on the shifter's thread (with full access to the `Shifter`)
+    /// and returns whatever it returns.  Use this for anything `Command`
+    /// doesn't cover.
+    pub fn execute<F, R>(&self, f: F) -> Result<R, ShifterError>
+        where F: FnOnce(&mut Shifter) -> Result<R, ShifterError> + Send + 'static, R: Send + 'static
+    {
+        let (reply, result) = mpsc::channel();
+        self.submit(Box::new(move |shifter| { let _ = reply.send(f(shifter)); }))?;
+        result.recv().map_err(|_| ShifterError::Disconnected)?
+    }
+
+    pub(crate) fn submit(&self, job: Job) -> Result<(), ShifterError> {
+        self.jobs.send(job).map_err(|_| ShifterError::Disconnected)
+    }
+}
+
+impl std::fmt::Debug for ShifterClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ShifterClient").finish()
+    }
+}
+
+impl Shifter {
+
+    /// Moves this `Shifter` to a thread of its own and returns a
+    /// `ShifterClient` for sending it commands along with the thread's
+    /// handle.  The thread keeps running until every clone of the client has
+    /// been dropped, at which point joining it gives you the `Shifter` back.
+    /// It also takes care of calling `tick()` when an apply held back by
+    /// `set_coalesce_window()` or `set_max_apply_rate()` is due, whenever
+    /// running animations (see `start_animation()`) need updating, and when
+    /// the next step of a sequence (see `play()`) or scheduled command (see
+    /// `set_schedule()`) is due.
+    ///
+    /// Returns a `ShifterError::IoError` if the thread can't be spawned.
+    pub fn spawn(self) -> Result<(ShifterClient, JoinHandle<Shifter>), ShifterError> {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let thread = thread::Builder::new()
+            .name("cupi_shift".to_string())
+            .spawn(move || {
+                let mut shifter = self;
+                loop {
+                    // Wake up in time for any apply that's being held back
+                    // (or anything else tick() takes care of):
+                    let job = match shifter.next_wakeup() {
+                        Some(due) => match queue.recv_timeout(due.saturating_duration_since(Instant::now())) {
+                            Ok(job) => Some(job),
+                            Err(RecvTimeoutError::Timeout) => None,
+                            Err(RecvTimeoutError::Disconnected) => break,
+                        },
+                        None => match queue.recv() {
+                            Ok(job) => Some(job),
+                            Err(_) => break,
+                        },
+                    };
+                    match job {
+                        Some(job) => job(&mut shifter),
+                        None => { let _ = shifter.tick(); },
+                    }
+                }
+                shifter
+            })?;
+        Ok((ShifterClient { jobs: jobs }, thread))
+    }
+}
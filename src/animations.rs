@@ -0,0 +1,349 @@
+//! A small animation engine:  Named, keyframed or procedural animations that
+//! change the brightness of pins, groups, or whole shift registers over time.
+//! See `Animation` and `Shifter.add_animation()`.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use {Shifter, ShifterError, RegisterHandle, Easing, period_from_hz};
+
+/// The pins an `Animation` drives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// A single pin on the given shift register.
+    Pin(RegisterHandle, u8),
+    /// The pin with the given name (see `Shifter.name_pin()`).
+    Named(String),
+    /// Every pin in the group with the given name (see
+    /// `Shifter.define_group()`) in the order in which they were given.
+    Group(String),
+    /// Every pin on the given shift register (starting with pin 0).
+    Register(RegisterHandle),
+}
+
+// How an animation works out the level of each pin
+enum Curve {
+    // (time, level) pairs sorted by time:
+    Keyframes(Vec<(Duration, f64)>),
+    Procedural(Box<dyn FnMut(Duration, usize) -> f64 + Send>),
+}
+
+/// Something that changes the brightness of a `Target`'s pins over time.
+/// Give it a name with `Shifter.add_animation()` and the `Shifter` takes care
+/// of the rest (see `start_animation()`):
+///
+/// ```
+/// // Fade the porch lights up over 2 seconds then back down:
+/// let pulse = Animation::keyframes(Target::Named("porch".into()), &[
+///     (Duration::from_secs(0), 0.0),
+///     (Duration::from_secs(2), 1.0),
+///     (Duration::from_secs(4), 0.0),
+/// ]).repeat();
+/// // A chase along a group of 8 LEDs, one step every 100ms:
+/// let chase = Animation::procedural(Target::Group("ring".into()), |t, i| {
+///     if (t.as_millis() / 100) as usize % 8 == i { 1.0 } else { 0.0 }
+/// });
+/// shifter.add_animation("pulse", pulse)?;
+/// shifter.add_animation("chase", chase)?;
+/// shifter.start_animation("chase")?;
+/// ```
+///
+/// Levels are brightnesses from 0.0 to 1.0 that get set via
+/// `Shifter.set_brightness()` so anything in between fully off and fully on
+/// needs the chain to be refreshing (see `Shifter.start_refresh()`).
+/// Animations that only ever use 0.0 and 1.0 work with plain `tick()` calls.
+pub struct Animation {
+    target: Target,
+    curve: Curve,
+    easing: Easing, // Between keyframes
+    repeat: bool,
+}
+
+impl Animation {
+
+    /// Returns a new `Animation` that moves the *target*'s pins from one
+    /// level to the next at the given times (relative to when the animation
+    /// is started) in a straight line (or along the curve given to
+    /// `easing()`).  The animation finishes at the last
+    /// keyframe (unless it's set to `repeat()`).  The keyframes don't need to
+    /// be in order.
+    pub fn keyframes(target: Target, keyframes: &[(Duration, f64)]) -> Animation {
+        let mut keyframes = keyframes.to_vec();
+        keyframes.sort_by_key(|&(time, _)| time);
+        Animation {
+            target: target,
+            curve: Curve::Keyframes(keyframes),
+            easing: Easing::Linear,
+            repeat: false,
+        }
+    }
+
+    /// Returns a new `Animation` that fades the *target*'s pins from one
+    /// level to another over the given *duration*.
+    pub fn fade(target: Target, from: f64, to: f64, duration: Duration) -> Animation {
+        Animation::keyframes(target, &[(Duration::from_secs(0), from), (duration, to)])
+    }
+
+    /// Returns a new `Animation` that calls *f* to work out the level of each
+    /// of the *target*'s pins every frame.  *f* gets the time since the
+    /// animation was started and the index of the pin within the target.
+    /// Procedural animations keep running until they're stopped.
+    pub fn procedural<F>(target: Target, f: F) -> Animation
+        where F: FnMut(Duration, usize) -> f64 + Send + 'static
+    {
+        Animation {
+            target: target,
+            curve: Curve::Procedural(Box::new(f)),
+            easing: Easing::Linear,
+            repeat: false,
+        }
+    }
+
+    /// Sets the `Easing` curve used to get from each keyframe to the next
+    /// (`Easing::Linear` by default) so fades speed up and slow down
+    /// smoothly instead of changing at a constant rate:
+    ///
+    /// ```
+    /// let breathe = Animation::keyframes(Target::Pin(sr0, 3), &[
+    ///     (Duration::from_secs(0), 0.0),
+    ///     (Duration::from_millis(1500), 1.0),
+    ///     (Duration::from_secs(3), 0.0),
+    /// ]).easing(Easing::SineInOut).repeat();
+    /// ```
+    ///
+    /// Has no effect on procedural animations (use `Easing.apply()` within
+    /// them instead).
+    pub fn easing(mut self, easing: Easing) -> Animation {
+        self.easing = easing;
+        self
+    }
+
+    /// Makes a keyframed animation start over from the beginning every time
+    /// it reaches the end instead of finishing.
+    pub fn repeat(mut self) -> Animation {
+        self.repeat = true;
+        self
+    }
+
+    /// Returns the pins this animation drives.
+    pub fn target(&self) -> &Target {
+        &self.target
+    }
+
+    /// Returns how long this animation takes to finish (`None` if it keeps
+    /// going until it's stopped).
+    pub fn duration(&self) -> Option<Duration> {
+        match self.curve {
+            Curve::Keyframes(_) if self.repeat => None,
+            Curve::Keyframes(ref keyframes) => Some(keyframes.last().map_or(Duration::from_secs(0), |&(time, _)| time)),
+            Curve::Procedural(_) => None,
+        }
+    }
+
+    // Returns the level of the pin at *index* at the given *time* (since the
+    // animation was started)
+    fn level(&mut self, time: Duration, index: usize) -> f64 {
+        let level = match self.curve {
+            Curve::Keyframes(ref keyframes) => {
+                let end = keyframes.last().map_or(Duration::from_secs(0), |&(time, _)| time);
+                let time = if self.repeat && end > Duration::from_secs(0) {
+                    Duration::from_secs_f64(time.as_secs_f64() % end.as_secs_f64())
+                } else {
+                    time
+                };
+                interpolate(keyframes, time, self.easing)
+            },
+            Curve::Procedural(ref mut f) => f(time, index),
+        };
+        level.clamp(0.0, 1.0)
+    }
+}
+
+impl std::fmt::Debug for Animation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut debug = f.debug_struct("Animation");
+        debug.field("target", &self.target);
+        if let Curve::Keyframes(ref keyframes) = self.curve {
+            debug.field("keyframes", keyframes);
+        }
+        debug.field("easing", &self.easing).field("repeat", &self.repeat).finish()
+    }
+}
+
+// Returns the level at *time* along the (sorted) *keyframes*
+fn interpolate(keyframes: &[(Duration, f64)], time: Duration, easing: Easing) -> f64 {
+    let next = keyframes.iter().position(|&(at, _)| at > time);
+    match next {
+        None => keyframes.last().map_or(0.0, |&(_, level)| level),
+        Some(0) => keyframes[0].1,
+        Some(next) => {
+            let ((start, from), (end, to)) = (keyframes[next - 1], keyframes[next]);
+            let progress = (time - start).as_secs_f64() / (end - start).as_secs_f64();
+            from + (to - from) * easing.apply(progress)
+        },
+    }
+}
+
+// An animation that's been given a name along with when it was started (if
+// it's running)
+#[derive(Debug)]
+struct Entry {
+    animation: Animation,
+    started: Option<Instant>,
+}
+
+/// Every animation that's been added to a `Shifter` and when the next frame
+/// of them is due.
+#[derive(Debug)]
+pub(crate) struct Animations {
+    entries: HashMap<String, Entry>,
+    period: Duration,
+    next: Instant,
+}
+
+impl Animations {
+
+    pub(crate) fn new() -> Animations {
+        Animations {
+            entries: HashMap::new(),
+            period: Duration::from_millis(20), // 50 frames per second
+            next: Instant::now(),
+        }
+    }
+
+    pub(crate) fn stop_all(&mut self) {
+        for entry in self.entries.values_mut() {
+            entry.started = None;
+        }
+    }
+
+    // Returns true if any animation is running
+    fn is_running(&self) -> bool {
+        self.entries.values().any(|entry| entry.started.is_some())
+    }
+}
+
+impl Shifter {
+
+    /// Adds an `Animation` with the given *name* (replacing any other
+    /// animation with that name).  It doesn't do anything until it's started
+    /// with `start_animation()`.  Returns an error if the animation's target
+    /// doesn't exist.
+    pub fn add_animation(&mut self, name: &str, animation: Animation) -> Result<(), ShifterError> {
+        self.target_pins(&animation.target)?;
+        let entry = Entry { animation: animation, started: None };
+        self.animations.entries.insert(name.to_string(), entry);
+        Ok(())
+    }
+
+    /// Starts (or restarts from the beginning) the animation with the given
+    /// *name*.  Running animations get updated (and applied) every time
+    /// `tick()` is called, by the refresh thread if the chain is being
+    /// refreshed (see `start_refresh()`), or by the `Shifter`'s own thread if
+    /// it has one (see `spawn()`).
+    ///
+    /// Returns a `ShifterError::UnknownAnimation` if there's no such
+    /// animation.
+    pub fn start_animation(&mut self, name: &str) -> Result<(), ShifterError> {
+        let now = Instant::now();
+        self.animation_mut(name)?.started = Some(now);
+        self.animations.next = now; // Show the first frame right away
+        Ok(())
+    }
+
+    /// Stops the animation with the given *name*, leaving its pins at
+    /// whatever level they're at.  Returns a
+    /// `ShifterError::UnknownAnimation` if there's no such animation.
+    pub fn stop_animation(&mut self, name: &str) -> Result<(), ShifterError> {
+        self.animation_mut(name)?.started = None;
+        Ok(())
+    }
+
+    /// Removes (and returns) the animation with the given *name*.
+    pub fn remove_animation(&mut self, name: &str) -> Option<Animation> {
+        self.animations.entries.remove(name).map(|entry| entry.animation)
+    }
+
+    /// Returns `true` if the animation with the given *name* is running (it
+    /// was started and hasn't been stopped or finished).
+    pub fn is_animating(&self, name: &str) -> bool {
+        self.animations.entries.get(name).is_some_and(|entry| entry.started.is_some())
+    }
+
+    /// Sets how many times per second running animations get updated (50 by
+    /// default).  Returns a `ShifterError::InvalidRate` if *fps* isn't a
+    /// positive number.
+    pub fn set_animation_fps(&mut self, fps: f64) -> Result<(), ShifterError> {
+        self.animations.period = period_from_hz(fps)?;
+        Ok(())
+    }
+
+    /// Returns when the next frame of the running animations is due (or
+    /// `None` if none are running).
+    pub fn next_animation_due(&self) -> Option<Instant> {
+        if self.animations.is_running() {
+            Some(self.animations.next)
+        } else {
+            None
+        }
+    }
+
+    // Updates the pins of every running animation (without applying) if the
+    // next frame is due as of *now*.  Returns true if anything was updated.
+    pub(crate) fn animate(&mut self, now: Instant) -> bool {
+        if self.next_animation_due().is_none_or(|due| due > now) {
+            return false;
+        }
+        self.animations.next += self.animations.period;
+        if self.animations.next <= now {
+            self.animations.next = now + self.animations.period; // Fell behind
+        }
+        let mut entries = std::mem::take(&mut self.animations.entries);
+        for entry in entries.values_mut() {
+            let started = match entry.started {
+                Some(started) => started,
+                None => continue,
+            };
+            let elapsed = now - started;
+            let pins = match self.target_pins(&entry.animation.target) {
+                Ok(pins) => pins,
+                Err(_) => {
+                    // Its target was removed out from under it
+                    entry.started = None;
+                    continue;
+                },
+            };
+            for (index, (sr, pin)) in pins.into_iter().enumerate() {
+                let level = entry.animation.level(elapsed, index);
+                let _ = self.set_brightness(sr, pin, level);
+            }
+            if entry.animation.duration().is_some_and(|duration| elapsed >= duration) {
+                entry.started = None; // Finished (having set the final levels)
+            }
+        }
+        self.animations.entries = entries;
+        true
+    }
+
+    fn animation_mut(&mut self, name: &str) -> Result<&mut Entry, ShifterError> {
+        match self.animations.entries.get_mut(name) {
+            Some(entry) => Ok(entry),
+            None => Err(ShifterError::UnknownAnimation(name.to_string())),
+        }
+    }
+
+    // Returns every pin the given *target* refers to
+    fn target_pins(&self, target: &Target) -> Result<Vec<(RegisterHandle, u8)>, ShifterError> {
+        match *target {
+            Target::Pin(sr, pin) => {
+                self.register(sr)?.check_pin(pin)?;
+                Ok(vec![(sr, pin)])
+            },
+            Target::Named(ref name) => self.lookup_name(name).map(|pin| vec![pin]),
+            Target::Group(ref name) => self.lookup_group(name),
+            Target::Register(sr) => {
+                let pins = self.register(sr)?.pins;
+                Ok((0..pins).map(|pin| (sr, pin)).collect())
+            },
+        }
+    }
+}
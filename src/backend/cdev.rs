@@ -0,0 +1,85 @@
+//! A backend built on the Linux GPIO character device (`/dev/gpiochipN`) via
+//! [gpio-cdev](https://crates.io/crates/gpio-cdev).  Unlike sysfs and
+//! `/dev/mem` this is the interface the kernel folks want everyone to use so
+//! it works on just about any Linux board.  Enabled via the `gpio-cdev`
+//! feature.
+//!
+//! This is also how a `Shifter` works on the Raspberry Pi 5:  Its GPIO pins
+//! hang off the RP1 chip (rather than the SoC) so CuPi's `/dev/mem` tricks
+//! (and `/dev/gpiomem`) don't work there.  See `Shifter::with_bcm_pins()`.
+
+use std::io;
+use std::path::Path;
+use gpio_cdev::{self, Chip, LineHandle, LineRequestFlags};
+use {Shifter, ShifterError, OutputPin};
+
+// What shows up as the "consumer" of our lines in tools like gpioinfo:
+pub(crate) const CONSUMER: &str = "cupi_shift";
+
+// Labels of the GPIO chips that drive the 40-pin header on the various
+// Raspberry Pi models:  The RP1 on the Pi 5 (gpiochip4 or gpiochip0 depending
+// on the kernel) and the SoC itself on everything before it.
+const HEADER_CHIPS: &[&str] = &["pinctrl-rp1", "pinctrl-bcm2711", "pinctrl-bcm2835"];
+
+impl OutputPin for LineHandle {
+    fn set_high(&mut self) -> Result<(), ShifterError> {
+        self.set_value(1)?;
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), ShifterError> {
+        self.set_value(0)?;
+        Ok(())
+    }
+}
+
+impl From<gpio_cdev::Error> for ShifterError {
+    fn from(err: gpio_cdev::Error) -> ShifterError {
+        ShifterError::BackendError(Box::new(err))
+    }
+}
+
+impl Shifter {
+
+    /// Returns a new `Shifter` object that will shift out data using the given
+    /// *data_line*, *latch_line*, and *clock_line* on the GPIO character device
+    /// at *chip* (e.g. `/dev/gpiochip0`).  The line numbers are the offsets
+    /// shown by `gpioinfo` (on a Raspberry Pi those are the BCM GPIO numbers).
+    ///
+    /// ```
+    /// let mut shifter = Shifter::with_gpio_cdev("/dev/gpiochip0", 21, 20, 16)?;
+    /// ```
+    ///
+    /// Returns a `ShifterError::BackendError` if the chip can't be opened or
+    /// any of the lines are already in use.
+    pub fn with_gpio_cdev<P: AsRef<Path>>(chip: P, data_line: u32, latch_line: u32, clock_line: u32) -> Result<Shifter, ShifterError> {
+        Shifter::from_chip(Chip::new(chip)?, data_line, latch_line, clock_line)
+    }
+
+    /// Just like `with_gpio_cdev()` but figures out which GPIO chip drives the
+    /// Raspberry Pi's 40-pin header by itself so the same code works on every
+    /// model, including the Pi 5.  The pins are BCM GPIO numbers:
+    ///
+    /// ```
+    /// let mut shifter = Shifter::with_bcm_pins(21, 20, 16)?;
+    /// ```
+    ///
+    /// Returns a `ShifterError::IoError` (`NotFound`) if this doesn't look like
+    /// a Raspberry Pi.
+    pub fn with_bcm_pins(data_pin: u32, latch_pin: u32, clock_pin: u32) -> Result<Shifter, ShifterError> {
+        for chip in gpio_cdev::chips()? {
+            let chip = chip?;
+            if HEADER_CHIPS.contains(&chip.label()) {
+                return Shifter::from_chip(chip, data_pin, latch_pin, clock_pin);
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::NotFound, "No Raspberry Pi GPIO chip found").into())
+    }
+
+    fn from_chip(mut chip: Chip, data_line: u32, latch_line: u32, clock_line: u32) -> Result<Shifter, ShifterError> {
+        let mut output = |line: u32| -> Result<LineHandle, ShifterError> {
+            Ok(chip.get_line(line)?.request(LineRequestFlags::OUTPUT, 0, CONSUMER)?)
+        };
+        Ok(Shifter::from_output_pins(output(data_line)?, output(latch_line)?, output(clock_line)?))
+    }
+}
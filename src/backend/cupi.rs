@@ -0,0 +1,72 @@
+//! The default backend:  CuPi's `PinOutput` (memory-mapped via `/dev/mem`) or
+//! its sysfs-based equivalent.
+
+use cupi::{self, sys, CuPi, PinOutput, DigitalWrite};
+use {OutputPin, ShifterError};
+
+/// How CuPi should go about toggling the data, latch, and clock pins.  See
+/// `Shifter::try_new_with()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpioAccess {
+    /// Write directly to the GPIO registers via `/dev/mem` (the default).  By
+    /// far the fastest but requires root.
+    Mmap,
+    /// Go through the kernel's sysfs interface (`/sys/class/gpio`).  Much
+    /// slower but works in containers (Docker, balena, etc) where only sysfs
+    /// GPIO is exposed.  If we're not running as root the pins have to have
+    /// been exported already (e.g. by the host).
+    Sysfs,
+}
+
+// A memory-mapped CuPi pin.  PinOutput isn't Send because the mapping it
+// shares with the other pins holds a raw pointer but that pointer is only
+// ever used behind CuPi's own Mutex (and the mapping is valid process-wide) so
+// it's fine to use from whichever thread owns the Shifter.
+pub(crate) struct MmapPin(pub(crate) PinOutput);
+
+unsafe impl Send for MmapPin {}
+
+impl OutputPin for MmapPin {
+    fn set_high(&mut self) -> Result<(), ShifterError> {
+        self.0.high()?;
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), ShifterError> {
+        self.0.low()?;
+        Ok(())
+    }
+}
+
+// A pin being controlled via sysfs.  The sys::Pin has to stick around because
+// it unexports the pin (if we exported it) when dropped.
+pub(crate) struct SysfsPin {
+    output: sys::PinOutput,
+    _pin: sys::Pin,
+}
+
+impl SysfsPin {
+
+    pub(crate) fn new(cupi: &CuPi, pin: usize) -> Result<SysfsPin, ShifterError> {
+        let mut sys_pin = cupi.pin_sys(pin)?;
+        if cupi::is_root() {
+            sys_pin.export()?;
+        }
+        Ok(SysfsPin {
+            output: sys_pin.output()?,
+            _pin: sys_pin,
+        })
+    }
+}
+
+impl OutputPin for SysfsPin {
+    fn set_high(&mut self) -> Result<(), ShifterError> {
+        self.output.high()?;
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), ShifterError> {
+        self.output.low()?;
+        Ok(())
+    }
+}
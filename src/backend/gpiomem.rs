@@ -0,0 +1,111 @@
+//! Bit-banging by writing straight to the BCM283x GPIO registers via
+//! `/dev/gpiomem`.  Setting or clearing a pin is a single store to the
+//! GPSET/GPCLR registers with no system call involved so this is about as fast
+//! as bit-banging gets (handy for long chains refreshed at display rates).
+//! Unlike `/dev/mem` it doesn't require root; just membership in the `gpio`
+//! group.  Only works on the Raspberry Pi models up to and including the 4.
+//! Enabled via the `gpiomem` feature.
+
+use std::fs::OpenOptions;
+use std::io;
+use std::ptr;
+use std::sync::Arc;
+use memmap2::{MmapMut, MmapOptions};
+use {Shifter, ShifterError, OutputPin};
+
+const GPFSEL0: usize = 0x00; // Function select; 3 bits per pin, 10 pins each
+const GPSET0: usize = 0x1c;
+const GPCLR0: usize = 0x28;
+const GPIO_COUNT: u8 = 54;
+
+// The mapped GPIO registers (shared by all the pins)
+struct GpioMem {
+    base: *mut u32,
+    _map: MmapMut, // Keeps base valid
+}
+
+// The mapping is valid from any thread and every access is a single volatile
+// load or store of a whole register:
+unsafe impl Send for GpioMem {}
+unsafe impl Sync for GpioMem {}
+
+impl GpioMem {
+
+    fn open() -> Result<GpioMem, ShifterError> {
+        let file = OpenOptions::new().read(true).write(true).open("/dev/gpiomem")?;
+        let mut map = unsafe { MmapOptions::new().len(4096).map_mut(&file)? };
+        Ok(GpioMem {
+            base: map.as_mut_ptr() as *mut u32,
+            _map: map,
+        })
+    }
+
+    fn read(&self, offset: usize) -> u32 {
+        unsafe { ptr::read_volatile(self.base.add(offset / 4)) }
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        unsafe { ptr::write_volatile(self.base.add(offset / 4), value) }
+    }
+}
+
+// A pin being controlled via the mapped registers
+pub(crate) struct GpioMemPin {
+    gpio: Arc<GpioMem>,
+    bank: usize,
+    mask: u32,
+}
+
+impl GpioMemPin {
+
+    // Configures *pin* (a BCM GPIO number) as an output
+    fn new(gpio: &Arc<GpioMem>, pin: u8) -> Result<GpioMemPin, ShifterError> {
+        if pin >= GPIO_COUNT {
+            let msg = format!("GPIO {} does not exist", pin);
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, msg).into());
+        }
+        let fsel = GPFSEL0 + (pin as usize / 10) * 4;
+        let shift = (pin as u32 % 10) * 3;
+        gpio.write(fsel, (gpio.read(fsel) & !(0b111 << shift)) | (0b001 << shift));
+        Ok(GpioMemPin {
+            gpio: gpio.clone(),
+            bank: pin as usize / 32 * 4,
+            mask: 1 << (pin % 32),
+        })
+    }
+}
+
+impl OutputPin for GpioMemPin {
+    fn set_high(&mut self) -> Result<(), ShifterError> {
+        self.gpio.write(GPSET0 + self.bank, self.mask);
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), ShifterError> {
+        self.gpio.write(GPCLR0 + self.bank, self.mask);
+        Ok(())
+    }
+}
+
+impl Shifter {
+
+    /// Returns a new `Shifter` object that will shift out data by writing
+    /// directly to the GPIO registers via `/dev/gpiomem` (see the `gpiomem`
+    /// feature).  Like rppal and gpio-cdev this uses BCM GPIO numbers rather
+    /// than WiringPi numbers:
+    ///
+    /// ```
+    /// let mut shifter = Shifter::with_gpiomem(21, 20, 16)?;
+    /// ```
+    ///
+    /// Returns a `ShifterError::IoError` if `/dev/gpiomem` can't be opened or
+    /// any of the pins don't exist.
+    pub fn with_gpiomem(data_pin: u8, latch_pin: u8, clock_pin: u8) -> Result<Shifter, ShifterError> {
+        let gpio = Arc::new(GpioMem::open()?);
+        Ok(Shifter::from_output_pins(
+            GpioMemPin::new(&gpio, data_pin)?,
+            GpioMemPin::new(&gpio, latch_pin)?,
+            GpioMemPin::new(&gpio, clock_pin)?,
+        ))
+    }
+}
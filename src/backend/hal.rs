@@ -0,0 +1,53 @@
+//! Support for any pin that implements [embedded-hal]'s `OutputPin` trait so
+//! the same `Shifter` can drive a chain from any board with an embedded-hal
+//! implementation.  Enabled via the `embedded-hal` feature.
+//!
+//! [embedded-hal]: https://crates.io/crates/embedded-hal
+
+use embedded_hal::digital;
+use {Shifter, ShifterError, OutputPin};
+
+/// Wraps an embedded-hal `OutputPin` so it can be used as a `Shifter`'s data,
+/// latch, or clock pin.  You normally won't need this directly; see
+/// `Shifter::from_hal_pins()`.
+#[derive(Debug)]
+pub struct HalPin<P>(pub P);
+
+impl<P: digital::OutputPin + Send> OutputPin for HalPin<P> {
+    fn set_high(&mut self) -> Result<(), ShifterError> {
+        self.0.set_high().map_err(hal_error)
+    }
+
+    fn set_low(&mut self) -> Result<(), ShifterError> {
+        self.0.set_low().map_err(hal_error)
+    }
+}
+
+// embedded-hal errors only have to implement Debug so that's all we can keep
+fn hal_error<E: digital::Error>(err: E) -> ShifterError {
+    ShifterError::BackendError(format!("{:?} ({:?})", err, err.kind()).into())
+}
+
+impl Shifter {
+
+    /// Returns a new `Shifter` object that will shift out data using the given
+    /// embedded-hal *data*, *latch*, and *clock* pins:
+    ///
+    /// ```
+    /// let mut shifter = Shifter::from_hal_pins(
+    ///     pins.gpio21.into_push_pull_output(),
+    ///     pins.gpio20.into_push_pull_output(),
+    ///     pins.gpio16.into_push_pull_output(),
+    /// );
+    /// ```
+    ///
+    /// Any errors returned by the pins end up as a
+    /// `ShifterError::BackendError`.
+    pub fn from_hal_pins<D, L, C>(data: D, latch: L, clock: C) -> Shifter
+        where D: digital::OutputPin + Send + 'static,
+              L: digital::OutputPin + Send + 'static,
+              C: digital::OutputPin + Send + 'static
+    {
+        Shifter::from_output_pins(HalPin(data), HalPin(latch), HalPin(clock))
+    }
+}
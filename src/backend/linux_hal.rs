@@ -0,0 +1,54 @@
+//! Support for [linux-embedded-hal](https://crates.io/crates/linux-embedded-hal)'s
+//! `CdevPin` so folks already using the embedded-hal ecosystem on Linux can
+//! hand their pins straight to a `Shifter`.  Enabled via the
+//! `linux-embedded-hal` feature.
+
+use std::path::Path;
+use embedded_hal_02::digital::v2::OutputPin as OutputPin02;
+use gpio_cdev::{Chip, LineRequestFlags};
+use linux_embedded_hal::CdevPin;
+use backend::cdev::CONSUMER;
+use {Shifter, ShifterError, OutputPin};
+
+// Goes through CdevPin's embedded-hal implementation (rather than straight to
+// the LineHandle inside) so that active-low lines are handled correctly:
+impl OutputPin for CdevPin {
+    fn set_high(&mut self) -> Result<(), ShifterError> {
+        OutputPin02::set_high(self)?;
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), ShifterError> {
+        OutputPin02::set_low(self)?;
+        Ok(())
+    }
+}
+
+impl Shifter {
+
+    /// Returns a new `Shifter` object that will shift out data using the given
+    /// linux-embedded-hal *data*, *latch*, and *clock* pins:
+    ///
+    /// ```
+    /// let mut chip = Chip::new("/dev/gpiochip0")?;
+    /// let pin = |line| -> Result<CdevPin, gpio_cdev::Error> {
+    ///     CdevPin::new(chip.get_line(line)?.request(LineRequestFlags::OUTPUT, 0, "my-app")?)
+    /// };
+    /// let mut shifter = Shifter::from_cdev_pins(pin(21)?, pin(20)?, pin(16)?);
+    /// ```
+    pub fn from_cdev_pins(data: CdevPin, latch: CdevPin, clock: CdevPin) -> Shifter {
+        Shifter::from_output_pins(data, latch, clock)
+    }
+
+    /// Just like `with_gpio_cdev()` but wraps each line in a `CdevPin` (which
+    /// takes care of active-low lines).  Handy if you want to be consistent
+    /// with the rest of your linux-embedded-hal code.
+    pub fn with_cdev_pins<P: AsRef<Path>>(chip: P, data_line: u32, latch_line: u32, clock_line: u32) -> Result<Shifter, ShifterError> {
+        let mut chip = Chip::new(chip)?;
+        let mut output = |line: u32| -> Result<CdevPin, ShifterError> {
+            let handle = chip.get_line(line)?.request(LineRequestFlags::OUTPUT, 0, CONSUMER)?;
+            Ok(CdevPin::new(handle)?)
+        };
+        Ok(Shifter::from_cdev_pins(output(data_line)?, output(latch_line)?, output(clock_line)?))
+    }
+}
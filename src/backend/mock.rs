@@ -0,0 +1,288 @@
+//! A pretend chain of shift registers for testing code that uses a `Shifter`
+//! (or a `ShifterIn`) without any hardware (e.g. on CI).  See
+//! `Shifter::mock()` and `ShifterIn::mock()`.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use {Shifter, ShifterIn, ShifterError, OutputPin, InputPin, Signal};
+
+#[derive(Debug, Default)]
+struct MockState {
+    data: bool,
+    clock: bool,
+    latch: bool,
+    shifted: Vec<bool>, // Bits clocked in since the last latch
+    outputs: Vec<bool>,
+    frames: Vec<Vec<bool>>,
+}
+
+impl MockState {
+
+    // Called on the rising edge of the latch:  The bits that were clocked in
+    // push whatever was already in the chain towards the far end (and off it)
+    fn latch(&mut self) {
+        let count = self.shifted.len();
+        if count >= self.outputs.len() {
+            self.outputs = std::mem::take(&mut self.shifted);
+        } else {
+            self.outputs.drain(..count);
+            self.outputs.append(&mut self.shifted);
+        }
+        self.frames.push(self.outputs.clone());
+    }
+}
+
+/// Keeps track of what a `Shifter` created via `Shifter::mock()` would have
+/// done to a real chain of shift registers so you can make assertions about
+/// it:
+///
+/// ```
+/// let (mut shifter, mock) = Shifter::mock();
+/// let sr0 = shifter.add(8);
+/// shifter.set(sr0, 0b00000101, Apply::Now).unwrap();
+/// assert_eq!(mock.output(0), Some(true));
+/// assert_eq!(mock.output(1), Some(false));
+/// assert_eq!(mock.apply_count(), 1);
+/// ```
+///
+/// Outputs are numbered in the order their bits were shifted out which (with
+/// the default `Order` and `BitOrder`) is the same as `Shifter.set_chain_pin()`
+/// uses:  Pin 0 of the first shift register added is output 0.  Since this is
+/// what the hardware would actually see, inversion and remapping are taken
+/// into account.
+#[derive(Debug, Clone, Default)]
+pub struct MockChain(Arc<Mutex<MockState>>);
+
+impl MockChain {
+
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns the state of every output as of the last `apply()`.
+    pub fn outputs(&self) -> Vec<bool> {
+        self.state().outputs.clone()
+    }
+
+    /// Returns the state of the given output as of the last `apply()` (or
+    /// `None` if it hasn't been shifted out yet).
+    pub fn output(&self, index: usize) -> Option<bool> {
+        self.state().outputs.get(index).cloned()
+    }
+
+    /// Returns the state of every output after each `apply()` (oldest first).
+    pub fn frames(&self) -> Vec<Vec<bool>> {
+        self.state().frames.clone()
+    }
+
+    /// Returns the number of times the outputs have been latched (i.e. the
+    /// number of `apply()` calls).
+    pub fn apply_count(&self) -> usize {
+        self.state().frames.len()
+    }
+
+    /// Forgets about every frame recorded so far (but not the current state of
+    /// the outputs).
+    pub fn clear_frames(&self) {
+        self.state().frames.clear();
+    }
+}
+
+// One of the three pins given to a mocked Shifter
+pub(crate) struct MockPin(pub(crate) MockChain, pub(crate) Signal);
+
+impl OutputPin for MockPin {
+    fn set_high(&mut self) -> Result<(), ShifterError> {
+        self.set_state(true)
+    }
+
+    fn set_low(&mut self) -> Result<(), ShifterError> {
+        self.set_state(false)
+    }
+
+    fn set_state(&mut self, high: bool) -> Result<(), ShifterError> {
+        let mut state = self.0.state();
+        match self.1 {
+            Signal::Data => state.data = high,
+            Signal::Clock => {
+                if high && !state.clock {
+                    let data = state.data;
+                    state.shifted.push(data);
+                }
+                state.clock = high;
+            },
+            Signal::Latch => {
+                if high && !state.latch {
+                    state.latch();
+                }
+                state.latch = high;
+            },
+        }
+        Ok(())
+    }
+}
+
+impl Shifter {
+
+    /// Returns a new `Shifter` object that isn't connected to any hardware
+    /// along with a `MockChain` that keeps track of what would've been shifted
+    /// out.  Use this for testing your code without a Raspberry Pi.
+    pub fn mock() -> (Shifter, MockChain) {
+        let mock = MockChain::default();
+        let shifter = Shifter::from_output_pins(
+            MockPin(mock.clone(), Signal::Data),
+            MockPin(mock.clone(), Signal::Latch),
+            MockPin(mock.clone(), Signal::Clock),
+        );
+        (shifter, mock)
+    }
+
+    /// Returns a new mocked `Shifter` (see `mock()`) with input shift
+    /// registers attached (see `attach_inputs()`) that share its clock along
+    /// with a `MockChain` for the outputs and a `MockInputs` for the inputs.
+    pub fn mock_with_inputs() -> (Shifter, MockChain, MockInputs) {
+        let mock = MockChain::default();
+        let mock_inputs = MockInputs::default();
+        let mut shifter = Shifter::from_output_pins(
+            MockPin(mock.clone(), Signal::Data),
+            MockPin(mock.clone(), Signal::Latch),
+            MockSharedClock(
+                MockPin(mock.clone(), Signal::Clock),
+                MockInputPin(mock_inputs.clone(), Signal::Clock),
+            ),
+        );
+        shifter.attach_inputs(
+            MockInputPin(mock_inputs.clone(), Signal::Data),
+            MockInputPin(mock_inputs.clone(), Signal::Latch),
+        );
+        (shifter, mock, mock_inputs)
+    }
+}
+
+// The clock pin of a mocked Shifter whose inputs share its clock
+struct MockSharedClock(MockPin, MockInputPin);
+
+impl OutputPin for MockSharedClock {
+    fn set_high(&mut self) -> Result<(), ShifterError> {
+        self.set_state(true)
+    }
+
+    fn set_low(&mut self) -> Result<(), ShifterError> {
+        self.set_state(false)
+    }
+
+    fn set_state(&mut self, high: bool) -> Result<(), ShifterError> {
+        self.0.set_state(high)?;
+        self.1.set_state(high)
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockInputState {
+    inputs: Vec<bool>,
+    shifting: Vec<bool>, // What's in the shift registers (QH being the last)
+    load: bool,
+    clock: bool,
+}
+
+/// A pretend chain of input shift registers (e.g. 74HC165s) for testing code
+/// that uses a `ShifterIn` (see `ShifterIn::mock()`):
+///
+/// ```
+/// let (mut inputs, mock) = ShifterIn::mock();
+/// let buttons = inputs.add(8);
+/// mock.set_input(3, true);
+/// inputs.read().unwrap();
+/// assert_eq!(inputs.get(buttons).unwrap(), 0b00001000);
+/// ```
+///
+/// Inputs are numbered the same way `ShifterIn.get_chain_pin()` numbers them
+/// with the default `Order`:  Pin 0 of the first shift register added is
+/// input 0.  Inputs that were never set are LOW.
+#[derive(Debug, Clone, Default)]
+pub struct MockInputs(Arc<Mutex<MockInputState>>);
+
+impl MockInputs {
+
+    fn state(&self) -> MutexGuard<'_, MockInputState> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Sets the level of the given input (as of the next `read()`).
+    pub fn set_input(&self, index: usize, high: bool) {
+        let mut state = self.state();
+        if index >= state.inputs.len() {
+            state.inputs.resize(index + 1, false);
+        }
+        state.inputs[index] = high;
+    }
+
+    /// Sets the level of every input (as of the next `read()`).
+    pub fn set_inputs(&self, inputs: &[bool]) {
+        self.state().inputs = inputs.to_vec();
+    }
+
+    /// Returns the level of every input.
+    pub fn inputs(&self) -> Vec<bool> {
+        self.state().inputs.clone()
+    }
+}
+
+// One of the pins given to a mocked ShifterIn (Signal::Latch being the load
+// pin)
+pub(crate) struct MockInputPin(pub(crate) MockInputs, pub(crate) Signal);
+
+impl OutputPin for MockInputPin {
+    fn set_high(&mut self) -> Result<(), ShifterError> {
+        self.set_state(true)
+    }
+
+    fn set_low(&mut self) -> Result<(), ShifterError> {
+        self.set_state(false)
+    }
+
+    fn set_state(&mut self, high: bool) -> Result<(), ShifterError> {
+        let mut state = self.0.state();
+        match self.1 {
+            Signal::Latch => {
+                if !high {
+                    // Parallel load (active LOW)
+                    state.shifting = state.inputs.clone();
+                }
+                state.load = high;
+            },
+            Signal::Clock => {
+                if high && !state.clock && state.load {
+                    // Everything moves one place towards QH (with the serial
+                    // input of the last shift register tied LOW)
+                    state.shifting.pop();
+                    state.shifting.insert(0, false);
+                }
+                state.clock = high;
+            },
+            Signal::Data => {},
+        }
+        Ok(())
+    }
+}
+
+impl InputPin for MockInputPin {
+    fn is_high(&mut self) -> Result<bool, ShifterError> {
+        Ok(self.0.state().shifting.last().cloned().unwrap_or(false))
+    }
+}
+
+impl ShifterIn {
+
+    /// Returns a new `ShifterIn` object that isn't connected to any hardware
+    /// along with a `MockInputs` for setting the levels of its inputs.  Use
+    /// this for testing your code without a Raspberry Pi.
+    pub fn mock() -> (ShifterIn, MockInputs) {
+        let mock = MockInputs::default();
+        let inputs = ShifterIn::from_pins(
+            MockInputPin(mock.clone(), Signal::Data),
+            MockInputPin(mock.clone(), Signal::Latch),
+            MockInputPin(mock.clone(), Signal::Clock),
+        );
+        (inputs, mock)
+    }
+}
@@ -0,0 +1,102 @@
+//! Pluggable GPIO backends for driving the data, latch, and clock lines.  See
+//! `OutputPin` and `Shifter::from_output_pins()`.
+
+use ShifterError;
+
+#[cfg(feature = "cupi")]
+pub use self::cupi::GpioAccess;
+pub use self::mock::{MockChain, MockInputs};
+pub use self::recording::{Recording, Signal, Event};
+#[cfg(feature = "cupi")]
+pub(crate) use self::cupi::{MmapPin, SysfsPin};
+
+#[cfg(feature = "cupi")]
+mod cupi;
+mod mock;
+mod recording;
+mod vcd;
+#[cfg(feature = "rppal")]
+mod rppal;
+#[cfg(feature = "gpio-cdev")]
+mod cdev;
+#[cfg(feature = "embedded-hal")]
+mod hal;
+#[cfg(feature = "linux-embedded-hal")]
+mod linux_hal;
+#[cfg(feature = "spidev")]
+mod spi;
+#[cfg(feature = "gpiomem")]
+mod gpiomem;
+#[cfg(feature = "terminal")]
+mod terminal;
+
+#[cfg(feature = "embedded-hal")]
+pub use self::hal::HalPin;
+
+/// A GPIO pin that a `Shifter` can drive HIGH or LOW.  Implement this for
+/// whatever GPIO library you're using and pass your pins to
+/// `Shifter::from_output_pins()`:
+///
+/// ```
+/// struct MyPin(my_gpio::Pin);
+///
+/// impl OutputPin for MyPin {
+///     fn set_high(&mut self) -> Result<(), ShifterError> {
+///         self.0.write(1);
+///         Ok(())
+///     }
+///     fn set_low(&mut self) -> Result<(), ShifterError> {
+///         self.0.write(0);
+///         Ok(())
+///     }
+/// }
+/// ```
+///
+/// Pins have to be `Send` so that a `Shifter` can be handed to another thread
+/// (see `Shifter.start_refresh()`).  CuPi's `PinOutput` is taken care of by
+/// `Shifter::from_pins()` (it's what `Shifter::new()` uses).
+pub trait OutputPin: Send {
+    /// Drives the pin HIGH.
+    fn set_high(&mut self) -> Result<(), ShifterError>;
+
+    /// Drives the pin LOW.
+    fn set_low(&mut self) -> Result<(), ShifterError>;
+
+    /// Drives the pin HIGH (if *high* is `true`) or LOW (if *high* is
+    /// `false`).
+    fn set_state(&mut self, high: bool) -> Result<(), ShifterError> {
+        if high { self.set_high() } else { self.set_low() }
+    }
+}
+
+/// A GPIO pin that a `ShifterIn` can read (the serial output of an input
+/// shift register).  Implement this for whatever GPIO library you're using
+/// and pass your pin to `ShifterIn::from_pins()`.
+pub trait InputPin: Send {
+    /// Returns `true` if the pin is HIGH.
+    fn is_high(&mut self) -> Result<bool, ShifterError>;
+}
+
+/// A GPIO pin driven by a hardware PWM peripheral.  Used for dimming the
+/// whole chain via its output enable pin (see
+/// `Shifter.set_output_enable_pwm()`).
+pub trait PwmPin: Send {
+    /// Sets the fraction of each PWM period (0.0 to 1.0) the pin is HIGH for.
+    fn set_duty_cycle(&mut self, duty: f64) -> Result<(), ShifterError>;
+}
+
+/// A faster way of getting the bits out to the shift registers than toggling
+/// the data and clock pins one bit at a time (e.g. hardware SPI).  See
+/// `Shifter::from_bus()`.
+pub trait ShiftBus: Send {
+    /// Clocks out all of the given *bytes*, most significant bit first.
+    fn write(&mut self, bytes: &[u8]) -> Result<(), ShifterError>;
+}
+
+// Stands in for the data and clock pins when a ShiftBus is doing their job
+pub(crate) struct NullPin;
+
+impl OutputPin for NullPin {
+    fn set_high(&mut self) -> Result<(), ShifterError> { Ok(()) }
+    fn set_low(&mut self) -> Result<(), ShifterError> { Ok(()) }
+}
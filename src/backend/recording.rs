@@ -0,0 +1,220 @@
+//! Records every transition of the data, latch, and clock pins so the
+//! waveforms `apply()` produces can be checked.  See `Shifter::recording()`.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use {Shifter, ShifterError, OutputPin};
+
+/// One of the three lines that run from the Raspberry Pi to the chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Signal {
+    /// The data (aka serial) line.
+    Data,
+    /// The latch (aka storage register clock) line.
+    Latch,
+    /// The clock (aka shift register clock) line.
+    Clock,
+}
+
+/// A single transition recorded by a `Recording`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Event {
+    /// When the transition happened (relative to when recording started).
+    pub time: Duration,
+    /// The line that changed.
+    pub signal: Signal,
+    /// The level the line changed to (`true` for HIGH).
+    pub high: bool,
+}
+
+#[derive(Debug)]
+struct RecordingState {
+    start: Instant,
+    levels: [Option<bool>; 3], // Data, Latch, Clock (None until first set)
+    initial: [Option<bool>; 3], // The levels before the first event
+    events: Vec<Event>,
+}
+
+/// A timestamped log of every transition of the data, latch, and clock lines
+/// of a `Shifter` created via `Shifter::recording()`, along with some helpers
+/// for checking what it looks like:
+///
+/// ```
+/// let (mut shifter, recording) = Shifter::recording();
+/// let sr0 = shifter.add(8);
+/// shifter.set(sr0, 0b00000101, Apply::Now).unwrap();
+/// recording.assert_latch_pulses(1);
+/// assert_eq!(recording.frames(), vec![vec![true, false, true, false, false, false, false, false]]);
+/// ```
+///
+/// Only actual changes are recorded (setting a line that's already HIGH to
+/// HIGH again doesn't count) except for the first time each line is set.  Use
+/// `save_vcd()` to look at the waveforms in GTKWave or similar.
+#[derive(Debug, Clone)]
+pub struct Recording(Arc<Mutex<RecordingState>>);
+
+impl Recording {
+
+    fn new() -> Recording {
+        Recording(Arc::new(Mutex::new(RecordingState {
+            start: Instant::now(),
+            levels: [None; 3],
+            initial: [None; 3],
+            events: Vec::new(),
+        })))
+    }
+
+    fn state(&self) -> MutexGuard<'_, RecordingState> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn record(&self, signal: Signal, high: bool) {
+        let mut state = self.state();
+        let level = &mut state.levels[signal as usize];
+        if *level == Some(high) {
+            return;
+        }
+        *level = Some(high);
+        let time = state.start.elapsed();
+        state.events.push(Event { time: time, signal: signal, high: high });
+    }
+
+    /// Returns every event recorded so far (oldest first).
+    pub fn events(&self) -> Vec<Event> {
+        self.state().events.clone()
+    }
+
+    /// Forgets about every event recorded so far.
+    pub fn clear(&self) {
+        let mut state = self.state();
+        state.initial = state.levels;
+        state.events.clear();
+    }
+
+    // Returns the level of the given *signal* before the first event
+    fn initial(&self, signal: Signal) -> bool {
+        self.state().initial[signal as usize].unwrap_or(false)
+    }
+
+    /// Returns the number of times the given *signal* went from LOW to HIGH.
+    pub fn rising_edges(&self, signal: Signal) -> usize {
+        self.state().events.iter()
+            .filter(|event| event.signal == signal && event.high)
+            .count()
+    }
+
+    /// Returns the bits clocked in before each rising edge of the latch (i.e.
+    /// what each `apply()` shifted out, first bit first).
+    pub fn frames(&self) -> Vec<Vec<bool>> {
+        let mut frames = Vec::new();
+        let mut bits = Vec::new();
+        let mut data = self.initial(Signal::Data);
+        for event in self.state().events.iter() {
+            match (event.signal, event.high) {
+                (Signal::Data, high) => data = high,
+                (Signal::Clock, true) => bits.push(data),
+                (Signal::Latch, true) => frames.push(std::mem::take(&mut bits)),
+                _ => {},
+            }
+        }
+        frames
+    }
+
+    /// Panics unless the latch went HIGH exactly *count* times (e.g. once per
+    /// `apply()`).
+    pub fn assert_latch_pulses(&self, count: usize) {
+        let pulses = self.rising_edges(Signal::Latch);
+        assert_eq!(pulses, count, "Expected {} latch pulses but there were {}", count, pulses);
+    }
+
+    /// Panics if the clock ever went HIGH while the latch was HIGH (which
+    /// would mean bits were shifted outside of an `apply()`).
+    pub fn assert_clocked_while_latch_low(&self) {
+        let mut latch = self.initial(Signal::Latch);
+        for event in self.state().events.iter() {
+            match event.signal {
+                Signal::Latch => latch = event.high,
+                Signal::Clock if event.high => {
+                    assert!(!latch, "Clock went HIGH while the latch was HIGH at {:?}", event.time);
+                },
+                _ => {},
+            }
+        }
+    }
+
+    /// Panics if the data line ever changed while the clock was HIGH (the
+    /// shift register samples the data line on the clock's rising edge so it
+    /// should only change while the clock is LOW).
+    pub fn assert_data_stable_while_clock_high(&self) {
+        let mut clock = self.initial(Signal::Clock);
+        for event in self.state().events.iter() {
+            match event.signal {
+                Signal::Clock => clock = event.high,
+                Signal::Data => {
+                    assert!(!clock, "Data changed while the clock was HIGH at {:?}", event.time);
+                },
+                _ => {},
+            }
+        }
+    }
+}
+
+// One of the three pins given to a recording Shifter (optionally passing
+// everything through to a real pin)
+struct RecordingPin(Recording, Signal, Option<Box<dyn OutputPin>>);
+
+impl OutputPin for RecordingPin {
+    fn set_high(&mut self) -> Result<(), ShifterError> {
+        self.set_state(true)
+    }
+
+    fn set_low(&mut self) -> Result<(), ShifterError> {
+        self.set_state(false)
+    }
+
+    fn set_state(&mut self, high: bool) -> Result<(), ShifterError> {
+        if let Some(ref mut pin) = self.2 {
+            pin.set_state(high)?;
+        }
+        self.0.record(self.1, high);
+        Ok(())
+    }
+}
+
+impl Shifter {
+
+    /// Returns a new `Shifter` object that isn't connected to any hardware
+    /// along with a `Recording` of every transition of its data, latch, and
+    /// clock pins.  Use this to check the timing of what gets shifted out
+    /// (see also `Shifter::mock()` if you only care about the end result).
+    pub fn recording() -> (Shifter, Recording) {
+        let recording = Recording::new();
+        let shifter = Shifter::from_output_pins(
+            RecordingPin(recording.clone(), Signal::Data, None),
+            RecordingPin(recording.clone(), Signal::Latch, None),
+            RecordingPin(recording.clone(), Signal::Clock, None),
+        );
+        (shifter, recording)
+    }
+
+    /// Just like `Shifter::from_output_pins()` but also returns a `Recording`
+    /// of every transition of the given pins.  Handy for capturing what was
+    /// actually sent to a misbehaving chain:
+    ///
+    /// ```
+    /// let (mut shifter, recording) = Shifter::recorded(data_pin, latch_pin, clock_pin);
+    /// // ...
+    /// recording.save_vcd("shifter.vcd")?;
+    /// ```
+    pub fn recorded<D, L, C>(data: D, latch: L, clock: C) -> (Shifter, Recording)
+        where D: OutputPin + 'static, L: OutputPin + 'static, C: OutputPin + 'static
+    {
+        let recording = Recording::new();
+        let shifter = Shifter::from_output_pins(
+            RecordingPin(recording.clone(), Signal::Data, Some(Box::new(data))),
+            RecordingPin(recording.clone(), Signal::Latch, Some(Box::new(latch))),
+            RecordingPin(recording.clone(), Signal::Clock, Some(Box::new(clock))),
+        );
+        (shifter, recording)
+    }
+}
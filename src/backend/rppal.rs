@@ -0,0 +1,90 @@
+//! A backend built on [rppal](https://crates.io/crates/rppal) which supports
+//! newer Raspberry Pi models (3, 4, 400, etc) than CuPi does.  Enabled via the
+//! `rppal` feature.
+
+use rppal::gpio::{self, Gpio};
+use rppal::pwm::{self, Pwm};
+use {Shifter, ShifterIn, ShifterError, OutputPin, InputPin, PwmPin};
+
+impl OutputPin for gpio::OutputPin {
+    fn set_high(&mut self) -> Result<(), ShifterError> {
+        gpio::OutputPin::set_high(self);
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), ShifterError> {
+        gpio::OutputPin::set_low(self);
+        Ok(())
+    }
+}
+
+impl InputPin for gpio::InputPin {
+    fn is_high(&mut self) -> Result<bool, ShifterError> {
+        Ok(gpio::InputPin::is_high(self))
+    }
+}
+
+// Lets a hardware PWM channel drive the output enable pin
+impl PwmPin for Pwm {
+    fn set_duty_cycle(&mut self, duty: f64) -> Result<(), ShifterError> {
+        Pwm::set_duty_cycle(self, duty)?;
+        Ok(())
+    }
+}
+
+impl From<gpio::Error> for ShifterError {
+    fn from(err: gpio::Error) -> ShifterError {
+        ShifterError::BackendError(Box::new(err))
+    }
+}
+
+impl From<pwm::Error> for ShifterError {
+    fn from(err: pwm::Error) -> ShifterError {
+        ShifterError::BackendError(Box::new(err))
+    }
+}
+
+impl Shifter {
+
+    /// Returns a new `Shifter` object that will shift out data using the given
+    /// *data_pin*, *latch_pin*, and *clock_pin* via rppal instead of CuPi.
+    /// Note that rppal uses BCM GPIO numbers (e.g. GPIO 21 is pin 40 on the
+    /// header) rather than the WiringPi numbers CuPi uses.
+    ///
+    /// ```
+    /// let mut shifter = Shifter::with_rppal(21, 20, 16)?;
+    /// ```
+    ///
+    /// Returns a `ShifterError::BackendError` if the GPIO peripheral can't be
+    /// accessed or any of the pins are already in use.
+    pub fn with_rppal(data_pin: u8, latch_pin: u8, clock_pin: u8) -> Result<Shifter, ShifterError> {
+        let gpio = Gpio::new()?;
+        Ok(Shifter::from_output_pins(
+            gpio.get(data_pin)?.into_output(),
+            gpio.get(latch_pin)?.into_output(),
+            gpio.get(clock_pin)?.into_output(),
+        ))
+    }
+}
+
+impl ShifterIn {
+
+    /// Returns a new `ShifterIn` object that reads a chain of input shift
+    /// registers (e.g. 74HC165s) via rppal using the given *data_pin* (QH),
+    /// *load_pin* (SH/LD), and *clock_pin* (CLK) BCM GPIO numbers.
+    ///
+    /// ```
+    /// let mut inputs = ShifterIn::with_rppal(26, 19, 13)?;
+    /// ```
+    ///
+    /// Returns a `ShifterError::BackendError` if the GPIO peripheral can't be
+    /// accessed or any of the pins are already in use.
+    pub fn with_rppal(data_pin: u8, load_pin: u8, clock_pin: u8) -> Result<ShifterIn, ShifterError> {
+        let gpio = Gpio::new()?;
+        Ok(ShifterIn::from_pins(
+            gpio.get(data_pin)?.into_input(),
+            gpio.get(load_pin)?.into_output_high(),
+            gpio.get(clock_pin)?.into_output_low(),
+        ))
+    }
+}
@@ -0,0 +1,48 @@
+//! Hardware SPI via [spidev](https://crates.io/crates/spidev).  The 74HC595
+//! is basically an SPI peripheral so wiring its data and clock inputs to the
+//! SPI MOSI and SCLK pins lets the kernel's SPI driver shift things out at
+//! several MHz (bit-banging through userspace GPIO tops out in the tens of
+//! kHz).  The latch stays a regular GPIO pin.  Enabled via the `spidev`
+//! feature.
+
+use std::io::Write;
+use std::path::Path;
+use spidev::{Spidev, SpidevOptions, SpiModeFlags};
+use {Shifter, ShifterError, OutputPin, ShiftBus};
+
+impl ShiftBus for Spidev {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), ShifterError> {
+        self.write_all(bytes)?;
+        Ok(())
+    }
+}
+
+impl Shifter {
+
+    /// Returns a new `Shifter` object that will shift out data over the SPI
+    /// device at *path* (e.g. `/dev/spidev0.0`) clocked at *speed_hz* using
+    /// the given *latch* pin:
+    ///
+    /// ```
+    /// let cupi = CuPi::new().unwrap();
+    /// let latch = cupi.pin(28).unwrap().output();
+    /// let mut shifter = Shifter::with_spidev("/dev/spidev0.0", 8_000_000, latch)?;
+    /// ```
+    ///
+    /// Wire the shift register's data input to MOSI and its clock input to
+    /// SCLK.  Note that the kernel limits how much can be sent in one go
+    /// (4096 bytes by default; see the `bufsiz` parameter of the `spidev`
+    /// module) which works out to chains of up to 4096 8-pin shift registers.
+    pub fn with_spidev<P, L>(path: P, speed_hz: u32, latch: L) -> Result<Shifter, ShifterError>
+        where P: AsRef<Path>, L: OutputPin + 'static
+    {
+        let mut spi = Spidev::open(path)?;
+        let options = SpidevOptions::new()
+            .bits_per_word(8)
+            .max_speed_hz(speed_hz)
+            .mode(SpiModeFlags::SPI_MODE_0) // The 74HC595 samples on the rising edge
+            .build();
+        spi.configure(&options)?;
+        Ok(Shifter::from_bus(spi, latch))
+    }
+}
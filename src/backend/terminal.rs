@@ -0,0 +1,83 @@
+//! Draws the state of the chain in the terminal instead of (well, as well as
+//! pretending to) driving real shift registers.  Handy for working on
+//! animations on a laptop.  See `Shifter::terminal()`.  Enabled via the
+//! `terminal` feature.
+
+use std::io::{self, Write};
+use backend::mock::MockPin;
+use {Shifter, ShifterError, OutputPin, MockChain, Signal};
+
+const HIGH: &str = "\x1b[92m\u{2588}\x1b[0m"; // Bright green block
+const LOW: &str = "\x1b[90m\u{2588}\x1b[0m"; // Dark grey block
+
+// The latch pin of a terminal Shifter:  Redraws everything after every apply()
+struct TerminalLatch {
+    pin: MockPin,
+    width: usize,
+    rows: usize, // How many rows were drawn last time (so we can go back up)
+}
+
+impl TerminalLatch {
+
+    fn draw(&mut self) -> io::Result<()> {
+        let outputs = (self.pin.0).outputs();
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        if self.rows > 0 {
+            write!(out, "\x1b[{}A", self.rows)?; // Move the cursor back up
+        }
+        self.rows = 0;
+        for (row, chunk) in outputs.chunks(self.width).enumerate() {
+            write!(out, "{:4} ", row * self.width)?;
+            for &high in chunk {
+                out.write_all(if high { HIGH } else { LOW }.as_bytes())?;
+            }
+            writeln!(out, "\x1b[K")?; // Clear whatever was left on the line
+            self.rows += 1;
+        }
+        out.flush()
+    }
+}
+
+impl OutputPin for TerminalLatch {
+    fn set_high(&mut self) -> Result<(), ShifterError> {
+        self.pin.set_high()?;
+        self.draw()?;
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), ShifterError> {
+        self.pin.set_low()
+    }
+}
+
+impl Shifter {
+
+    /// Returns a new `Shifter` object that draws the state of every output in
+    /// the terminal (as rows of *width* coloured blocks) on every `apply()`
+    /// instead of driving real hardware.  Outputs are numbered the same way
+    /// as `MockChain` does (the number at the start of each row is the index
+    /// of its first output) and the same rows are redrawn in place each time:
+    ///
+    /// ```
+    /// let mut shifter = Shifter::terminal(8);
+    /// let sr0 = shifter.add(8);
+    /// shifter.set(sr0, 0b10101010, Apply::Now).unwrap();
+    /// ```
+    ///
+    /// Panics if *width* is 0.
+    pub fn terminal(width: usize) -> Shifter {
+        assert!(width > 0, "The width must be at least 1");
+        let mock = MockChain::default();
+        let latch = TerminalLatch {
+            pin: MockPin(mock.clone(), Signal::Latch),
+            width: width,
+            rows: 0,
+        };
+        Shifter::from_output_pins(
+            MockPin(mock.clone(), Signal::Data),
+            latch,
+            MockPin(mock, Signal::Clock),
+        )
+    }
+}
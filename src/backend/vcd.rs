@@ -0,0 +1,57 @@
+//! Exporting a `Recording` as a [Value Change Dump][vcd] file which can be
+//! opened in GTKWave, PulseView, etc (e.g. to compare against a logic analyzer
+//! capture).  See `Recording.save_vcd()`.
+//!
+//! [vcd]: https://en.wikipedia.org/wiki/Value_change_dump
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use {Recording, Signal, ShifterError};
+
+// The identifier each signal goes by in the dump
+fn id(signal: Signal) -> char {
+    match signal {
+        Signal::Data => 'd',
+        Signal::Latch => 'l',
+        Signal::Clock => 'c',
+    }
+}
+
+impl Recording {
+
+    /// Writes every event recorded so far to *out* in VCD format (with
+    /// nanosecond timestamps).  Lines that haven't been set yet start out as
+    /// `x` (unknown).
+    pub fn write_vcd<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "$version cupi_shift {} $end", env!("CARGO_PKG_VERSION"))?;
+        writeln!(out, "$timescale 1ns $end")?;
+        writeln!(out, "$scope module shifter $end")?;
+        for &(signal, name) in &[(Signal::Data, "data"), (Signal::Latch, "latch"), (Signal::Clock, "clock")] {
+            writeln!(out, "$var wire 1 {} {} $end", id(signal), name)?;
+        }
+        writeln!(out, "$upscope $end")?;
+        writeln!(out, "$enddefinitions $end")?;
+        writeln!(out, "#0")?;
+        writeln!(out, "$dumpvars")?;
+        writeln!(out, "xd\nxl\nxc")?;
+        writeln!(out, "$end")?;
+        let mut last = None;
+        for event in self.events() {
+            let time = event.time.as_secs() * 1_000_000_000 + event.time.subsec_nanos() as u64;
+            if last != Some(time) {
+                writeln!(out, "#{}", time)?;
+                last = Some(time);
+            }
+            writeln!(out, "{}{}", if event.high { 1 } else { 0 }, id(event.signal))?;
+        }
+        out.flush()
+    }
+
+    /// Just like `write_vcd()` but writes to the file at *path* (overwriting
+    /// it if it already exists).
+    pub fn save_vcd<P: AsRef<Path>>(&self, path: P) -> Result<(), ShifterError> {
+        self.write_vcd(BufWriter::new(File::create(path)?))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+//! Staging multiple changes so they get applied all at once.  See
+//! `Shifter.batch()` and `Shifter.deferred()`.
+
+use std::ops::{Deref, DerefMut};
+use {Shifter, ShifterError, RegisterHandle, Apply, Pattern};
+
+/// Stages changes to a `Shifter` without applying them.  You get one of these
+/// inside the closure passed to `Shifter.batch()`; it has all the same methods
+/// as `Shifter` for changing state but without the *apply* argument since
+/// everything gets applied (exactly once) when the closure returns.
+pub struct Batch<'a> {
+    shifter: &'a mut Shifter,
+}
+
+impl<'a> Batch<'a> {
+
+    pub(crate) fn new(shifter: &'a mut Shifter) -> Batch<'a> {
+        Batch { shifter: shifter }
+    }
+
+    /// Provides read-only access to the underlying `Shifter` (e.g. to call
+    /// `get_pin()` in the middle of a batch).
+    pub fn shifter(&self) -> &Shifter {
+        self.shifter
+    }
+
+    /// See `Shifter.set()`.
+    pub fn set(&mut self, sr: RegisterHandle, data: usize) -> Result<(), ShifterError> {
+        self.shifter.set(sr, data, Apply::Later)
+    }
+
+    /// See `Shifter.set_masked()`.
+    pub fn set_masked(&mut self, sr: RegisterHandle, data: usize, mask: usize) -> Result<(), ShifterError> {
+        self.shifter.set_masked(sr, data, mask, Apply::Later)
+    }
+
+    /// See `Shifter.set_bytes()`.
+    pub fn set_bytes(&mut self, sr: RegisterHandle, bytes: &[u8]) -> Result<(), ShifterError> {
+        self.shifter.set_bytes(sr, bytes, Apply::Later)
+    }
+
+    /// See `Shifter.set_pin_high()`.
+    pub fn set_pin_high(&mut self, sr: RegisterHandle, pin: u8) -> Result<(), ShifterError> {
+        self.shifter.set_pin_high(sr, pin, Apply::Later)
+    }
+
+    /// See `Shifter.set_pin_low()`.
+    pub fn set_pin_low(&mut self, sr: RegisterHandle, pin: u8) -> Result<(), ShifterError> {
+        self.shifter.set_pin_low(sr, pin, Apply::Later)
+    }
+
+    /// See `Shifter.set_pin_range()`.
+    pub fn set_pin_range(&mut self, sr: RegisterHandle, start: u8, end: u8, state: bool) -> Result<(), ShifterError> {
+        self.shifter.set_pin_range(sr, start, end, state, Apply::Later)
+    }
+
+    /// See `Shifter.toggle_pin()`.
+    pub fn toggle_pin(&mut self, sr: RegisterHandle, pin: u8) -> Result<(), ShifterError> {
+        self.shifter.toggle_pin(sr, pin, Apply::Later)
+    }
+
+    /// See `Shifter.rotate_left()`.
+    pub fn rotate_left(&mut self, sr: RegisterHandle, n: usize) -> Result<(), ShifterError> {
+        self.shifter.rotate_left(sr, n, Apply::Later)
+    }
+
+    /// See `Shifter.rotate_right()`.
+    pub fn rotate_right(&mut self, sr: RegisterHandle, n: usize) -> Result<(), ShifterError> {
+        self.shifter.rotate_right(sr, n, Apply::Later)
+    }
+
+    /// See `Shifter.shift_in()`.
+    pub fn shift_in(&mut self, sr: RegisterHandle, state: bool) -> Result<(), ShifterError> {
+        self.shifter.shift_in(sr, state, Apply::Later)
+    }
+
+    /// See `Shifter.set_chain()`.
+    pub fn set_chain(&mut self, bytes: &[u8]) -> Result<(), ShifterError> {
+        self.shifter.set_chain(bytes, Apply::Later)
+    }
+
+    /// See `Shifter.set_chain_pin()`.
+    pub fn set_chain_pin(&mut self, index: usize, state: bool) -> Result<(), ShifterError> {
+        self.shifter.set_chain_pin(index, state, Apply::Later)
+    }
+
+    /// See `Shifter.set_named()`.
+    pub fn set_named(&mut self, name: &str, state: bool) -> Result<(), ShifterError> {
+        self.shifter.set_named(name, state, Apply::Later)
+    }
+
+    /// See `Shifter.set_group()`.
+    pub fn set_group(&mut self, name: &str, state: bool) -> Result<(), ShifterError> {
+        self.shifter.set_group(name, state, Apply::Later)
+    }
+
+    /// See `Shifter.toggle_group()`.
+    pub fn toggle_group(&mut self, name: &str) -> Result<(), ShifterError> {
+        self.shifter.toggle_group(name, Apply::Later)
+    }
+
+    /// See `Shifter.fill()`.
+    pub fn fill(&mut self, sr: RegisterHandle, pattern: Pattern) -> Result<(), ShifterError> {
+        self.shifter.fill(sr, pattern, Apply::Later)
+    }
+
+    /// See `Shifter.fill_chain()`.
+    pub fn fill_chain(&mut self, pattern: Pattern) -> Result<(), ShifterError> {
+        self.shifter.fill_chain(pattern, Apply::Later)
+    }
+
+    /// See `Shifter.all_high()`.
+    pub fn all_high(&mut self) -> Result<(), ShifterError> {
+        self.shifter.all_high(Apply::Later)
+    }
+
+    /// See `Shifter.all_low()`.
+    pub fn all_low(&mut self) -> Result<(), ShifterError> {
+        self.shifter.all_low(Apply::Later)
+    }
+}
+
+/// A guard that stages changes to a `Shifter` and applies them when it goes
+/// out of scope (see `Shifter.deferred()`).  It dereferences to a `Batch` so
+/// it has all the same methods for changing state.
+///
+/// Since `Drop` can't return errors any error from the final `apply()` is
+/// ignored; call `commit()` instead of letting the guard drop if you need to
+/// know whether the changes made it out.
+pub struct Deferred<'a> {
+    batch: Batch<'a>,
+    committed: bool,
+}
+
+impl<'a> Deferred<'a> {
+
+    pub(crate) fn new(shifter: &'a mut Shifter) -> Deferred<'a> {
+        Deferred { batch: Batch::new(shifter), committed: false }
+    }
+
+    /// Applies all the staged changes right now (instead of when the guard is
+    /// dropped) and returns the result.
+    pub fn commit(mut self) -> Result<(), ShifterError> {
+        self.committed = true;
+        self.batch.shifter.apply().map(|_| ())
+    }
+}
+
+impl<'a> Deref for Deferred<'a> {
+    type Target = Batch<'a>;
+
+    fn deref(&self) -> &Batch<'a> {
+        &self.batch
+    }
+}
+
+impl<'a> DerefMut for Deferred<'a> {
+    fn deref_mut(&mut self) -> &mut Batch<'a> {
+        &mut self.batch
+    }
+}
+
+impl<'a> Drop for Deferred<'a> {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = self.batch.shifter.apply();
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! Per-pin blink scheduling.  See `Shifter.blink()` and `Shifter.tick()`.
+
+use std::time::{Duration, Instant};
+use period_from_hz;
+
+/// Keeps track of when a blinking pin needs to be toggled next.
+#[derive(Debug, Clone)]
+pub(crate) struct Blink {
+    half_period: Duration, // Time between toggles (half of a full blink)
+    next: Instant,
+    running: bool,
+}
+
+impl Blink {
+
+    /// Returns a new (running) `Blink` that toggles *hz* times per second or
+    /// `None` if *hz* isn't a positive number (see `period_from_hz()`).
+    pub(crate) fn new(hz: f64, now: Instant) -> Option<Blink> {
+        let half_period = period_from_hz(hz).ok()? / 2;
+        Some(Blink {
+            half_period: half_period,
+            next: now + half_period,
+            running: true,
+        })
+    }
+
+    pub(crate) fn start(&mut self, now: Instant) {
+        if !self.running {
+            self.running = true;
+            self.next = now + self.half_period;
+        }
+    }
+
+    pub(crate) fn stop(&mut self) {
+        self.running = false;
+    }
+
+    /// Returns `true` if the pin needs to be toggled as of *now* (and
+    /// schedules the next toggle).
+    pub(crate) fn due(&mut self, now: Instant) -> bool {
+        if !self.running || now < self.next {
+            return false;
+        }
+        self.next += self.half_period;
+        if self.next <= now {
+            // We fell behind (tick() wasn't called often enough); skip ahead
+            // rather than toggling a bunch of times in a row to catch up:
+            self.next = now + self.half_period;
+        }
+        true
+    }
+}
@@ -0,0 +1,151 @@
+//! Construction-time configuration for `Shifter`.  See `Shifter::builder()`.
+
+use {Shifter, ShifterError, Order, RegisterConfig, GpioAccess, PinNumbering, DropPolicy};
+
+/// Builds a fully-configured `Shifter` in one expression:
+///
+/// ```
+/// let shifter = Shifter::builder()
+///     .data(29).latch(28).clock(27)
+///     .invert(true)
+///     .register(8)
+///     .register(16)
+///     .build()?;
+/// ```
+///
+/// Use `Shifter.registers()` to get the handles of the shift registers that
+/// were added (in the order in which they were declared).
+#[derive(Debug, Clone)]
+pub struct ShifterBuilder {
+    data_pin: Option<usize>,
+    latch_pin: Option<usize>,
+    clock_pin: Option<usize>,
+    access: GpioAccess,
+    numbering: PinNumbering,
+    invert: bool,
+    order: Order,
+    auto_apply: bool,
+    clock_delay_ns: u32,
+    drop_policy: DropPolicy,
+    registers: Vec<RegisterConfig>,
+}
+
+impl ShifterBuilder {
+
+    pub(crate) fn new() -> ShifterBuilder {
+        ShifterBuilder {
+            data_pin: None,
+            latch_pin: None,
+            clock_pin: None,
+            access: GpioAccess::Mmap,
+            numbering: PinNumbering::WiringPi,
+            invert: false,
+            order: Order::LastToFirst,
+            auto_apply: false,
+            clock_delay_ns: 0,
+            drop_policy: DropPolicy::Leave,
+            registers: Vec::new(),
+        }
+    }
+
+    /// Sets the pin connected to the shift register's data (aka serial) input.
+    pub fn data(mut self, pin: usize) -> ShifterBuilder {
+        self.data_pin = Some(pin);
+        self
+    }
+
+    /// Sets the pin connected to the shift register's latch (aka storage
+    /// register clock) input.
+    pub fn latch(mut self, pin: usize) -> ShifterBuilder {
+        self.latch_pin = Some(pin);
+        self
+    }
+
+    /// Sets the pin connected to the shift register's clock (aka shift
+    /// register clock) input.
+    pub fn clock(mut self, pin: usize) -> ShifterBuilder {
+        self.clock_pin = Some(pin);
+        self
+    }
+
+    /// Sets how the pins get accessed (see `Shifter::try_new_with()`).
+    pub fn access(mut self, access: GpioAccess) -> ShifterBuilder {
+        self.access = access;
+        self
+    }
+
+    /// Sets the numbering scheme used for the data, latch, and clock pins (see
+    /// `PinNumbering`).  Defaults to `PinNumbering::WiringPi`.
+    pub fn numbering(mut self, numbering: PinNumbering) -> ShifterBuilder {
+        self.numbering = numbering;
+        self
+    }
+
+    /// Inverts all logic (see `Shifter.invert()`).
+    pub fn invert(mut self, invert: bool) -> ShifterBuilder {
+        self.invert = invert;
+        self
+    }
+
+    /// Sets the order in which shift registers are declared (see
+    /// `Shifter.set_order()`).
+    pub fn order(mut self, order: Order) -> ShifterBuilder {
+        self.order = order;
+        self
+    }
+
+    /// Enables or disables auto-apply (see `Shifter.set_auto_apply()`).
+    pub fn auto_apply(mut self, auto_apply: bool) -> ShifterBuilder {
+        self.auto_apply = auto_apply;
+        self
+    }
+
+    /// Sets the pause between clock edges (see `Shifter.set_clock_delay_ns()`).
+    pub fn clock_delay_ns(mut self, ns: u32) -> ShifterBuilder {
+        self.clock_delay_ns = ns;
+        self
+    }
+
+    /// Sets what happens to the chain when the `Shifter` is dropped (see
+    /// `Shifter.set_drop_policy()`).
+    pub fn drop_policy(mut self, policy: DropPolicy) -> ShifterBuilder {
+        self.drop_policy = policy;
+        self
+    }
+
+    /// Adds a shift register with the given number of *pins* (see
+    /// `Shifter.add()`).
+    pub fn register(mut self, pins: u8) -> ShifterBuilder {
+        self.registers.push(RegisterConfig::new(pins));
+        self
+    }
+
+    /// Adds a shift register with the given *config* (see
+    /// `Shifter.add_with()`).
+    pub fn register_with(mut self, config: RegisterConfig) -> ShifterBuilder {
+        self.registers.push(config);
+        self
+    }
+
+    /// Sets up the GPIO pins and returns the configured `Shifter`.  Returns a
+    /// `ShifterError::MissingPin` if the data, latch, or clock pin wasn't
+    /// given or a `ShifterError::InvalidGpio` if one doesn't exist.
+    pub fn build(self) -> Result<Shifter, ShifterError> {
+        let data_pin = self.data_pin.ok_or(ShifterError::MissingPin("data"))?;
+        let latch_pin = self.latch_pin.ok_or(ShifterError::MissingPin("latch"))?;
+        let clock_pin = self.clock_pin.ok_or(ShifterError::MissingPin("clock"))?;
+        let data_pin = self.numbering.to_wiringpi(data_pin)?;
+        let latch_pin = self.numbering.to_wiringpi(latch_pin)?;
+        let clock_pin = self.numbering.to_wiringpi(clock_pin)?;
+        let mut shifter = Shifter::try_new_with(data_pin, latch_pin, clock_pin, self.access)?;
+        if self.invert { shifter.invert(); }
+        shifter.set_order(self.order);
+        shifter.set_auto_apply(self.auto_apply);
+        shifter.set_clock_delay_ns(self.clock_delay_ns);
+        shifter.set_drop_policy(self.drop_policy);
+        for config in self.registers {
+            shifter.add_with(config);
+        }
+        Ok(shifter)
+    }
+}
@@ -0,0 +1,87 @@
+//! Measuring how fast the chain can actually be refreshed on the current
+//! hardware and backend.  See `Shifter.calibrate()`.
+
+use std::time::Duration;
+use {Shifter, ShifterError, ApplyStats};
+
+/// The results of `Shifter.calibrate()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    /// The number of `apply()` calls that were timed.
+    pub applies: usize,
+    /// The number of bits shifted out by each `apply()`.
+    pub bits: usize,
+    /// The fastest `apply()`.
+    pub fastest: Duration,
+    /// The average `apply()`.
+    pub average: Duration,
+    /// The slowest `apply()`.
+    pub slowest: Duration,
+}
+
+impl Calibration {
+
+    /// Returns the average effective clock rate (in Hz) that was achieved
+    /// (see `ApplyStats.clock_rate()`).
+    pub fn clock_rate(&self) -> f64 {
+        ApplyStats { bits: self.bits, duration: self.average }.clock_rate()
+    }
+
+    /// Returns the number of times per second the whole chain can be refreshed
+    /// (going by the average `apply()`).
+    pub fn max_refresh_rate(&self) -> f64 {
+        let secs = self.average.as_secs() as f64 + self.average.subsec_nanos() as f64 / 1e9;
+        if secs > 0.0 { 1.0 / secs } else { f64::INFINITY }
+    }
+
+    /// Returns a `ShifterError::TooSlow` if the chain can't be refreshed *hz*
+    /// times per second (going by the *slowest* `apply()` so there's some
+    /// headroom).
+    pub fn check_refresh_rate(&self, hz: f64) -> Result<(), ShifterError> {
+        let secs = self.slowest.as_secs() as f64 + self.slowest.subsec_nanos() as f64 / 1e9;
+        if secs * hz > 1.0 {
+            Err(ShifterError::TooSlow { requested: hz, achievable: 1.0 / secs })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Shifter {
+
+    /// Times *applies* calls to `apply()` to find out how fast the chain can
+    /// really be refreshed with the current hardware, backend, and settings
+    /// (e.g. `Timing`).  The current state is what gets shifted out so nothing
+    /// visibly changes.
+    ///
+    /// ```
+    /// let calibration = shifter.calibrate(100)?;
+    /// println!("{:.0} Hz clock, {:.0} refreshes/s",
+    ///     calibration.clock_rate(), calibration.max_refresh_rate());
+    /// calibration.check_refresh_rate(60.0)?; // Warn if animations will stutter
+    /// ```
+    ///
+    /// Returns a `ShifterError::InvalidRate` if *applies* is 0.
+    pub fn calibrate(&mut self, applies: usize) -> Result<Calibration, ShifterError> {
+        if applies == 0 {
+            return Err(ShifterError::InvalidRate(0.0));
+        }
+        let mut calibration = Calibration {
+            applies: applies,
+            bits: 0,
+            fastest: Duration::MAX,
+            average: Duration::from_secs(0),
+            slowest: Duration::from_secs(0),
+        };
+        let mut total = Duration::from_secs(0);
+        for _ in 0..applies {
+            let stats = self.apply()?;
+            calibration.bits = stats.bits;
+            calibration.fastest = calibration.fastest.min(stats.duration);
+            calibration.slowest = calibration.slowest.max(stats.duration);
+            total += stats.duration;
+        }
+        calibration.average = total / applies as u32;
+        Ok(calibration)
+    }
+}
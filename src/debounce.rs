@@ -0,0 +1,147 @@
+//! Filtering out the bouncing of mechanical switches so a polled input (see
+//! `ShifterIn.start_polling()`) only reports a change once it has settled.
+//! See `Debounce`.
+
+use std::time::{Duration, Instant};
+use {ShifterIn, ShifterError, RegisterHandle};
+
+/// How long an input has to stay at a new level before `ShifterIn`'s
+/// polling thread (see `ShifterIn.start_polling()`) believes it changed.
+/// Set for every input via `ShifterIn.set_debounce()` or for individual pins
+/// via `ShifterIn.set_pin_debounce()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Debounce {
+    /// Every change is reported straight away (the default).
+    #[default]
+    Off,
+    /// A change is reported once the input has read the same for at least
+    /// this long (5-20 ms suits most switches).  Since the inputs are only
+    /// looked at when they're read, the delay rounds up to the next read.
+    Time(Duration),
+    /// A change is reported once the input has read the same this many
+    /// times in a row (at 1 kHz, 10 samples is about 10 ms).  0 and 1 both
+    /// mean every change is reported straight away.
+    Samples(u32),
+}
+
+// Tracks the debounced level of one input
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct InputFilter {
+    level: bool,
+    since: Option<Instant>, // When the input started reading differently
+    count: u32, // How many reads in a row it's read differently
+}
+
+impl InputFilter {
+
+    pub(crate) fn new(level: bool) -> InputFilter {
+        InputFilter { level: level, since: None, count: 0 }
+    }
+
+    // Returns the debounced level
+    pub(crate) fn level(&self) -> bool {
+        self.level
+    }
+
+    // Takes a *raw* reading taken *at* the given time and returns the new
+    // level if that's enough (according to *debounce*) for it to change
+    pub(crate) fn update(&mut self, raw: bool, at: Instant, debounce: Debounce) -> Option<bool> {
+        if raw == self.level {
+            self.since = None;
+            self.count = 0;
+            return None;
+        }
+        let since = *self.since.get_or_insert(at);
+        self.count = self.count.saturating_add(1);
+        let settled = match debounce {
+            Debounce::Off => true,
+            Debounce::Time(duration) => at.duration_since(since) >= duration,
+            Debounce::Samples(samples) => self.count >= samples,
+        };
+        if !settled {
+            return None;
+        }
+        *self = InputFilter::new(raw);
+        Some(raw)
+    }
+}
+
+impl ShifterIn {
+
+    /// Sets how every input (apart from those given their own setting via
+    /// `set_pin_debounce()`) is debounced when polled (see `Debounce`):
+    ///
+    /// ```
+    /// inputs.set_debounce(Debounce::Time(Duration::from_millis(10)));
+    /// inputs.set_pin_debounce(buttons, 7, Debounce::Samples(3))?; // A noisy one
+    /// let (poller, events) = inputs.start_polling(1000.0)?;
+    /// ```
+    ///
+    /// Debouncing only affects the `InputEvent`s sent by the polling thread;
+    /// `get()`, `get_pin()`, etc still return exactly what was last read.
+    pub fn set_debounce(&mut self, debounce: Debounce) {
+        self.debounce = debounce;
+    }
+
+    /// Sets how the given *pin* on the given shift register (*sr*) is
+    /// debounced when polled (overriding `set_debounce()`).
+    pub fn set_pin_debounce(&mut self, sr: RegisterHandle, pin: u8, debounce: Debounce) -> Result<(), ShifterError> {
+        self.register(sr)?.check_pin(pin)?;
+        self.pin_debounce.insert((sr, pin), debounce);
+        Ok(())
+    }
+
+    /// Returns how the given *pin* on the given shift register (*sr*) is
+    /// debounced when polled (see `set_debounce()` and `set_pin_debounce()`).
+    pub fn debounce(&self, sr: RegisterHandle, pin: u8) -> Result<Debounce, ShifterError> {
+        self.register(sr)?.check_pin(pin)?;
+        Ok(self.pin_debounce_or_default(sr, pin))
+    }
+
+    pub(crate) fn pin_debounce_or_default(&self, sr: RegisterHandle, pin: u8) -> Debounce {
+        self.pin_debounce.get(&(sr, pin)).cloned().unwrap_or(self.debounce)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use super::{Debounce, InputFilter};
+
+    #[test]
+    fn samples() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut filter = InputFilter::new(false);
+        let debounce = Debounce::Samples(3);
+        assert_eq!(filter.update(true, ms(0), debounce), None);
+        assert_eq!(filter.update(false, ms(1), debounce), None); // Bounced back
+        assert_eq!(filter.update(true, ms(2), debounce), None);
+        assert_eq!(filter.update(true, ms(3), debounce), None);
+        assert_eq!(filter.update(true, ms(4), debounce), Some(true));
+        assert_eq!(filter.update(true, ms(5), debounce), None);
+        assert!(filter.level());
+    }
+
+    #[test]
+    fn time() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut filter = InputFilter::new(true);
+        let debounce = Debounce::Time(Duration::from_millis(10));
+        assert_eq!(filter.update(false, ms(10), debounce), None);
+        assert_eq!(filter.update(true, ms(15), debounce), None); // Bounced back
+        assert_eq!(filter.update(false, ms(16), debounce), None);
+        assert_eq!(filter.update(false, ms(25), debounce), None);
+        assert_eq!(filter.update(false, ms(26), debounce), Some(false));
+    }
+
+    #[test]
+    fn off() {
+        let now = Instant::now();
+        let mut filter = InputFilter::new(false);
+        assert_eq!(filter.update(true, now, Debounce::Off), Some(true));
+        assert_eq!(filter.update(false, now, Debounce::Samples(0)), Some(false));
+        assert_eq!(filter.update(false, now, Debounce::Off), None);
+    }
+}
@@ -0,0 +1,293 @@
+//! Software dimming:  Approximating analog brightness on plain on/off outputs
+//! by switching them on for only part of the time.  See
+//! `Shifter.set_brightness()`.
+
+use std::collections::HashMap;
+use {Shifter, ShifterError, RegisterHandle, Apply, Polarity};
+
+/// How dimmed pins get switched on and off (see `Shifter.set_dimming_mode()`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DimmingMode {
+    /// Plain PWM (the default):  Each cycle is made up of one refresh per PWM
+    /// step and a pin is on for the first *brightness* of them.
+    Pwm,
+    /// Bit angle modulation:  Each cycle is made up of one refresh per bit of
+    /// the brightness level (5 with the default 32 PWM steps), each lasting
+    /// twice as long as the one before, and a pin is on for the refreshes
+    /// whose bit is set.  You get the same brightness levels and cycle length
+    /// with far fewer refreshes (so far less CPU) than plain PWM.
+    Bam,
+}
+
+/// Keeps track of every dimmed pin and where we are in the dimming cycle.
+#[derive(Debug, Clone)]
+pub(crate) struct Dimmer {
+    // How many of the `steps` refreshes in each cycle each pin is HIGH for:
+    levels: HashMap<(RegisterHandle, u8), u16>,
+    steps: u16,
+    phase: u16, // The PWM step or (in BAM mode) bit we're on
+    mode: DimmingMode,
+    global: u8, // See Shifter.set_global_brightness()
+    gamma: f64,
+    // How many steps something at each level (0..=steps) is actually on for
+    // after gamma correction:
+    table: Vec<u16>,
+}
+
+impl Dimmer {
+
+    pub(crate) fn new() -> Dimmer {
+        Dimmer {
+            levels: HashMap::new(),
+            steps: 32,
+            phase: 0,
+            mode: DimmingMode::Pwm,
+            global: 255,
+            gamma: 1.0,
+            table: (0..=32).collect(),
+        }
+    }
+
+    // Stops dimming every pin (leaving them at whatever state they're in)
+    pub(crate) fn clear_levels(&mut self) {
+        self.levels.clear();
+    }
+
+    // Rebuilds the gamma table after the number of steps or gamma changes
+    fn build_table(&mut self) {
+        let (steps, gamma) = (self.steps as f64, self.gamma);
+        self.table = (0..=self.steps)
+            .map(|level| ((level as f64 / steps).powf(gamma) * steps).round() as u16)
+            .collect();
+    }
+
+    pub(crate) fn is_active(&self) -> bool {
+        !self.levels.is_empty() || self.global < 255
+    }
+
+    // As many bits as fit in the number of steps (BAM mode)
+    fn bits(&self) -> u16 {
+        15 - self.steps.leading_zeros() as u16
+    }
+
+    /// Moves on to the next step of the cycle and returns how many refresh
+    /// periods it should last.
+    pub(crate) fn advance(&mut self) -> u32 {
+        match self.mode {
+            DimmingMode::Pwm => {
+                self.phase = (self.phase + 1) % self.steps;
+                1
+            },
+            DimmingMode::Bam => {
+                self.phase = (self.phase + 1) % self.bits();
+                1 << self.phase
+            },
+        }
+    }
+
+    /// Returns `true` if something at the given *level* (out of `steps`)
+    /// should be on for the current step of the cycle (after gamma
+    /// correction).
+    pub(crate) fn is_on(&self, level: u16) -> bool {
+        let level = self.table[level as usize];
+        match self.mode {
+            DimmingMode::Pwm => self.phase < level,
+            DimmingMode::Bam => {
+                // The levels get rescaled from 0..=steps to 0..=(2^bits - 1):
+                let (max, steps) = ((1u32 << self.bits()) - 1, self.steps as u32);
+                let value = (level as u32 * max + steps / 2) / steps;
+                value & (1 << self.phase) != 0
+            },
+        }
+    }
+
+    // The global brightness as a level out of `steps`
+    fn global_level(&self) -> u16 {
+        ((self.global as u32 * self.steps as u32 + 127) / 255) as u16
+    }
+
+    /// The fraction of the time the outputs should be enabled for the global
+    /// brightness (after gamma correction) when using hardware PWM.
+    pub(crate) fn global_duty(&self) -> f64 {
+        (self.global as f64 / 255.0).powf(self.gamma)
+    }
+}
+
+impl Shifter {
+
+    /// Dims the given *pin* on the given shift register (*sr*) to the given
+    /// *brightness* (0.0 being off and 1.0 being fully on) via software PWM
+    /// (or BAM; see `set_dimming_mode()`):  Every refresh of the chain the pin
+    /// is switched on or off so that over the course of a PWM cycle (see
+    /// `set_pwm_steps()`) it's on for *brightness* of the time.
+    ///
+    /// This only works while the chain is being refreshed continuously (see
+    /// `Shifter.start_refresh()`) and the refresh rate needs to be high
+    /// enough that a whole cycle doesn't flicker:  With the default 32 steps
+    /// a 3.2 kHz refresh rate gives a 100 Hz PWM cycle.  Use `calibrate()` to
+    /// see what your chain can do.
+    ///
+    /// ```
+    /// shifter.set_brightness(sr0, 3, 0.3)?;
+    /// let refresh = shifter.start_refresh(3200.0)?;
+    /// ```
+    ///
+    /// Setting a brightness of exactly 0.0 or 1.0 stops dimming the pin and
+    /// just sets it LOW or HIGH.  Changing a dimmed pin's state any other way
+    /// (e.g. `set_pin_high()`) only lasts until the next refresh.  Returns a
+    /// `ShifterError::InvalidBrightness` if *brightness* isn't between 0.0
+    /// and 1.0.
+    pub fn set_brightness(&mut self, sr: RegisterHandle, pin: u8, brightness: f64) -> Result<(), ShifterError> {
+        if !(0.0..=1.0).contains(&brightness) {
+            return Err(ShifterError::InvalidBrightness(brightness));
+        }
+        self.register(sr)?.check_pin(pin)?;
+        if brightness == 0.0 || brightness == 1.0 {
+            self.dimmer.levels.remove(&(sr, pin));
+            return self.register_mut(sr).map(|reg| reg.set_pin(pin, brightness == 1.0));
+        }
+        let level = (brightness * self.dimmer.steps as f64).round() as u16;
+        self.dimmer.levels.insert((sr, pin), level);
+        Ok(())
+    }
+
+    /// Returns the brightness of the given *pin* on the given shift register
+    /// (*sr*):  Whatever was given to `set_brightness()` (rounded to the
+    /// nearest PWM step and before gamma correction) if it's being dimmed or
+    /// 1.0 or 0.0 depending on whether it's HIGH or LOW if it isn't.
+    pub fn brightness(&self, sr: RegisterHandle, pin: u8) -> Result<f64, ShifterError> {
+        let reg = self.register(sr)?;
+        reg.check_pin(pin)?;
+        Ok(match self.dimmer.levels.get(&(sr, pin)) {
+            Some(&level) => level as f64 / self.dimmer.steps as f64,
+            None => if reg.pin(pin) { 1.0 } else { 0.0 },
+        })
+    }
+
+    /// Sets the number of steps (refreshes) in each PWM cycle which is also
+    /// the number of distinct brightness levels (32 by default).  More steps
+    /// means smoother dimming but a lower PWM frequency for a given refresh
+    /// rate.  Pins that are already being dimmed keep their brightness.
+    ///
+    /// Panics if *steps* is less than 2.
+    pub fn set_pwm_steps(&mut self, steps: u16) {
+        assert!(steps >= 2, "PWM needs at least 2 steps");
+        let old = self.dimmer.steps as f64;
+        for level in self.dimmer.levels.values_mut() {
+            *level = (*level as f64 / old * steps as f64).round() as u16;
+        }
+        self.dimmer.steps = steps;
+        self.dimmer.phase = 0;
+        self.dimmer.build_table();
+    }
+
+    /// Sets the gamma used to correct every brightness (including the
+    /// global brightness) for how the eye perceives it.  LEDs that are on for
+    /// half the time look almost as bright as fully on ones so with a gamma
+    /// of 2.2 or so (a common choice) `set_brightness(sr0, 3, 0.5)` actually
+    /// keeps the pin on for about 22% of the time which looks like half
+    /// brightness.  The default of 1.0 leaves brightnesses as they are.
+    ///
+    /// Bear in mind that correction uses up some of the PWM steps at the low
+    /// end (with the default 32 steps and a gamma of 2.2 anything below about
+    /// 0.15 is off) so you may want more of them (see `set_pwm_steps()`).
+    ///
+    /// Returns a `ShifterError::InvalidGamma` if *gamma* isn't a positive
+    /// number.
+    pub fn set_gamma(&mut self, gamma: f64) -> Result<(), ShifterError> {
+        if !gamma.is_finite() || gamma <= 0.0 {
+            return Err(ShifterError::InvalidGamma(gamma));
+        }
+        self.dimmer.gamma = gamma;
+        self.dimmer.build_table();
+        if self.oe_pwm.is_some() {
+            // Update the duty cycle for the new gamma:
+            let enabled = self.outputs_enabled;
+            self.set_outputs_enabled(enabled)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the gamma set with `set_gamma()` (1.0 by default).
+    pub fn gamma(&self) -> f64 {
+        self.dimmer.gamma
+    }
+
+    /// Picks how dimmed pins get switched on and off:  Plain PWM (the
+    /// default) or bit angle modulation (see `DimmingMode`).
+    pub fn set_dimming_mode(&mut self, mode: DimmingMode) {
+        self.dimmer.mode = mode;
+        self.dimmer.phase = 0;
+    }
+
+    /// Returns the current `DimmingMode`.
+    pub fn dimming_mode(&self) -> DimmingMode {
+        self.dimmer.mode
+    }
+
+    /// Dims every output in the chain at once to the given *level* (0 being
+    /// off and 255, the default, being full brightness) by modulating the
+    /// output enable pin instead of re-shifting any data.
+    ///
+    /// If the output enable pin was set with `set_output_enable_pwm()` the
+    /// hardware PWM peripheral does all the work.  Otherwise the pin set with
+    /// `set_output_enable_pin()` gets switched on and off in software just
+    /// like the pins dimmed via `set_brightness()` which means it only works
+    /// while the chain is being refreshed (see `Shifter.start_refresh()`).
+    /// Either way `disable_outputs()` still switches everything off.
+    ///
+    /// Returns a `ShifterError::MissingPin` if there's no output enable pin.
+    pub fn set_global_brightness(&mut self, level: u8) -> Result<(), ShifterError> {
+        if self.oe.is_none() && self.oe_pwm.is_none() {
+            return Err(ShifterError::MissingPin("output enable"));
+        }
+        self.dimmer.global = level;
+        // Updates the PWM duty cycle (or leaves a plain pin fully enabled
+        // until the next refresh):
+        let enabled = self.outputs_enabled;
+        self.set_outputs_enabled(enabled)
+    }
+
+    /// Returns the level set with `set_global_brightness()` (255 by default).
+    pub fn global_brightness(&self) -> u8 {
+        self.dimmer.global
+    }
+
+    // Sets every dimmed pin for the next step of the dimming cycle (without
+    // applying) and returns how many refresh periods the step should last.
+    // Also switches the output enable pin if it's being used for global
+    // brightness without hardware PWM.  Called by the refresh thread before
+    // every refresh.
+    pub(crate) fn dimming_step(&mut self) -> Result<u32, ShifterError> {
+        if !self.dimmer.is_active() {
+            return Ok(1);
+        }
+        let periods = self.dimmer.advance();
+        let states: Vec<_> = self.dimmer.levels.iter()
+            .map(|(&(sr, pin), &level)| (sr, pin, self.dimmer.is_on(level)))
+            .collect();
+        for (sr, pin, state) in states {
+            if self.set_pin_state(sr, pin, state).is_err() {
+                // The shift register was removed (or resized) out from under us
+                self.dimmer.levels.remove(&(sr, pin));
+            }
+        }
+        if self.dimmer.global < 255 && self.outputs_enabled {
+            let on = self.dimmer.is_on(self.dimmer.global_level());
+            if let Some((ref mut pin, polarity)) = self.oe {
+                pin.set_state(on == (polarity == Polarity::ActiveHigh))?;
+            }
+        }
+        Ok(periods)
+    }
+
+    // Sets the given pin HIGH or LOW without applying
+    fn set_pin_state(&mut self, sr: RegisterHandle, pin: u8, state: bool) -> Result<(), ShifterError> {
+        if state {
+            self.set_pin_high(sr, pin, Apply::Later)
+        } else {
+            self.set_pin_low(sr, pin, Apply::Later)
+        }
+    }
+}
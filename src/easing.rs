@@ -0,0 +1,88 @@
+//! Easing curves for smoothing out fades and transitions.  See `Easing`.
+
+use std::f64::consts::PI;
+
+/// How a transition from one level to another speeds up and slows down (see
+/// `Animation.easing()`).  Each curve maps how far along the transition we
+/// are in time (0.0 to 1.0) to how far along it the level should be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Easing {
+    /// A constant rate of change (the default).
+    Linear,
+    /// Starts slowly and speeds up (quadratic).
+    EaseIn,
+    /// Starts quickly and slows down (quadratic).
+    EaseOut,
+    /// Starts and finishes slowly (quadratic).
+    EaseInOut,
+    /// Starts slowly and speeds up (a quarter of a sine wave).
+    SineIn,
+    /// Starts quickly and slows down (a quarter of a sine wave).
+    SineOut,
+    /// Starts and finishes slowly (half of a sine wave).  About as gentle as
+    /// it gets; good for "breathing" LEDs.
+    SineInOut,
+    /// Starts slowly and speeds up (cubic; more pronounced than `EaseIn`).
+    CubicIn,
+    /// Starts quickly and slows down (cubic).
+    CubicOut,
+    /// Starts and finishes slowly (cubic).
+    CubicInOut,
+}
+
+impl Easing {
+
+    /// Returns how far along a transition (0.0 to 1.0) the level should be
+    /// when *t* (0.0 to 1.0, clamped) of its time has passed.
+    pub fn apply(&self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match *self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut => if t < 0.5 { 2.0 * t * t } else { 1.0 - (-2.0 * t + 2.0).powi(2) / 2.0 },
+            Easing::SineIn => 1.0 - (t * PI / 2.0).cos(),
+            Easing::SineOut => (t * PI / 2.0).sin(),
+            Easing::SineInOut => (1.0 - (t * PI).cos()) / 2.0,
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut => if t < 0.5 { 4.0 * t * t * t } else { 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0 },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Easing;
+
+    const CURVES: [Easing; 10] = [Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut, Easing::SineIn,
+        Easing::SineOut, Easing::SineInOut, Easing::CubicIn, Easing::CubicOut, Easing::CubicInOut];
+
+    #[test]
+    fn endpoints() {
+        for easing in CURVES.iter() {
+            assert!(easing.apply(0.0).abs() < 1e-9, "{:?}", easing);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-9, "{:?}", easing);
+        }
+    }
+
+    #[test]
+    fn always_increasing() {
+        for easing in CURVES.iter() {
+            assert!((1..10).all(|i| easing.apply(i as f64 / 10.0) < easing.apply((i + 1) as f64 / 10.0)), "{:?}", easing);
+        }
+    }
+
+    #[test]
+    fn shapes() {
+        assert!(Easing::EaseIn.apply(0.5) < 0.5 && Easing::EaseOut.apply(0.5) > 0.5);
+        assert!((Easing::SineInOut.apply(0.5) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn clamped() {
+        assert_eq!(Easing::CubicIn.apply(2.0), 1.0);
+        assert_eq!(Easing::CubicIn.apply(-1.0), 0.0);
+    }
+}
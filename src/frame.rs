@@ -0,0 +1,217 @@
+//! Double buffering:  Composing the next state of the whole chain in a
+//! `Frame` while the current one stays latched and then swapping it in all at
+//! once.  See `Shifter.back_buffer()` and `Shifter.swap_buffers()`.
+
+use register::Bits;
+use {Shifter, ShifterError, RegisterHandle, Apply};
+
+/// The data for every shift register in a chain, kept separately from the
+/// `Shifter` so a whole frame can be put together without any of it getting
+/// shifted out (or picked up by a refresh) before it's finished.  Get one via
+/// `Shifter.frame()` or `Shifter.back_buffer()`.
+///
+/// The methods work just like the `Shifter` methods of the same name (minus
+/// the *apply* argument).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    // Every shift register in the order in which they were added:
+    registers: Vec<(RegisterHandle, Bits)>,
+}
+
+impl Frame {
+
+    /// Sets the *data* on the given shift register (*sr*).
+    pub fn set(&mut self, sr: RegisterHandle, data: usize) -> Result<(), ShifterError> {
+        self.bits_mut(sr)?.set_word(data);
+        Ok(())
+    }
+
+    /// Returns the data on the given shift register (*sr*) as a `usize`.
+    pub fn get(&self, sr: RegisterHandle) -> Result<usize, ShifterError> {
+        Ok(self.bits(sr)?.word())
+    }
+
+    /// Sets the data on the given shift register (*sr*) from *bytes* (see
+    /// `Shifter.set_bytes()`).
+    pub fn set_bytes(&mut self, sr: RegisterHandle, bytes: &[u8]) -> Result<(), ShifterError> {
+        let bits = self.bits_mut(sr)?;
+        if bytes.len() != bits.bytes.len() {
+            return Err(ShifterError::InvalidLength { expected: bits.bytes.len(), actual: bytes.len() });
+        }
+        bits.set_bytes(bytes);
+        Ok(())
+    }
+
+    /// Returns the data on the given shift register (*sr*) as bytes.
+    pub fn get_bytes(&self, sr: RegisterHandle) -> Result<Vec<u8>, ShifterError> {
+        Ok(self.bits(sr)?.bytes.clone())
+    }
+
+    /// Sets the given *pin* on the given shift register (*sr*) HIGH (if
+    /// *state* is `true`) or LOW.
+    pub fn set_pin(&mut self, sr: RegisterHandle, pin: u8, state: bool) -> Result<(), ShifterError> {
+        let bits = self.bits_mut(sr)?;
+        check_pin(bits, pin)?;
+        bits.set(pin as usize, state);
+        Ok(())
+    }
+
+    /// Returns `true` if the given *pin* on the given shift register (*sr*)
+    /// is HIGH.
+    pub fn get_pin(&self, sr: RegisterHandle, pin: u8) -> Result<bool, ShifterError> {
+        let bits = self.bits(sr)?;
+        check_pin(bits, pin)?;
+        Ok(bits.get(pin as usize))
+    }
+
+    /// Flips the given *pin* on the given shift register (*sr*).
+    pub fn toggle_pin(&mut self, sr: RegisterHandle, pin: u8) -> Result<(), ShifterError> {
+        let state = self.get_pin(sr, pin)?;
+        self.set_pin(sr, pin, !state)
+    }
+
+    /// Sets the pin at the given *index* in the whole chain (see
+    /// `Shifter.set_chain_pin()`) HIGH (if *state* is `true`) or LOW.
+    pub fn set_chain_pin(&mut self, index: usize, state: bool) -> Result<(), ShifterError> {
+        let (position, pin) = self.chain_pin(index)?;
+        self.registers[position].1.set(pin, state);
+        Ok(())
+    }
+
+    /// Returns `true` if the pin at the given *index* in the whole chain is
+    /// HIGH.
+    pub fn get_chain_pin(&self, index: usize) -> Result<bool, ShifterError> {
+        let (position, pin) = self.chain_pin(index)?;
+        Ok(self.registers[position].1.get(pin))
+    }
+
+    /// Sets every pin in the frame HIGH.
+    pub fn all_high(&mut self) {
+        for (_, bits) in self.registers.iter_mut() {
+            bits.fill(true);
+        }
+    }
+
+    /// Sets every pin in the frame LOW.
+    pub fn all_low(&mut self) {
+        for (_, bits) in self.registers.iter_mut() {
+            bits.fill(false);
+        }
+    }
+
+    /// Returns the total number of pins in the frame.
+    pub fn total_pins(&self) -> usize {
+        self.registers.iter().map(|(_, bits)| bits.len).sum()
+    }
+
+    fn bits(&self, sr: RegisterHandle) -> Result<&Bits, ShifterError> {
+        self.registers.iter()
+            .find(|&&(handle, _)| handle == sr)
+            .map(|(_, bits)| bits)
+            .ok_or(ShifterError::InvalidRegister(sr))
+    }
+
+    fn bits_mut(&mut self, sr: RegisterHandle) -> Result<&mut Bits, ShifterError> {
+        self.registers.iter_mut()
+            .find(|&&mut (handle, _)| handle == sr)
+            .map(|(_, bits)| bits)
+            .ok_or(ShifterError::InvalidRegister(sr))
+    }
+
+    // Converts a chain-wide pin *index* into a position in self.registers and
+    // the pin on that shift register
+    fn chain_pin(&self, index: usize) -> Result<(usize, usize), ShifterError> {
+        let mut remaining = index;
+        for (position, (_, bits)) in self.registers.iter().enumerate() {
+            if remaining < bits.len {
+                return Ok((position, remaining));
+            }
+            remaining -= bits.len;
+        }
+        Err(ShifterError::InvalidPosition(index))
+    }
+}
+
+fn check_pin(bits: &Bits, pin: u8) -> Result<(), ShifterError> {
+    if (pin as usize) < bits.len {
+        Ok(())
+    } else {
+        Err(ShifterError::InvalidPin(pin))
+    }
+}
+
+impl Shifter {
+
+    /// Returns a copy of the current data on every shift register in the
+    /// chain as a `Frame`.
+    pub fn frame(&self) -> Frame {
+        Frame {
+            registers: self.registers().map(|(sr, reg)| (sr, reg.data.clone())).collect(),
+        }
+    }
+
+    /// Replaces the data on every shift register in the chain with that in
+    /// the given *frame* in one go.  The frame must have come from this
+    /// `Shifter` and the chain's layout can't have changed since or you'll get
+    /// a `ShifterError::LayoutMismatch` (and nothing will be changed).
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn set_frame(&mut self, frame: &Frame, apply: Apply) -> Result<(), ShifterError> {
+        if !self.matches_layout(frame) {
+            return Err(ShifterError::LayoutMismatch);
+        }
+        for (sr, (_, bits)) in self.shift_registers.iter_mut().zip(frame.registers.iter()) {
+            sr.data.bytes.copy_from_slice(&bits.bytes);
+        }
+        self.dirty = true;
+        self.maybe_apply(apply)
+    }
+
+    /// Returns the back buffer:  A `Frame` for composing the next state of
+    /// the chain without touching what's currently latched (the front
+    /// buffer).  Nothing in it goes anywhere until `swap_buffers()` is
+    /// called, so a refresh (see `start_refresh()`) never shifts out half a
+    /// frame:
+    ///
+    /// ```
+    /// let back = shifter.back_buffer();
+    /// back.all_low();
+    /// back.set_pin(sr0, 3, true)?;
+    /// back.set(sr1, 0b11110000)?;
+    /// shifter.swap_buffers(Apply::Now)?;
+    /// ```
+    ///
+    /// The back buffer starts out as a copy of the front buffer (as it also
+    /// does whenever the chain's layout changes).
+    pub fn back_buffer(&mut self) -> &mut Frame {
+        let back = self.take_back_buffer();
+        self.back.insert(back)
+    }
+
+    /// Swaps the back buffer (see `back_buffer()`) with the front buffer:
+    /// Every shift register gets the data that was composed in the back
+    /// buffer all at once and the back buffer gets the frame that was being
+    /// displayed (handy for effects that build on the previous frame).
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn swap_buffers(&mut self, apply: Apply) -> Result<(), ShifterError> {
+        let back = self.take_back_buffer();
+        self.back = Some(self.frame());
+        self.set_frame(&back, apply)
+    }
+
+    // Removes the back buffer (or makes a new one if there isn't one or it no
+    // longer fits the chain)
+    fn take_back_buffer(&mut self) -> Frame {
+        match self.back.take() {
+            Some(back) if self.matches_layout(&back) => back,
+            _ => self.frame(),
+        }
+    }
+
+    // Returns true if *frame* has the same shift registers (with the same
+    // number of pins) as the chain does now
+    fn matches_layout(&self, frame: &Frame) -> bool {
+        frame.registers.len() == self.shift_registers.len()
+            && self.registers().zip(frame.registers.iter())
+                .all(|((sr, reg), &(handle, ref bits))| sr == handle && reg.data.len == bits.len)
+    }
+}
@@ -0,0 +1,125 @@
+//! Rendering frames at a fixed rate.  See `FrameLoop`.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use {Shifter, ShifterError, Frame, Apply, period_from_hz};
+
+/// Calls a function to draw each frame of an animation at a fixed frame rate
+/// and applies every frame it draws:
+///
+/// ```
+/// let start = Instant::now();
+/// let mut frames = FrameLoop::new(30.0)?;
+/// frames.run(&mut shifter, |frame| {
+///     let lit = (start.elapsed().as_millis() / 100) as usize % 8;
+///     frame.all_low();
+///     frame.set_pin(sr0, lit as u8, true).unwrap();
+/// })?;
+/// ```
+///
+/// Each frame starts out as a copy of the one before (see `Shifter.frame()`)
+/// and is applied all at once when the function returns.  Frames are
+/// scheduled relative to when the loop started so the frame rate doesn't
+/// drift even if drawing and applying take a while.  If a frame takes so long
+/// that the next one is already overdue the loop skips ahead instead of
+/// rushing to catch up (see `dropped()`).
+///
+/// `run()` keeps going until the frame limit (see `limit()`) is reached or
+/// `FrameLoopHandle.stop()` is called.
+#[derive(Debug)]
+pub struct FrameLoop {
+    period: Duration,
+    limit: Option<u64>,
+    stopping: Arc<AtomicBool>,
+    frames: u64,
+    dropped: u64,
+}
+
+impl FrameLoop {
+
+    /// Returns a new `FrameLoop` that draws *fps* frames per second.
+    /// Returns a `ShifterError::InvalidRate` if *fps* isn't a positive
+    /// number.
+    pub fn new(fps: f64) -> Result<FrameLoop, ShifterError> {
+        Ok(FrameLoop {
+            period: period_from_hz(fps)?,
+            limit: None,
+            stopping: Arc::new(AtomicBool::new(false)),
+            frames: 0,
+            dropped: 0,
+        })
+    }
+
+    /// Makes `run()` return after drawing the given number of *frames*.
+    pub fn limit(mut self, frames: u64) -> FrameLoop {
+        self.limit = Some(frames);
+        self
+    }
+
+    /// Returns a handle that can stop `run()` from another thread.
+    pub fn handle(&self) -> FrameLoopHandle {
+        FrameLoopHandle(self.stopping.clone())
+    }
+
+    /// Returns the number of frames per second this loop was created with.
+    pub fn fps(&self) -> f64 {
+        1.0 / self.period.as_secs_f64()
+    }
+
+    /// Returns the number of frames drawn by the last (or current) `run()`.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Returns the number of frames the last `run()` skipped because it
+    /// fell behind.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Calls *draw* once per frame with the next frame to fill in and applies
+    /// it to *shifter* until the frame limit is reached or the loop is
+    /// stopped.  Returns as soon as applying a frame fails.
+    pub fn run<F>(&mut self, shifter: &mut Shifter, mut draw: F) -> Result<(), ShifterError>
+        where F: FnMut(&mut Frame)
+    {
+        self.stopping.store(false, Ordering::SeqCst);
+        self.frames = 0;
+        self.dropped = 0;
+        let mut next = Instant::now();
+        while !self.stopping.load(Ordering::SeqCst) && self.limit.is_none_or(|limit| self.frames < limit) {
+            let now = Instant::now();
+            if now < next {
+                thread::sleep(next - now);
+            }
+            let mut frame = shifter.frame();
+            draw(&mut frame);
+            shifter.set_frame(&frame, Apply::Now)?;
+            self.frames += 1;
+            next += self.period;
+            let now = Instant::now();
+            if now >= next + self.period {
+                // Skip the frames we've missed entirely rather than trying to
+                // catch up (staying in step with the original schedule):
+                let missed = ((now - next).as_secs_f64() / self.period.as_secs_f64()) as u32;
+                self.dropped += missed as u64;
+                next += self.period * missed;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A handle for stopping a `FrameLoop` (see `FrameLoop.handle()`).
+#[derive(Debug, Clone)]
+pub struct FrameLoopHandle(Arc<AtomicBool>);
+
+impl FrameLoopHandle {
+
+    /// Makes `FrameLoop.run()` return after the frame it's currently on.
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,201 @@
+//! Turning the presses and releases of a polled input into clicks, double
+//! clicks, and holds.  See `Gestures`.
+
+use std::time::{Duration, Instant};
+use {ShifterIn, ShifterError, RegisterHandle, InputChange};
+
+/// Which button gestures `ShifterIn`'s polling thread (see
+/// `ShifterIn.start_polling()`) recognizes on top of plain presses and
+/// releases.  Set for every input via `ShifterIn.set_gestures()` or for
+/// individual pins via `ShifterIn.set_pin_gestures()`.  Everything defaults
+/// to `None` (off) in which case only presses and releases are sent;
+/// `InputChange::Clicked` is sent once either gesture is turned on.
+///
+/// Gestures are worked out from the debounced level of the input (see
+/// `Debounce`) and only looked at when the inputs are read so their timing
+/// is only as good as the polling rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Gestures {
+    /// Sends `InputChange::Held` once the input has been pressed for this
+    /// long (half a second or so feels natural).  A press that turns into a
+    /// hold isn't also a click.
+    pub hold: Option<Duration>,
+    /// Sends `InputChange::DoubleClicked` when the input is pressed again
+    /// within this long of being released (250-400 ms is typical).  This
+    /// delays `InputChange::Clicked` by as long since a click can't be told
+    /// apart from the first half of a double click any sooner.
+    pub double_click: Option<Duration>,
+}
+
+// Tracks the gestures of one input
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct GestureState {
+    pressed_at: Option<Instant>,
+    held: bool, // The current press has turned into a hold
+    double: bool, // The current press is the second half of a double click
+    click_at: Option<Instant>, // When a click that might turn into a double click was released
+}
+
+impl GestureState {
+
+    // Called when the (debounced) input goes HIGH
+    pub(crate) fn press(&mut self, at: Instant, gestures: Gestures) -> Option<InputChange> {
+        self.pressed_at = Some(at);
+        self.held = false;
+        self.double = false;
+        if let (Some(released), Some(window)) = (self.click_at.take(), gestures.double_click) {
+            if at.duration_since(released) <= window {
+                self.double = true;
+                return Some(InputChange::DoubleClicked);
+            }
+        }
+        None
+    }
+
+    // Called when the (debounced) input goes LOW
+    pub(crate) fn release(&mut self, at: Instant, gestures: Gestures) -> Option<InputChange> {
+        let was_pressed = self.pressed_at.take().is_some();
+        if !was_pressed || self.held || self.double {
+            return None;
+        }
+        match (gestures.hold, gestures.double_click) {
+            (None, None) => None, // Just presses and releases
+            (_, Some(_)) => {
+                self.click_at = Some(at); // Wait and see if it's pressed again
+                None
+            },
+            (Some(_), None) => Some(InputChange::Clicked),
+        }
+    }
+
+    // Called on every read to send whatever gestures are due by *at*
+    pub(crate) fn poll(&mut self, at: Instant, gestures: Gestures) -> Option<InputChange> {
+        if let Some(released) = self.click_at {
+            if gestures.double_click.is_none_or(|window| at.duration_since(released) > window) {
+                self.click_at = None;
+                return Some(InputChange::Clicked);
+            }
+        }
+        if let (Some(pressed), Some(hold), false) = (self.pressed_at, gestures.hold, self.held) {
+            if at.duration_since(pressed) >= hold {
+                self.held = true;
+                return Some(InputChange::Held);
+            }
+        }
+        None
+    }
+}
+
+impl ShifterIn {
+
+    /// Sets which gestures every input (apart from those given their own
+    /// setting via `set_pin_gestures()`) is watched for when polled (see
+    /// `Gestures`):
+    ///
+    /// ```
+    /// inputs.set_gestures(Gestures {
+    ///     hold: Some(Duration::from_millis(600)),
+    ///     double_click: Some(Duration::from_millis(300)),
+    /// });
+    /// let (poller, events) = inputs.start_polling(200.0)?;
+    /// for event in events {
+    ///     match event.change {
+    ///         InputChange::Clicked => println!("Click on {}", event.pin),
+    ///         InputChange::DoubleClicked => println!("Double click on {}", event.pin),
+    ///         InputChange::Held => println!("Holding {}", event.pin),
+    ///         _ => {},
+    ///     }
+    /// }
+    /// ```
+    pub fn set_gestures(&mut self, gestures: Gestures) {
+        self.gestures = gestures;
+    }
+
+    /// Sets which gestures the given *pin* on the given shift register (*sr*)
+    /// is watched for when polled (overriding `set_gestures()`).  Use
+    /// `Gestures::default()` for switches that aren't buttons.
+    pub fn set_pin_gestures(&mut self, sr: RegisterHandle, pin: u8, gestures: Gestures) -> Result<(), ShifterError> {
+        self.register(sr)?.check_pin(pin)?;
+        self.pin_gestures.insert((sr, pin), gestures);
+        Ok(())
+    }
+
+    /// Returns which gestures the given *pin* on the given shift register
+    /// (*sr*) is watched for when polled (see `set_gestures()` and
+    /// `set_pin_gestures()`).
+    pub fn gestures(&self, sr: RegisterHandle, pin: u8) -> Result<Gestures, ShifterError> {
+        self.register(sr)?.check_pin(pin)?;
+        Ok(self.pin_gestures_or_default(sr, pin))
+    }
+
+    pub(crate) fn pin_gestures_or_default(&self, sr: RegisterHandle, pin: u8) -> Gestures {
+        self.pin_gestures.get(&(sr, pin)).cloned().unwrap_or(self.gestures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use InputChange;
+    use super::{Gestures, GestureState};
+
+    fn both() -> Gestures {
+        Gestures {
+            hold: Some(Duration::from_millis(500)),
+            double_click: Some(Duration::from_millis(300)),
+        }
+    }
+
+    #[test]
+    fn click() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut state = GestureState::default();
+        // A click only counts once it's too late for a double click
+        assert_eq!(state.press(ms(0), both()), None);
+        assert_eq!(state.poll(ms(50), both()), None);
+        assert_eq!(state.release(ms(100), both()), None);
+        assert_eq!(state.poll(ms(400), both()), None);
+        assert_eq!(state.poll(ms(401), both()), Some(InputChange::Clicked));
+        assert_eq!(state.poll(ms(402), both()), None);
+    }
+
+    #[test]
+    fn double_click() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut state = GestureState::default();
+        assert_eq!(state.press(ms(0), both()), None);
+        assert_eq!(state.release(ms(100), both()), None);
+        assert_eq!(state.press(ms(300), both()), Some(InputChange::DoubleClicked));
+        assert_eq!(state.release(ms(400), both()), None); // Not a click as well
+        assert_eq!(state.poll(ms(1000), both()), None);
+    }
+
+    #[test]
+    fn hold() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut state = GestureState::default();
+        assert_eq!(state.press(ms(0), both()), None);
+        assert_eq!(state.poll(ms(499), both()), None);
+        assert_eq!(state.poll(ms(500), both()), Some(InputChange::Held));
+        assert_eq!(state.poll(ms(600), both()), None); // Only once per press
+        assert_eq!(state.release(ms(700), both()), None); // Not a click either
+        assert_eq!(state.poll(ms(1500), both()), None);
+    }
+
+    #[test]
+    fn click_on_release() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        // Without double clicks a click is sent on release
+        let gestures = Gestures { hold: Some(Duration::from_secs(1)), double_click: None };
+        let mut state = GestureState::default();
+        assert_eq!(state.press(ms(0), gestures), None);
+        assert_eq!(state.release(ms(500), gestures), Some(InputChange::Clicked));
+        assert_eq!(state.press(ms(600), Gestures::default()), None);
+        assert_eq!(state.poll(ms(5000), Gestures::default()), None);
+        assert_eq!(state.release(ms(5001), Gestures::default()), None); // No gestures at all
+    }
+}
@@ -0,0 +1,330 @@
+//! Reading banks of buttons and switches through parallel-in/serial-out shift
+//! registers like the 74HC165.  See `ShifterIn`.
+
+use std::collections::HashMap;
+use std::sync::atomic::Ordering as AtomicOrdering;
+use std::time::Duration;
+use {ShifterError, RegisterHandle, Order, Debounce, Gestures, InputPin, OutputPin, Timing, Delay, SpinDelay, NEXT_SHIFTER_ID};
+use register::ShiftRegister;
+
+/// Reads a daisy chain of input shift registers (e.g. 74HC165s) and keeps
+/// track of the state of every input so it can be looked up with the same
+/// sort of per-pin API `Shifter` has:
+///
+/// ```
+/// let mut inputs = ShifterIn::from_pins(qh_pin, load_pin, clock_pin);
+/// let buttons = inputs.add(8);
+/// let switches = inputs.add(8);
+/// inputs.name_pin(buttons, 0, "start")?;
+/// inputs.read()?; // Latches every input and clocks them all in
+/// if inputs.get_named("start")? {
+///     println!("Start pressed; switches: {:08b}", inputs.get(switches)?);
+/// }
+/// ```
+///
+/// *data* is connected to the serial output (QH) of the shift register
+/// nearest the Raspberry Pi, *load* to every shift register's parallel load
+/// (SH/LD) pin, and *clock* to every clock (CLK) pin.  The clock inhibit
+/// (CLK INH) pins should be tied LOW and the serial input (SER) of the last
+/// shift register in the chain to either rail.  Just like with `Shifter`
+/// the shift registers are declared in the order given by `set_order()`
+/// (`Order::LastToFirst`, the one furthest from the Raspberry Pi first, by
+/// default).
+///
+/// Nothing is read until `read()` is called; in between every method works
+/// on the inputs as they were when they were last read.
+pub struct ShifterIn {
+    id: usize,
+    data: Box<dyn InputPin>,
+    load: Box<dyn OutputPin>,
+    clock: Option<Box<dyn OutputPin>>, // None when sharing a Shifter's clock
+    timing: Timing,
+    delay: Box<dyn Delay>,
+    // Input registers can't be removed so a RegisterHandle's ID is also its
+    // position in here:
+    shift_registers: Vec<ShiftRegister>,
+    names: HashMap<String, (RegisterHandle, u8)>,
+    invert: bool,
+    order: Order,
+    pub(crate) debounce: Debounce, // See set_debounce()
+    pub(crate) pin_debounce: HashMap<(RegisterHandle, u8), Debounce>,
+    pub(crate) gestures: Gestures, // See set_gestures()
+    pub(crate) pin_gestures: HashMap<(RegisterHandle, u8), Gestures>,
+}
+
+impl std::fmt::Debug for ShifterIn {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ShifterIn")
+            .field("id", &self.id)
+            .field("invert", &self.invert)
+            .field("order", &self.order)
+            .field("shift_registers", &self.shift_registers)
+            .finish()
+    }
+}
+
+impl ShifterIn {
+
+    /// Returns a new `ShifterIn` that reads the chain using the given *data*
+    /// (QH), *load* (SH/LD), and *clock* (CLK) pins.
+    pub fn from_pins<D, L, C>(data: D, load: L, clock: C) -> ShifterIn
+        where D: InputPin + 'static, L: OutputPin + 'static, C: OutputPin + 'static
+    {
+        ShifterIn::new(Box::new(data), Box::new(load), Some(Box::new(clock)))
+    }
+
+    fn new(data: Box<dyn InputPin>, load: Box<dyn OutputPin>, clock: Option<Box<dyn OutputPin>>) -> ShifterIn {
+        ShifterIn {
+            id: NEXT_SHIFTER_ID.fetch_add(1, AtomicOrdering::SeqCst),
+            data: data,
+            load: load,
+            clock: clock,
+            timing: Timing::default(),
+            delay: Box::new(SpinDelay),
+            shift_registers: Vec::new(),
+            names: HashMap::new(),
+            invert: false,
+            order: Order::LastToFirst,
+            debounce: Debounce::Off,
+            pin_debounce: HashMap::new(),
+            gestures: Gestures::default(),
+            pin_gestures: HashMap::new(),
+        }
+    }
+
+    /// Adds a shift register with the given number of input *pins* (8 for a
+    /// 74HC165) to the chain and returns a handle to it.  Its inputs read as
+    /// LOW until the next `read()`.
+    pub fn add(&mut self, pins: u8) -> RegisterHandle {
+        let id = self.shift_registers.len();
+        self.shift_registers.push(ShiftRegister::new(id, pins));
+        RegisterHandle {
+            shifter: self.id,
+            id: id,
+        }
+    }
+
+    /// Sets the order in which shift registers are declared (see
+    /// `Shifter.set_order()`).
+    pub fn set_order(&mut self, order: Order) {
+        self.order = order;
+    }
+
+    /// Inverts every input (HIGH reads as LOW and LOW as HIGH) which is handy
+    /// for buttons that pull their inputs LOW when pressed.
+    pub fn invert(&mut self) {
+        self.invert = !self.invert;
+    }
+
+    /// Inverts the inputs of the given shift register (*sr*) only (on top of
+    /// `invert()`).  Takes effect on the next `read()`.
+    pub fn set_invert(&mut self, sr: RegisterHandle, invert: bool) -> Result<(), ShifterError> {
+        self.register_mut(sr)?.invert = invert;
+        Ok(())
+    }
+
+    /// Sets how long `read()` pauses between edges (see `Timing`):  `setup`
+    /// after each input is read, `hold` with the clock HIGH, and `latch` with
+    /// the load pin LOW.
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+    }
+
+    /// Sets how `read()` waits for the pauses given to `set_timing()` (see
+    /// `Shifter.set_delay()`).
+    pub fn set_delay<D: Delay + 'static>(&mut self, delay: D) {
+        self.delay = Box::new(delay);
+    }
+
+    /// Latches every input (by pulsing the load pin LOW) and clocks them all
+    /// in.  Afterwards `get()`, `get_pin()`, etc return the new state.
+    ///
+    /// Returns a `ShifterError::MissingPin` if the inputs share a `Shifter`'s
+    /// clock (see `Shifter.attach_inputs()`); use `Shifter.read_inputs()`
+    /// instead.
+    pub fn read(&mut self) -> Result<(), ShifterError> {
+        let mut clock = self.clock.take().ok_or(ShifterError::MissingPin("clock"))?;
+        let result = self.read_with(&mut *clock);
+        self.clock = Some(clock);
+        result
+    }
+
+    fn read_with(&mut self, clock: &mut dyn OutputPin) -> Result<(), ShifterError> {
+        self.load()?;
+        for input in self.read_order() {
+            self.sample(input)?;
+            self.wait(self.timing.setup);
+            clock.set_high()?;
+            self.wait(self.timing.hold);
+            clock.set_low()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the inputs of the given shift register (*sr*) as of the last
+    /// `read()` (pin 0 being the least significant bit).
+    pub fn get(&self, sr: RegisterHandle) -> Result<usize, ShifterError> {
+        Ok(self.register(sr)?.data())
+    }
+
+    /// Returns the inputs of the given shift register (*sr*) as of the last
+    /// `read()` as bytes (see `Shifter.get_bytes()`).
+    pub fn get_bytes(&self, sr: RegisterHandle) -> Result<Vec<u8>, ShifterError> {
+        Ok(self.register(sr)?.bytes().to_vec())
+    }
+
+    /// Returns `true` if the given *pin* on the given shift register (*sr*)
+    /// was HIGH as of the last `read()`.
+    pub fn get_pin(&self, sr: RegisterHandle, pin: u8) -> Result<bool, ShifterError> {
+        let reg = self.register(sr)?;
+        reg.check_pin(pin)?;
+        Ok(reg.pin(pin))
+    }
+
+    /// Returns every input in the chain as bytes in the same layout as
+    /// `Shifter.get_chain()`.
+    pub fn get_chain(&self) -> Vec<u8> {
+        let mut bytes = vec![0; self.total_pins().div_ceil(8)];
+        let mut bit = 0;
+        for sr in self.shift_registers.iter() {
+            for pin in 0..sr.pins {
+                if sr.pin(pin) {
+                    bytes[bit / 8] |= 1 << (bit % 8);
+                }
+                bit += 1;
+            }
+        }
+        bytes
+    }
+
+    /// Returns the input at the given *index* across the whole chain (see
+    /// `Shifter.get_chain_pin()`).
+    pub fn get_chain_pin(&self, index: usize) -> Result<bool, ShifterError> {
+        let mut remaining = index;
+        for sr in self.shift_registers.iter() {
+            if remaining < sr.pins as usize {
+                return Ok(sr.pin(remaining as u8));
+            }
+            remaining -= sr.pins as usize;
+        }
+        Err(ShifterError::InvalidPosition(index))
+    }
+
+    /// Gives the given *pin* on the given shift register (*sr*) a *name* so
+    /// it can be read via `get_named()`.
+    pub fn name_pin(&mut self, sr: RegisterHandle, pin: u8, name: &str) -> Result<(), ShifterError> {
+        self.register(sr)?.check_pin(pin)?;
+        self.names.insert(name.to_string(), (sr, pin));
+        Ok(())
+    }
+
+    /// Returns the shift register handle and pin that were given the *name*
+    /// (via `name_pin()`) or `None` if there's no such pin.
+    pub fn named_pin(&self, name: &str) -> Option<(RegisterHandle, u8)> {
+        self.names.get(name).cloned()
+    }
+
+    /// Returns `true` if the pin with the given *name* (see `name_pin()`) was
+    /// HIGH as of the last `read()`.
+    pub fn get_named(&self, name: &str) -> Result<bool, ShifterError> {
+        match self.names.get(name) {
+            Some(&(sr, pin)) => self.get_pin(sr, pin),
+            None => Err(ShifterError::UnknownName(name.to_string())),
+        }
+    }
+
+    /// Returns the number of shift registers in the chain.
+    pub fn len(&self) -> usize {
+        self.shift_registers.len()
+    }
+
+    /// Returns `true` if no shift registers have been added yet.
+    pub fn is_empty(&self) -> bool {
+        self.shift_registers.is_empty()
+    }
+
+    /// Returns the total number of input pins across every shift register in
+    /// the chain.
+    pub fn total_pins(&self) -> usize {
+        self.shift_registers.iter().map(|sr| sr.pins as usize).sum()
+    }
+
+    fn wait(&mut self, duration: Duration) {
+        if duration > Duration::ZERO {
+            self.delay.delay(duration);
+        }
+    }
+
+    // Returns a ShifterIn that reads its inputs while a Shifter clocks out
+    // its outputs (see Shifter.attach_inputs())
+    pub(crate) fn sharing_clock(data: Box<dyn InputPin>, load: Box<dyn OutputPin>) -> ShifterIn {
+        ShifterIn::new(data, load, None)
+    }
+
+    // Latches every input by pulsing the load pin LOW
+    pub(crate) fn load(&mut self) -> Result<(), ShifterError> {
+        self.load.set_low()?;
+        self.wait(self.timing.latch);
+        self.load.set_high()
+    }
+
+    // Returns the (position in self.shift_registers, pin) of every input in
+    // the order they come out of the chain
+    pub(crate) fn read_order(&self) -> Vec<(usize, u8)> {
+        let mut order = Vec::with_capacity(self.total_pins());
+        for i in 0..self.shift_registers.len() {
+            let position = self.read_position(i);
+            // The highest input (D7 on a 74HC165) comes out first:
+            for pin in (0..self.shift_registers[position].pins).rev() {
+                order.push((position, pin));
+            }
+        }
+        order
+    }
+
+    // Reads the data pin and stores its level as the given *input* (one of
+    // the entries from read_order())
+    pub(crate) fn sample(&mut self, input: (usize, u8)) -> Result<(), ShifterError> {
+        let (position, pin) = input;
+        let high = self.data.is_high()?;
+        let sr = &mut self.shift_registers[position];
+        let state = high != (self.invert != sr.invert);
+        sr.set_pin(pin, state);
+        Ok(())
+    }
+
+    // Returns the (handle, pin, level) of every input (as of the last read)
+    pub(crate) fn levels(&self) -> Vec<(RegisterHandle, u8, bool)> {
+        let mut levels = Vec::with_capacity(self.total_pins());
+        for (id, sr) in self.shift_registers.iter().enumerate() {
+            let handle = RegisterHandle { shifter: self.id, id: id };
+            for pin in 0..sr.pins {
+                levels.push((handle, pin, sr.pin(pin)));
+            }
+        }
+        levels
+    }
+
+    // Returns the position (in self.shift_registers) of the shift register
+    // that gets read *i*th (the one nearest the Raspberry Pi comes first)
+    fn read_position(&self, i: usize) -> usize {
+        match self.order {
+            Order::LastToFirst => self.shift_registers.len() - 1 - i,
+            Order::FirstToLast => i,
+        }
+    }
+
+    pub(crate) fn register(&self, sr: RegisterHandle) -> Result<&ShiftRegister, ShifterError> {
+        match self.shift_registers.get(sr.id) {
+            Some(reg) if sr.shifter == self.id => Ok(reg),
+            _ => Err(ShifterError::InvalidRegister(sr)),
+        }
+    }
+
+    fn register_mut(&mut self, sr: RegisterHandle) -> Result<&mut ShiftRegister, ShifterError> {
+        match self.shift_registers.get_mut(sr.id) {
+            Some(reg) if sr.shifter == self.id => Ok(reg),
+            _ => Err(ShifterError::InvalidRegister(sr)),
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! Scanning a matrix of keys whose rows are driven by a `Shifter`'s outputs
+//! and whose columns are read by input shift registers on the same chain
+//! (see `Shifter.attach_inputs()`).  See `Keypad`.
+
+use std::time::Instant;
+use {Shifter, ShifterError, RegisterHandle, Apply, Debounce, InputChange};
+use debounce::InputFilter;
+
+/// A key of a `Keypad` being pressed or released (see `Keypad.scan()`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// The key's row (an index into the rows given to `Keypad::new()`).
+    pub row: usize,
+    /// The key's column (an index into the columns given to
+    /// `Keypad::new()`).
+    pub column: usize,
+    /// `InputChange::Pressed` or `InputChange::Released`.
+    pub change: InputChange,
+    /// When the scan that noticed the change finished.
+    pub at: Instant,
+}
+
+/// Scans a matrix of keys (e.g. a 4×4 keypad or a whole front panel) where
+/// every row is connected to an output pin and every column to an input pin
+/// of the input shift registers attached to the same `Shifter` (see
+/// `Shifter.attach_inputs()`):
+///
+/// ```
+/// let rows = shifter.add(8);
+/// let columns = shifter.attach_inputs(qh_pin, load_pin).add(8);
+/// let mut keypad = Keypad::new(
+///     (0..4).map(|pin| (rows, pin)).collect(),
+///     (0..4).map(|pin| (columns, pin)).collect(),
+/// );
+/// keypad.set_debounce(Debounce::Samples(3));
+/// loop {
+///     for event in keypad.scan(&mut shifter)? {
+///         println!("Key {},{} {:?}", event.row, event.column, event.change);
+///     }
+///     thread::sleep(Duration::from_millis(5));
+/// }
+/// ```
+///
+/// By default the selected row is driven HIGH (every other row LOW) and a
+/// pressed key pulls its column HIGH so the columns need pull-down
+/// resistors; see `set_active_low()` for the opposite.  Other pins on the
+/// same shift registers as the rows keep whatever state they're given.
+///
+/// Without a diode on every key, pressing three keys at the corners of a
+/// rectangle makes the fourth corner look pressed too (ghosting).  Since
+/// there's no telling which of the four is the ghost, keys in such a
+/// rectangle keep the state they had before it appeared until it goes away.
+/// Use `set_diodes()` if the matrix has diodes (which prevent ghosting) to
+/// turn this off.
+#[derive(Debug, Clone)]
+pub struct Keypad {
+    rows: Vec<(RegisterHandle, u8)>,
+    columns: Vec<(RegisterHandle, u8)>,
+    active_low: bool,
+    diodes: bool,
+    debounce: Debounce,
+    keys: Vec<InputFilter>, // Row by row
+}
+
+impl Keypad {
+
+    /// Returns a new `Keypad` whose rows are connected to the given output
+    /// pins and columns to the given input pins (each being a shift register
+    /// handle and pin number).  Every key starts out released.
+    pub fn new(rows: Vec<(RegisterHandle, u8)>, columns: Vec<(RegisterHandle, u8)>) -> Keypad {
+        Keypad {
+            keys: vec![InputFilter::new(false); rows.len() * columns.len()],
+            rows: rows,
+            columns: columns,
+            active_low: false,
+            diodes: false,
+            debounce: Debounce::Off,
+        }
+    }
+
+    /// Sets whether the selected row is driven LOW (and every other row
+    /// HIGH) so that a pressed key pulls its column LOW.  Use this with
+    /// pull-up resistors on the columns.
+    pub fn set_active_low(&mut self, active_low: bool) {
+        self.active_low = active_low;
+    }
+
+    /// Sets whether every key has a diode in series with it, in which case
+    /// any combination of keys can be told apart and ghosting doesn't need
+    /// to be accounted for.
+    pub fn set_diodes(&mut self, diodes: bool) {
+        self.diodes = diodes;
+    }
+
+    /// Sets how keys are debounced from one `scan()` to the next (see
+    /// `Debounce`).
+    pub fn set_debounce(&mut self, debounce: Debounce) {
+        self.debounce = debounce;
+    }
+
+    /// Returns the number of rows.
+    pub fn rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Returns the number of columns.
+    pub fn columns(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Returns `true` if the key at the given *row* and *column* was pressed
+    /// as of the last `scan()`.
+    pub fn is_pressed(&self, row: usize, column: usize) -> bool {
+        row < self.rows.len() && column < self.columns.len()
+            && self.keys[row * self.columns.len() + column].level()
+    }
+
+    /// Returns the (row, column) of every key that was pressed as of the
+    /// last `scan()`.
+    pub fn pressed(&self) -> Vec<(usize, usize)> {
+        let columns = self.columns.len();
+        (0..self.keys.len())
+            .filter(|&i| self.keys[i].level())
+            .map(|i| (i / columns, i % columns))
+            .collect()
+    }
+
+    /// Selects every row in turn and reads the columns, returning a
+    /// `KeyEvent` for every key that was pressed or released since the last
+    /// scan.  Each `apply()` reads the columns as they were before it
+    /// latched the next row so a scan takes one `apply()` per row plus one
+    /// more (which leaves every row deselected).
+    ///
+    /// Returns a `ShifterError::MissingPin` if *shifter* has no inputs
+    /// attached or a `ShifterError::InvalidRegister` (or
+    /// `ShifterError::InvalidPin`) if any row or column doesn't exist.
+    pub fn scan(&mut self, shifter: &mut Shifter) -> Result<Vec<KeyEvent>, ShifterError> {
+        if shifter.inputs().is_none() {
+            return Err(ShifterError::MissingPin("input data"));
+        }
+        let mut raw = Vec::with_capacity(self.rows.len());
+        for selected in 0..self.rows.len() + 1 {
+            for (row, &(sr, pin)) in self.rows.iter().enumerate() {
+                if (row == selected) != self.active_low {
+                    shifter.set_pin_high(sr, pin, Apply::Later)?;
+                } else {
+                    shifter.set_pin_low(sr, pin, Apply::Later)?;
+                }
+            }
+            shifter.apply()?;
+            if selected > 0 {
+                // The columns as they were while the previous row was selected
+                let inputs = shifter.inputs().expect("checked above");
+                let mut columns = Vec::with_capacity(self.columns.len());
+                for &(sr, pin) in self.columns.iter() {
+                    columns.push(inputs.get_pin(sr, pin)? != self.active_low);
+                }
+                raw.push(columns);
+            }
+        }
+        let ambiguous = if self.diodes {
+            vec![vec![false; self.columns.len()]; self.rows.len()]
+        } else {
+            ghosts(&raw)
+        };
+        let at = Instant::now();
+        let mut events = Vec::new();
+        for (row, columns) in raw.iter().enumerate() {
+            for (column, &pressed) in columns.iter().enumerate() {
+                if ambiguous[row][column] {
+                    continue;
+                }
+                let key = &mut self.keys[row * self.columns.len() + column];
+                if let Some(pressed) = key.update(pressed, at, self.debounce) {
+                    let change = if pressed { InputChange::Pressed } else { InputChange::Released };
+                    events.push(KeyEvent { row: row, column: column, change: change, at: at });
+                }
+            }
+        }
+        Ok(events)
+    }
+}
+
+// Returns which keys of the *pressed* matrix (rows of columns) might be
+// ghosts:  Every key at a corner of a rectangle whose corners all look
+// pressed.
+pub(crate) fn ghosts(pressed: &[Vec<bool>]) -> Vec<Vec<bool>> {
+    let mut ambiguous: Vec<Vec<bool>> = pressed.iter().map(|row| vec![false; row.len()]).collect();
+    for a in 0..pressed.len() {
+        for b in a + 1..pressed.len() {
+            let shared: Vec<usize> = (0..pressed[a].len())
+                .filter(|&column| pressed[a][column] && pressed[b][column])
+                .collect();
+            if shared.len() >= 2 {
+                for &column in shared.iter() {
+                    ambiguous[a][column] = true;
+                    ambiguous[b][column] = true;
+                }
+            }
+        }
+    }
+    ambiguous
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ghosts;
+
+    #[test]
+    fn no_rectangle() {
+        let pressed = vec![vec![true, true, false], vec![true, false, false], vec![false, true, true]];
+        assert_eq!(ghosts(&pressed), vec![vec![false; 3]; 3]);
+    }
+
+    #[test]
+    fn rectangle() {
+        let pressed = vec![vec![true, true, false], vec![true, true, false], vec![false, true, true]];
+        assert_eq!(ghosts(&pressed), vec![vec![true, true, false], vec![true, true, false], vec![false; 3]]);
+    }
+}
@@ -18,7 +18,7 @@
 //!
 //! ```
 //! extern crate cupi_shift;
-//! use cupi_shift::Shifter;
+//! use cupi_shift::{Shifter, Apply};
 //!
 //! fn main() {
 //!     // First define which pins you're using for your shift register(s)
@@ -33,7 +33,7 @@
 //!     let sr0 = shifter.add(pins); // Starts tracking a new shift register
 //!
 //!     // Now we can set the state (aka data) of our shift register
-//!     shifter.set(sr0, 0b11111111, true); // Set all pins HIGH
+//!     shifter.set(sr0, 0b11111111, Apply::Now).unwrap(); // Set all pins HIGH
 //! }
 //!
 //! ```
@@ -45,6 +45,13 @@
 //!
 //! http://pi4j.com/images/j8header-2b-large.png
 //!
+//! If you'd rather use header (physical) or BCM numbers pass a `PinNumbering`
+//! and they'll be translated for you:
+//!
+//! ```
+//! let mut shifter = Shifter::with_numbering(40, 38, 36, PinNumbering::Physical).unwrap();
+//! ```
+//!
 //! # Controlling individual pins
 //!
 //! That's all well and good (setting the state of all pins at once) but what if
@@ -52,10 +59,10 @@
 //!
 //! ```
 //! // Set the 8th pin (aka pin 7) HIGH and apply this change immediately
-//! shifter.set_pin_high(sr0, 7, true); // NOTE: 3rd arg is 'apply'
+//! shifter.set_pin_high(sr0, 7, Apply::Now).unwrap(); // NOTE: 3rd arg is 'apply'
 //! // Set the first pin (aka pin 0) LOW but don't apply just yet
-//! shifter.set_pin_low(sr0, 0, false);
-//! shifter.apply(); // Apply the change (the other way to apply changes)
+//! shifter.set_pin_low(sr0, 0, Apply::Later).unwrap();
+//! shifter.apply().unwrap(); // Apply the change (the other way to apply changes)
 //! ```
 //!
 //! # Controlling multiple shift registers
@@ -65,12 +72,12 @@
 //! together you can add and control them individually like so:
 //!
 //! ```
-//! let last = shifter.add(8); // Add an 8-pin shift register (sr_index: 0)
-//! let first = shifter.add(8); // Add another (sr_index: 1)
+//! let last = shifter.add(8); // Add an 8-pin shift register (index: 0)
+//! let first = shifter.add(8); // Add another (index: 1)
 //! // Set pin 0 HIGH on shift register 0 (all others LOW) but don't apply the change yet
-//! shifter.set(last, 0b00000001, false);
+//! shifter.set(last, 0b00000001, Apply::Later).unwrap();
 //! // Set pin 7 HIGH on shift register 1 (all others LOW) and apply the change
-//! shifter.set(first, 0b10000000, true);
+//! shifter.set(first, 0b10000000, Apply::Now).unwrap();
 //! ```
 //!
 //! **Note:** Shift registers need to be added in the order in which they are
@@ -79,69 +86,708 @@
 //! time data is "shifted out" to a shift register it dumps its memory to the
 //! the next shift register in the chain.
 //!
+//! If you'd rather add your shift registers in the order in which they're
+//! wired you can tell the `Shifter` to reverse things for you:
+//!
+//! ```
+//! shifter.set_order(Order::FirstToLast);
+//! let first = shifter.add(8); // The one connected to the Raspberry Pi
+//! let last = shifter.add(8); // The next one in the chain
+//! ```
+//!
 //! You can also apply changes to individual pins on individual shift registers:
 //!
 //! ```
-//! shifter.set_pin_high(sr1, 2, false); // Set pin 2 HIGH on shift register 1
-//! shifter.set_pin_low(sr0, 3, true); // Set pin 3 LOW on shift register 0 (and apply)
+//! shifter.set_pin_high(sr1, 2, Apply::Later).unwrap(); // Set pin 2 HIGH on shift register 1
+//! shifter.set_pin_low(sr0, 3, Apply::Now).unwrap(); // Set pin 3 LOW on shift register 0 (and apply)
+//! ```
+//!
+//! In the above example we didn't set the *apply* (3rd) argument to
+//! `Apply::Now` until the we were done making our changes.  If we set *apply*
+//! to `Apply::Now` on each we could wind up with some flickering.  The more
+//! shift registers you have in your chain the more flickering you can get if
+//! you call `apply()` with every state (aka data) change.
+//!
+//! To make that impossible to get wrong you can make all your changes inside
+//! a `batch()` which applies everything exactly once at the end:
+//!
+//! ```
+//! shifter.batch(|tx| {
+//!     tx.set_pin_high(sr1, 2)?;
+//!     tx.set_pin_low(sr0, 3)
+//! }).unwrap();
+//! ```
+//!
+//! # Error handling
+//!
+//! All the methods that change the state of a shift register (and `apply()`)
+//! return a `Result<_, ShifterError>`.  If you pass a shift register handle
+//! from a different `Shifter` or a pin that doesn't exist on the shift
+//! register in question you'll get a `ShifterError::InvalidRegister` or
+//! `ShifterError::InvalidPin` (respectively) instead of having the change
+//! silently go nowhere.
+//!
+//! # Using a different GPIO library
+//!
+//! By default `Shifter::new()` uses [CuPi][1] to drive the data, latch, and
+//! clock pins.  If you want to use something else just implement the
+//! `OutputPin` trait for its pins and hand them to
+//! `Shifter::from_output_pins()` (pins have to be `Send`):
+//!
+//! ```
+//! let mut shifter = Shifter::from_output_pins(data_pin, latch_pin, clock_pin);
+//! ```
+//!
+//! # Testing without hardware
+//!
+//! `Shifter::mock()` returns a `Shifter` that isn't connected to anything along
+//! with a `MockChain` that keeps track of what a real chain would be doing.  Use
+//! it to unit test your code on any machine (e.g. CI):
+//!
+//! ```
+//! let (mut shifter, mock) = Shifter::mock();
+//! let sr0 = shifter.add(8);
+//! shifter.set(sr0, 0b00000101, Apply::Now).unwrap();
+//! assert_eq!(mock.output(0), Some(true));
+//! assert_eq!(mock.apply_count(), 1);
+//! ```
+//!
+//! If you care about *how* things get shifted out (e.g. the timing of the data,
+//! latch, and clock lines) use `Shifter::recording()` instead which records
+//! every transition of every line (`Recording.save_vcd()` saves it as a VCD
+//! file you can open in GTKWave).  `Shifter::recorded()` does the same for real
+//! pins.
+//!
+//! # Using threads
+//!
+//! Multiplexed displays (and anything else that needs the chain re-shifted
+//! continuously) can hand the `Shifter` over to a background thread that
+//! applies its state at a fixed rate.  Make changes through the returned
+//! `Refresh` handle:
+//!
+//! ```
+//! let refresh = shifter.start_refresh(200.0).unwrap(); // 200 times a second
+//! refresh.lock().set(sr0, 0b10101010, Apply::Later).unwrap();
+//! let shifter = refresh.stop(); // Get the Shifter back
+//! ```
+//!
+//! To control the same chain from several threads use `Shifter.into_shared()`
+//! which returns a `SharedShifter` that can be cloned and sent anywhere.  Every
+//! call locks the `Shifter` for its duration so an `apply()` always shifts out
+//! a consistent state.  Alternatively `Shifter.spawn()` moves the `Shifter`
+//! to a thread of its own and returns a cloneable `ShifterClient` that sends
+//! it `Command`s (a natural fit for async web servers and GUI apps).
+//!
+//! # Dimming
+//!
+//! Plain shift register outputs are either on or off but LEDs can be dimmed by
+//! switching them on for only part of the time.  Set a pin's brightness and
+//! keep the chain refreshing in the background:
+//!
+//! ```
+//! shifter.set_brightness(sr0, 3, 0.3).unwrap(); // 30% brightness
+//! let refresh = shifter.start_refresh(3200.0).unwrap();
+//! ```
+//!
+//! To dim the whole chain at once wire the output enable pin to a GPIO with
+//! hardware PWM (see `Shifter.set_output_enable_pwm()`) and use
+//! `set_global_brightness()`.
+//!
+//! # Animations
+//!
+//! Fades, pulses, and chases can be defined once, given a name, and started or
+//! stopped whenever you like.  The `Shifter` updates them from `tick()`, the
+//! refresh thread, or its own thread (see `spawn()`):
+//!
+//! ```
+//! let fade = Animation::fade(Target::Group("ring".into()), 0.0, 1.0, Duration::from_secs(2));
+//! shifter.add_animation("fade_in", fade).unwrap();
+//! shifter.start_animation("fade_in").unwrap();
+//! ```
+//!
+//! # Reading inputs
+//!
+//! Banks of buttons and switches can be read through 74HC165 (or similar
+//! parallel-in/serial-out) shift registers with a `ShifterIn`, which works
+//! just like a `Shifter` in reverse:
+//!
+//! ```
+//! let mut inputs = ShifterIn::with_rppal(26, 19, 13).unwrap(); // QH, SH/LD, CLK
+//! let buttons = inputs.add(8);
+//! inputs.read().unwrap();
+//! if inputs.get_pin(buttons, 0).unwrap() {
+//!     println!("Button 0 is pressed");
+//! }
+//! ```
+//!
+//! If the 165s share a clock line with your 595s attach them to the `Shifter`
+//! instead so every `apply()` reads the inputs while it shifts out the
+//! outputs (see `Shifter.attach_inputs()`):
+//!
+//! ```
+//! let leds = shifter.add(8);
+//! let buttons = shifter.attach_inputs(qh_pin, load_pin).add(8);
+//! shifter.set(leds, 0b00000001, Apply::Now).unwrap(); // Reads the buttons too
+//! let pressed = shifter.inputs().unwrap().get(buttons).unwrap();
+//! ```
+//!
+//! To have a background thread read the inputs and tell you whenever one
+//! changes use `ShifterIn.start_polling()`:
+//!
 //! ```
+//! let (poller, events) = inputs.start_polling(100.0).unwrap(); // 100 times a second
+//! for event in events {
+//!     println!("{:?} pin {} {:?}", event.register, event.pin, event.change);
+//! }
+//! ```
+//!
+//! Mechanical switches bounce so give the poller a `Debounce` (via
+//! `ShifterIn.set_debounce()` or `set_pin_debounce()`) to get one event per
+//! press.  Buttons can also send clicks, double clicks, and holds (see
+//! `Gestures`).
+//!
+//! Key matrices can be scanned with the rows on a `Shifter`'s outputs and the
+//! columns on its attached inputs (see `Keypad`).
 //!
-//! In the above example we didn't set the *apply* (3rd) argument to `true`
-//! until the we were done making our changes.  If we set *apply* to `true` on
-//! each we could wind up with some flickering.  The more shift registers you
-//! have in your chain the more flickering you can get if you call `apply()`
-//! with every state (aka data) change.
+//! # Cargo features
+//!
+//! * `cupi` (enabled by default): Drives the pins via [CuPi][1] (see
+//!   `Shifter::new()`).  Turn off default features (`default-features =
+//!   false`) if you're using a different backend or building on something
+//!   other than a Raspberry Pi; everything else (including `Shifter::mock()`)
+//!   works without it.
+//! * `serde`: Derives `Serialize` and `Deserialize` for `Snapshot` (see
+//!   `Shifter.snapshot()`) and the configuration types (`RegisterConfig`,
+//!   `Order`, etc) so you can store or send the state of your chain in any
+//!   format supported by [Serde][5].
+//! * `rppal`: Adds `Shifter::with_rppal()` which drives the pins via
+//!   [rppal][6] (a maintained GPIO library that supports newer Raspberry Pi
+//!   models) instead of CuPi.
+//! * `gpio-cdev`: Adds `Shifter::with_gpio_cdev()` which drives the pins via
+//!   the Linux GPIO character device (`/dev/gpiochipN`) so it works on just
+//!   about any Linux board (not just the Raspberry Pi).  Also adds
+//!   `Shifter::with_bcm_pins()` which finds the right chip on any Raspberry Pi
+//!   model.  This is what you want on a Raspberry Pi 5 (CuPi doesn't support
+//!   it).
+//! * `embedded-hal`: Adds `Shifter::from_hal_pins()` which accepts any pins
+//!   that implement [embedded-hal][7]'s `OutputPin` trait.
+//! * `linux-embedded-hal`: Adds `Shifter::from_cdev_pins()` and
+//!   `Shifter::with_cdev_pins()` for use with [linux-embedded-hal][8]'s
+//!   `CdevPin` (implies `gpio-cdev`).
+//! * `spidev`: Adds `Shifter::with_spidev()` which shifts data out via
+//!   hardware SPI (many times faster than toggling GPIO pins) while the latch
+//!   remains a regular GPIO pin.
+//! * `gpiomem`: Adds `Shifter::with_gpiomem()` which toggles pins by writing
+//!   straight to the GPIO registers via `/dev/gpiomem` (the fastest way to
+//!   bit-bang; Raspberry Pi 4 and earlier).
+//! * `terminal`: Adds `Shifter::terminal()` which draws the state of the chain
+//!   in your terminal on every `apply()` so you can work on animations without
+//!   a Raspberry Pi.
+//! * `async`: Adds `Shifter.into_async()` which returns an `AsyncShifter`
+//!   whose methods return futures (that work with any async runtime) instead
+//!   of blocking while the chain is shifted out.
+//! * `json` and `ron`: Add `Sequence::from_json()`, `Sequence::from_ron()`,
+//!   and `Shifter.play_file()` for playing back light sequences written as
+//!   JSON or RON files (implies `serde`).
+//! * `signals`: Adds `SignalShutdown` which puts the chain into a safe state
+//!   (e.g. everything off) when the process gets SIGINT (Ctrl-C) or SIGTERM.
+//! * `realtime`: Adds `Refresh.set_realtime_priority()` which runs the
+//!   refresh thread under the `SCHED_FIFO` real-time scheduling policy so
+//!   multiplexed displays and software PWM don't flicker when the system is
+//!   busy (Linux).
 //!
 //!
 //! [1]: https://crates.io/crates/cupi
 //! [2]: https://www.adafruit.com/product/732
 //! [3]: https://www.sparkfun.com/datasheets/IC/SN74HC595.pdf
 //! [4]: https://en.wikipedia.org/wiki/Shift_register
+//! [5]: https://serde.rs/
+//! [6]: https://crates.io/crates/rppal
+//! [7]: https://crates.io/crates/embedded-hal
+//! [8]: https://crates.io/crates/linux-embedded-hal
 
 #![allow(dead_code, unused_variables)]
 
+// The register and protocol modules only use core and alloc (no std)
+extern crate core;
+extern crate alloc;
+#[cfg(feature = "cupi")]
 extern crate cupi;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(feature = "rppal")]
+extern crate rppal;
+#[cfg(feature = "json")]
+extern crate serde_json;
+#[cfg(feature = "ron")]
+extern crate ron;
+#[cfg(feature = "signals")]
+extern crate signal_hook;
+#[cfg(feature = "realtime")]
+extern crate libc;
+#[cfg(feature = "gpio-cdev")]
+extern crate gpio_cdev;
+#[cfg(feature = "embedded-hal")]
+extern crate embedded_hal;
+#[cfg(feature = "linux-embedded-hal")]
+extern crate embedded_hal_02;
+#[cfg(feature = "linux-embedded-hal")]
+extern crate linux_embedded_hal;
+#[cfg(feature = "spidev")]
+extern crate spidev;
+#[cfg(feature = "gpiomem")]
+extern crate memmap2;
+
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "cupi")]
+use cupi::{CuPi, PinOutput};
 
-// Using a singly-linked list to represent the chain of shift registers since
-// it accurately represents how they're physically linked together.
-use std::collections::LinkedList;
-use std::cell::RefCell;
-use cupi::{CuPi, PinOutput, DigitalWrite};
+mod actor;
+mod animations;
+mod backend;
+mod batch;
+mod blink;
+#[cfg(feature = "cupi")]
+mod builder;
+mod calibrate;
+mod debounce;
+mod dimming;
+mod easing;
+mod frame;
+mod frameloop;
+mod gesture;
+mod input;
+mod keypad;
+mod mixed;
+#[cfg(feature = "async")]
+mod nonblocking;
+mod numbering;
+mod pattern;
+mod persist;
+mod poll;
+mod protocol;
+mod recurrence;
+mod refresh;
+mod schedule;
+mod sequence;
+#[cfg(any(feature = "json", feature = "ron"))]
+mod sequence_file;
+mod register;
+mod shared;
+mod shutdown;
+#[cfg(feature = "signals")]
+mod signals;
+mod snapshot;
+mod sun;
+mod timing;
 
+pub use actor::{Command, ShifterClient};
+pub use animations::{Animation, Target};
+use animations::Animations;
+pub use backend::{OutputPin, InputPin, PwmPin, ShiftBus, MockChain, MockInputs, Recording, Signal, Event};
+use backend::NullPin;
+#[cfg(feature = "cupi")]
+pub use backend::GpioAccess;
+#[cfg(feature = "cupi")]
+use backend::{MmapPin, SysfsPin};
+#[cfg(feature = "embedded-hal")]
+pub use backend::HalPin;
+pub use batch::{Batch, Deferred};
+#[cfg(feature = "cupi")]
+pub use builder::ShifterBuilder;
+pub use calibrate::Calibration;
+pub use debounce::Debounce;
+pub use dimming::DimmingMode;
+pub use easing::Easing;
+pub use frame::Frame;
+pub use frameloop::{FrameLoop, FrameLoopHandle};
+pub use gesture::Gestures;
+pub use input::ShifterIn;
+pub use keypad::{Keypad, KeyEvent};
+#[cfg(feature = "async")]
+pub use nonblocking::{AsyncShifter, ShifterFuture};
+pub use numbering::PinNumbering;
+pub use pattern::Pattern;
+pub use poll::{InputPoller, InputEvent, InputChange};
+pub use protocol::{ShiftProtocol, Hc595, Max7219};
+pub use recurrence::{Recurrence, Weekday};
+pub use refresh::Refresh;
+pub use schedule::{Schedule, TimeOfDay};
+pub use shutdown::{DropPolicy, Failsafe};
+#[cfg(feature = "signals")]
+pub use signals::SignalShutdown;
+pub use sun::SunEvent;
+pub use sequence::{Sequence, Step, Repeat};
+use sequence::Player;
+pub use register::{ShiftRegister, BitOrder, RegisterConfig};
+pub use shared::{SharedShifter, SplitRegister};
+pub use snapshot::{Snapshot, RegisterSnapshot};
+pub use timing::{Delay, SpinDelay, NoDelay, Timing, Polarity, LatchMode, ClockMode};
+use blink::Blink;
+use dimming::Dimmer;
 
-struct ShiftRegister {
-    data: usize, // e.g. 0b01010101
-    pins: u8, // Not aware of any shift registers that have more than 255 output pins
+/// The error type returned by all of `Shifter`'s fallible methods.
+#[derive(Debug)]
+pub enum ShifterError {
+    /// The given handle doesn't refer to a shift register on this `Shifter`
+    /// (e.g. it was returned by a different `Shifter`'s `add()` or the shift
+    /// register was `remove()`d).
+    InvalidRegister(RegisterHandle),
+    /// The given pin doesn't exist on the shift register in question (e.g. pin
+    /// 8 on an 8-pin shift register; pin numbers start at 0).
+    InvalidPin(u8),
+    /// The given GPIO pin doesn't exist in the chosen numbering scheme (see
+    /// `PinNumbering`).
+    InvalidGpio(usize),
+    /// The given position is past the end of the chain.
+    InvalidPosition(usize),
+    /// A required pin (data, latch, or clock) wasn't given to a
+    /// `ShifterBuilder`.
+    MissingPin(&'static str),
+    /// There's no pin (or group of pins) with the given name.
+    UnknownName(String),
+    /// There's no animation with the given name (see
+    /// `Shifter.add_animation()`).
+    UnknownAnimation(String),
+    /// The given data was the wrong length for the shift register(s) in
+    /// question.
+    InvalidLength {
+        /// The number of bytes that were expected
+        expected: usize,
+        /// The number of bytes that were given
+        actual: usize,
+    },
+    /// A pin remapping table didn't contain every pin on the shift register
+    /// exactly once.
+    InvalidRemap,
+    /// The given rate (in Hz) wasn't a positive number.
+    InvalidRate(f64),
+    /// The given brightness wasn't between 0.0 and 1.0.
+    InvalidBrightness(f64),
+    /// The given gamma wasn't a positive number.
+    InvalidGamma(f64),
+    /// The given time of day wasn't valid (see `TimeOfDay`).
+    InvalidTime(String),
+    /// The given cron expression wasn't valid (see `Recurrence`).
+    InvalidRecurrence(String),
+    /// A schedule with sun events has no (or an invalid) location (see
+    /// `Schedule.location()`).
+    InvalidLocation(String),
+    /// The given real-time priority is out of range (see
+    /// `Refresh.set_realtime_priority()`).
+    InvalidPriority(i32),
+    /// The chain can't be refreshed as often as requested (see
+    /// `Calibration.check_refresh_rate()`).
+    TooSlow {
+        /// The requested refresh rate (in Hz)
+        requested: f64,
+        /// The best refresh rate (in Hz) that can be achieved
+        achievable: f64,
+    },
+    /// A `Snapshot` or `Frame` doesn't match the layout of the chain (the
+    /// number of shift registers or their pin counts differ).
+    LayoutMismatch,
+    /// The thread running the `Shifter` has stopped (see `ShifterClient`).
+    Disconnected,
+    /// The requested operation can't be done with the way this `Shifter` is
+    /// set up (e.g. sending part of a byte through a `ShiftBus`).
+    Unsupported(&'static str),
+    /// The underlying GPIO library (CuPi) returned an error while toggling the
+    /// data, latch, or clock pin.
+    #[cfg(feature = "cupi")]
+    GpioError(cupi::Error),
+    /// Reading or writing a file failed (e.g. in `Shifter.save_state()`).
+    IoError(std::io::Error),
+    /// A GPIO backend other than CuPi (e.g. rppal, gpio-cdev, or an
+    /// embedded-hal pin) returned an error while setting up or toggling the
+    /// data, latch, or clock pin.
+    BackendError(Box<dyn std::error::Error + Send + Sync>),
 }
 
-// This is great for debugging; displays the Shift Register data in binary:
-impl std::fmt::Display for ShiftRegister {
+impl std::fmt::Display for ShifterError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let string = format!("{:b}", self.data);
-        let pad = (self.pins as usize) - string.len();
-        let _ = f.write_str("0b");
-        for _ in 0..pad { let _ = f.write_str("0").unwrap(); }
-        f.pad_integral(true, "", &string)
+        match *self {
+            ShifterError::InvalidRegister(sr) => write!(f, "Shift register {} does not exist on this Shifter", sr.id),
+            ShifterError::InvalidPin(pin) => write!(f, "Pin {} does not exist on this shift register", pin),
+            ShifterError::InvalidGpio(pin) => write!(f, "Pin {} is not a GPIO pin", pin),
+            ShifterError::InvalidPosition(position) => write!(f, "Position {} is past the end of the chain", position),
+            ShifterError::MissingPin(pin) => write!(f, "No {} pin was given", pin),
+            ShifterError::UnknownName(ref name) => write!(f, "No pin or group named {:?}", name),
+            ShifterError::UnknownAnimation(ref name) => write!(f, "No animation named {:?}", name),
+            ShifterError::InvalidLength { expected, actual } => write!(f, "Expected {} bytes of data but got {}", expected, actual),
+            ShifterError::InvalidRemap => write!(f, "Remapping tables must contain every pin exactly once"),
+            ShifterError::InvalidRate(hz) => write!(f, "Invalid rate: {} Hz", hz),
+            ShifterError::InvalidBrightness(brightness) => write!(f, "Invalid brightness: {} (must be between 0.0 and 1.0)", brightness),
+            ShifterError::InvalidGamma(gamma) => write!(f, "Invalid gamma: {}", gamma),
+            ShifterError::InvalidTime(ref time) => write!(f, "Invalid time of day: {:?}", time),
+            ShifterError::InvalidRecurrence(ref cron) => write!(f, "Invalid cron expression: {:?}", cron),
+            ShifterError::InvalidLocation(ref location) => write!(f, "Invalid location for sun events: {}", location),
+            ShifterError::InvalidPriority(priority) => write!(f, "Invalid real-time priority: {}", priority),
+            ShifterError::TooSlow { requested, achievable } => write!(f, "Can't refresh at {} Hz (only {:.1} Hz is possible)", requested, achievable),
+            ShifterError::LayoutMismatch => write!(f, "The snapshot or frame doesn't match the layout of this chain"),
+            ShifterError::Disconnected => write!(f, "The shifter's thread has stopped"),
+            ShifterError::Unsupported(what) => write!(f, "Unsupported: {}", what),
+            #[cfg(feature = "cupi")]
+            ShifterError::GpioError(ref err) => write!(f, "GPIO error: {:?}", err),
+            ShifterError::IoError(ref err) => write!(f, "I/O error: {}", err),
+            ShifterError::BackendError(ref err) => write!(f, "GPIO backend error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ShifterError {}
+
+#[cfg(feature = "cupi")]
+impl From<cupi::Error> for ShifterError {
+    fn from(err: cupi::Error) -> ShifterError {
+        ShifterError::GpioError(err)
+    }
+}
+
+impl From<std::io::Error> for ShifterError {
+    fn from(err: std::io::Error) -> ShifterError {
+        ShifterError::IoError(err)
+    }
+}
+
+// Returns the time between events happening *hz* times per second or a
+// ShifterError::InvalidRate if *hz* isn't a positive number (or is so small
+// that the period doesn't fit in a Duration)
+pub(crate) fn period_from_hz(hz: f64) -> Result<Duration, ShifterError> {
+    if !hz.is_finite() || hz <= 0.0 {
+        return Err(ShifterError::InvalidRate(hz));
+    }
+    Duration::try_from_secs_f64(1.0 / hz).map_err(|_| ShifterError::InvalidRate(hz))
+}
+
+// Converts a remapping table as given to Shifter.set_remap() (logical pin ->
+// physical output) into the physical output -> logical pin form that apply()
+// uses, making sure it contains every pin exactly once
+fn physical_to_logical(remap: &[u8], pins: u8) -> Result<Vec<u8>, ShifterError> {
+    if remap.len() != pins as usize {
+        return Err(ShifterError::InvalidRemap);
     }
+    let mut table: Vec<Option<u8>> = vec![None; remap.len()];
+    for (logical, &physical) in remap.iter().enumerate() {
+        let physical = physical as usize;
+        // Catch out-of-range and duplicate entries:
+        if physical >= remap.len() || table[physical].is_some() {
+            return Err(ShifterError::InvalidRemap);
+        }
+        table[physical] = Some(logical as u8);
+    }
+    Ok(table.into_iter().map(|l| l.unwrap()).collect())
 }
 
 impl ShiftRegister {
 
-    fn set(&mut self, data: usize) {
-        self.data = data;
+    // Makes sure the given *pin* actually exists on this shift register
+    fn check_pin(&self, pin: u8) -> Result<(), ShifterError> {
+        if pin < self.pins {
+            Ok(())
+        } else {
+            Err(ShifterError::InvalidPin(pin))
+        }
+    }
+
+    // Makes sure *bytes* is the right length to hold this shift register's data
+    fn check_bytes(&self, bytes: &[u8]) -> Result<(), ShifterError> {
+        if bytes.len() == self.data.bytes.len() {
+            Ok(())
+        } else {
+            Err(ShifterError::InvalidLength { expected: self.data.bytes.len(), actual: bytes.len() })
+        }
+    }
+}
+
+// Every Shifter gets a unique ID so that handles from one can't be used with
+// another:
+static NEXT_SHIFTER_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A handle to one of the shift registers being tracked by a `Shifter`.  These
+/// are returned by `Shifter.add()` and are what all the other `Shifter` methods
+/// use to refer to a particular shift register.
+///
+/// Handles are tied to the `Shifter` that created them:  Passing a handle to a
+/// different `Shifter` will result in a `ShifterError::InvalidRegister`.
+///
+/// Handles stay valid (and keep referring to the same shift register) when
+/// other shift registers are removed from the chain; use `Shifter.position()`
+/// to find out where a shift register currently sits in the chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RegisterHandle {
+    shifter: usize,
+    id: usize,
+}
+
+/// Whether a change should be applied (shifted out to the shift registers)
+/// immediately or not.  This is the last argument to all of the `Shifter`
+/// methods that change state:
+///
+/// ```
+/// shifter.set_pin_high(sr0, 7, Apply::Later).unwrap(); // Stage a change
+/// shifter.set_pin_low(sr0, 0, Apply::Now).unwrap(); // Apply both changes
+/// ```
+///
+/// These methods used to take a plain `bool` (`true` being `Apply::Now` and
+/// `false` being `Apply::Later`) but `shifter.set(sr0, 0xFF, true)` doesn't
+/// say what `true` means.  `Shifter.set_bool()`,
+/// `Shifter.set_pin_high_bool()`, and `Shifter.set_pin_low_bool()` still
+/// take one but they're deprecated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Apply {
+    /// Apply the change immediately.
+    Now,
+    /// Just track the change; it'll go out with the next `apply()`.
+    Later,
+    /// Apply the change immediately only if auto-apply is enabled (see
+    /// `Shifter.set_auto_apply()`).
+    Auto,
+}
+
+impl Apply {
+
+    // What the old bool apply flag meant (see Shifter.set_bool())
+    fn from_bool(apply: bool) -> Apply {
+        if apply { Apply::Now } else { Apply::Later }
+    }
+}
+
+/// Statistics about a single `Shifter.apply()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ApplyStats {
+    /// The number of bits that were shifted out (the total number of pins in
+    /// the chain).
+    pub bits: usize,
+    /// How long the whole shift-out (including latching) took.
+    pub duration: Duration,
+}
+
+impl ApplyStats {
+    /// Returns the effective clock rate (in Hz) that was achieved:  The number
+    /// of bits shifted out divided by how long it took.
+    pub fn clock_rate(&self) -> f64 {
+        let secs = self.duration.as_secs() as f64 + self.duration.subsec_nanos() as f64 / 1e9;
+        if secs > 0.0 { self.bits as f64 / secs } else { 0.0 }
     }
+}
+
+/// A named group of pins (possibly spanning multiple shift registers) that can
+/// be controlled together.  See `Shifter.define_group()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinGroup {
+    pins: Vec<(RegisterHandle, u8)>,
+}
 
-    fn get_ref(self) -> RefCell<ShiftRegister> {
-        RefCell::new(self)
+impl PinGroup {
+    /// Returns the `(shift register, pin)` pairs that make up this group.
+    pub fn pins(&self) -> &[(RegisterHandle, u8)] {
+        &self.pins
     }
 }
 
+/// The order in which shift registers are declared (via `Shifter.add()`).  See
+/// `Shifter.set_order()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Order {
+    /// The *last* shift register in the chain gets added first (the default).
+    /// This matches the order in which data is shifted out.
+    LastToFirst,
+    /// The *first* shift register in the chain (the one wired directly to the
+    /// Raspberry Pi) gets added first.
+    FirstToLast,
+}
+
 pub struct Shifter {
-    pub data: PinOutput,
-    pub latch: PinOutput,
-    pub clock: PinOutput,
-    shift_registers: LinkedList<ShiftRegister>,
+    id: usize,
+    pub data: Box<dyn OutputPin>,
+    pub latch: Box<dyn OutputPin>,
+    pub clock: Box<dyn OutputPin>,
+    // Replaces the data and clock pins if set (see from_bus()):
+    bus: Option<Box<dyn ShiftBus>>,
+    // What apply() is about to shift out (kept around to avoid reallocating):
+    buffer: Vec<u8>,
+    timing: Timing,
+    delay: Box<dyn Delay>,
+    latch_polarity: Polarity,
+    latch_mode: LatchMode,
+    clock_mode: ClockMode,
+    // The output enable pin (if any) and which level enables the outputs:
+    oe: Option<(Box<dyn OutputPin>, Polarity)>,
+    oe_pwm: Option<(Box<dyn PwmPin>, Polarity)>, // Replaces oe if set
+    outputs_enabled: bool,
+    // Same for the shift register clear (SRCLR) pin:
+    clear: Option<(Box<dyn OutputPin>, Polarity)>,
+    // Shift registers are stored in the order they were added (which is the
+    // order in which they get shifted out).  A Vec gives us O(1) lookups by
+    // index and keeps apply() iterating over contiguous memory:
+    shift_registers: Vec<ShiftRegister>,
+    // Maps RegisterHandle IDs to their current position in shift_registers
+    // (None if the shift register was removed):
+    positions: Vec<Option<usize>>,
+    names: HashMap<String, (RegisterHandle, u8)>,
+    groups: HashMap<String, PinGroup>,
+    blinks: HashMap<(RegisterHandle, u8), Blink>,
+    dimmer: Dimmer,
+    animations: Animations,
+    player: Option<Player>, // The sequence that's playing (see play())
+    schedule: Option<Schedule>,
+    back: Option<Frame>, // The back buffer (see back_buffer())
+    inputs: Option<ShifterIn>, // Read during apply() (see attach_inputs())
+    drop_policy: DropPolicy,
+    failsafe: DropPolicy, // Used instead of drop_policy during a panic
     invert: bool,
+    order: Order,
+    auto_apply: bool,
+    dirty: bool, // True if anything changed since the last apply()
+    // Applies requested within this long of each other go out together (see
+    // set_coalesce_window()):
+    coalesce: Duration,
+    // The shortest time allowed between applies (see set_max_apply_rate()):
+    min_interval: Duration,
+    // Re-apply if nothing's been applied for this long (see set_watchdog()):
+    watchdog: Duration,
+    last_apply: Option<Instant>,
+    pending: Option<Instant>, // When the apply that's being held back is due
+}
+
+/// Prints every shift register in the chain (in the order in which they were
+/// added) along with its pin count and current state:
+///
+/// ```
+/// Shifter (2 shift registers, 16 pins):
+///   0: 8 pins 0b00000001
+///   1: 8 pins 0b10000000 (relays)
+/// ```
+impl std::fmt::Display for Shifter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Shifter ({} shift registers, {} pins):", self.len(), self.total_pins())?;
+        for (position, sr) in self.shift_registers.iter().enumerate() {
+            write!(f, "\n  {}: {} pins {}", position, sr.pins, sr)?;
+            if let Some(ref name) = sr.name {
+                write!(f, " ({})", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Shifter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Shifter")
+            .field("id", &self.id)
+            .field("invert", &self.invert)
+            .field("order", &self.order)
+            .field("auto_apply", &self.auto_apply)
+            .field("dirty", &self.dirty)
+            .field("shift_registers", &self.shift_registers)
+            .finish()
+    }
 }
 
 impl Shifter {
@@ -158,105 +804,3056 @@ impl Shifter {
     /// figure out which pin is which:
     ///
     /// http://pi4j.com/images/j8header-2b-large.png
+    ///
+    /// Panics if the GPIO pins can't be set up (e.g. if you're not root).  Use
+    /// `try_new()` if you'd rather handle that yourself.
+    #[cfg(feature = "cupi")]
     pub fn new(data_pin: usize, latch_pin: usize, clock_pin: usize) -> Shifter {
-        let cupi = CuPi::new().unwrap();
-        let shift_registers: LinkedList<ShiftRegister> = LinkedList::new();
+        Shifter::try_new(data_pin, latch_pin, clock_pin).unwrap()
+    }
+
+    /// Just like `new()` but returns a `ShifterError::GpioError` if CuPi can't
+    /// be initialized or any of the given pins can't be set up as an output
+    /// (instead of panicking).  Handy for daemons that need to degrade
+    /// gracefully or retry.
+    #[cfg(feature = "cupi")]
+    pub fn try_new(data_pin: usize, latch_pin: usize, clock_pin: usize) -> Result<Shifter, ShifterError> {
+        Shifter::try_new_with(data_pin, latch_pin, clock_pin, GpioAccess::Mmap)
+    }
+
+    /// Just like `try_new()` but lets you pick how CuPi accesses the pins at
+    /// runtime.  Use `GpioAccess::Sysfs` if you're running somewhere (e.g. a
+    /// container) that doesn't have access to `/dev/mem`:
+    ///
+    /// ```
+    /// let access = if in_container { GpioAccess::Sysfs } else { GpioAccess::Mmap };
+    /// let mut shifter = Shifter::try_new_with(29, 28, 27, access).unwrap();
+    /// ```
+    #[cfg(feature = "cupi")]
+    pub fn try_new_with(data_pin: usize, latch_pin: usize, clock_pin: usize, access: GpioAccess) -> Result<Shifter, ShifterError> {
+        let cupi = CuPi::new()?;
+        Ok(match access {
+            GpioAccess::Mmap => Shifter::with_cupi(&cupi, data_pin, latch_pin, clock_pin)?,
+            GpioAccess::Sysfs => Shifter::from_output_pins(
+                SysfsPin::new(&cupi, data_pin)?,
+                SysfsPin::new(&cupi, latch_pin)?,
+                SysfsPin::new(&cupi, clock_pin)?,
+            ),
+        })
+    }
+
+    /// Just like `try_new()` but takes the pins numbered according to the given
+    /// scheme instead of WiringPi numbers:
+    ///
+    /// ```
+    /// // Header pins 40, 38, and 36 (aka BCM GPIO 21, 20, and 16):
+    /// let mut shifter = Shifter::with_numbering(40, 38, 36, PinNumbering::Physical).unwrap();
+    /// ```
+    #[cfg(feature = "cupi")]
+    pub fn with_numbering(data_pin: usize, latch_pin: usize, clock_pin: usize, numbering: PinNumbering) -> Result<Shifter, ShifterError> {
+        Shifter::try_new(
+            numbering.to_wiringpi(data_pin)?,
+            numbering.to_wiringpi(latch_pin)?,
+            numbering.to_wiringpi(clock_pin)?,
+        )
+    }
+
+    /// Just like `try_new()` but uses an existing `CuPi` instance instead of
+    /// creating its own.  Use this if your application already has a `CuPi`
+    /// for controlling other pins:
+    ///
+    /// ```
+    /// let cupi = CuPi::new().unwrap();
+    /// let mut button = cupi.pin(0).unwrap().input();
+    /// let mut shifter = Shifter::with_cupi(&cupi, 29, 28, 27).unwrap();
+    /// ```
+    #[cfg(feature = "cupi")]
+    pub fn with_cupi(cupi: &CuPi, data_pin: usize, latch_pin: usize, clock_pin: usize) -> Result<Shifter, ShifterError> {
+        Ok(Shifter::from_pins(
+            cupi.pin(data_pin)?.output(),
+            cupi.pin(latch_pin)?.output(),
+            cupi.pin(clock_pin)?.output(),
+        ))
+    }
+
+    /// Returns a new `Shifter` object that will shift out data using the given
+    /// CuPi *data*, *latch*, and *clock* pins.  Handy if your application
+    /// allocates its own pins (or needs to set them up in some special way)
+    /// rather than handing over pin numbers:
+    ///
+    /// ```
+    /// let cupi = CuPi::new().unwrap();
+    /// let data = cupi.pin(29).unwrap().output();
+    /// let latch = cupi.pin(28).unwrap().output();
+    /// let clock = cupi.pin(27).unwrap().output();
+    /// let mut shifter = Shifter::from_pins(data, latch, clock);
+    /// ```
+    #[cfg(feature = "cupi")]
+    pub fn from_pins(data: PinOutput, latch: PinOutput, clock: PinOutput) -> Shifter {
+        Shifter::from_output_pins(MmapPin(data), MmapPin(latch), MmapPin(clock))
+    }
+
+    /// Returns a new `Shifter` object that will shift out data using the given
+    /// *data*, *latch*, and *clock* pins which can come from any GPIO library
+    /// that implements `OutputPin`.  This is how you use a `Shifter` without
+    /// CuPi.
+    pub fn from_output_pins<D, L, C>(data: D, latch: L, clock: C) -> Shifter
+        where D: OutputPin + 'static, L: OutputPin + 'static, C: OutputPin + 'static
+    {
         Shifter {
-            data: cupi.pin(data_pin).unwrap().output(),
-            latch: cupi.pin(latch_pin).unwrap().output(),
-            clock: cupi.pin(clock_pin).unwrap().output(),
-            shift_registers: shift_registers,
+            id: NEXT_SHIFTER_ID.fetch_add(1, Ordering::SeqCst),
+            data: Box::new(data),
+            latch: Box::new(latch),
+            clock: Box::new(clock),
+            bus: None,
+            buffer: Vec::new(),
+            timing: Timing::default(),
+            delay: Box::new(SpinDelay),
+            latch_polarity: Polarity::ActiveHigh,
+            latch_mode: LatchMode::Edge,
+            clock_mode: ClockMode::Mode3,
+            oe: None,
+            oe_pwm: None,
+            outputs_enabled: true,
+            clear: None,
+            shift_registers: Vec::new(),
+            positions: Vec::new(),
+            names: HashMap::new(),
+            groups: HashMap::new(),
+            blinks: HashMap::new(),
+            dimmer: Dimmer::new(),
+            animations: Animations::new(),
+            player: None,
+            schedule: None,
+            back: None,
+            inputs: None,
+            drop_policy: DropPolicy::Leave,
+            failsafe: DropPolicy::Leave,
             invert: false,
+            order: Order::LastToFirst,
+            auto_apply: false,
+            dirty: true, // We don't know what state the hardware is in yet
+            coalesce: Duration::ZERO,
+            min_interval: Duration::ZERO,
+            watchdog: Duration::ZERO,
+            last_apply: None,
+            pending: None,
         }
     }
 
-    /// Adds a new shift register to this Shifter and returns a reference to it.
+    /// Returns a new `Shifter` object for shift registers that don't have a
+    /// latch (aka storage register) like the 74HC164, using the given *data*
+    /// and *clock* pins.
+    ///
+    /// Without a latch the outputs change as every bit goes by so `apply()`
+    /// ripples the new state across the chain instead of updating it all at
+    /// once.  With a fast clock (and a short chain) you won't notice but LEDs
+    /// on a long or slow chain will visibly flicker and anything slower to
+    /// react (like relays) may briefly see the wrong state.  If that matters,
+    /// use a chip with a latch (e.g. the 74HC595).
+    pub fn without_latch<D, C>(data: D, clock: C) -> Shifter
+        where D: OutputPin + 'static, C: OutputPin + 'static
+    {
+        let mut shifter = Shifter::from_output_pins(data, NullPin, clock);
+        shifter.latch_mode = LatchMode::None;
+        shifter
+    }
+
+    /// Returns a new `Shifter` object that will shift out data via the given
+    /// *bus* (e.g. hardware SPI) instead of toggling data and clock pins,
+    /// using the given *latch* pin.  The `data` and `clock` fields of the
+    /// returned `Shifter` are placeholders that don't do anything.
+    pub fn from_bus<B, L>(bus: B, latch: L) -> Shifter
+        where B: ShiftBus + 'static, L: OutputPin + 'static
+    {
+        let mut shifter = Shifter::from_output_pins(NullPin, latch, NullPin);
+        shifter.bus = Some(Box::new(bus));
+        shifter
+    }
+
+    /// Returns a `ShifterBuilder` for configuring a new `Shifter` (pins,
+    /// settings, and shift registers) in one expression.
+    #[cfg(feature = "cupi")]
+    pub fn builder() -> ShifterBuilder {
+        ShifterBuilder::new()
+    }
+
+    /// Adds a new shift register to this Shifter and returns a handle to it.
     /// You must specify the number of pins.
-    pub fn add(&mut self, pins: u8) -> usize {
-        let sr = ShiftRegister { data: 0, pins: pins };
-        self.shift_registers.push_back(sr);
-        self.shift_registers.len() - 1
-    }
-
-    /// Sets the *data* on the shift register at the given *sr_index*.
-    /// If *apply* is `true` the change will be applied immediately.
-    pub fn set(&mut self, sr_index: usize, data: usize, apply: bool) {
-        for (i, sr) in self.shift_registers.iter_mut().enumerate() {
-            if i == sr_index {
-                sr.set(data);
-                break;
-            }
+    pub fn add(&mut self, pins: u8) -> RegisterHandle {
+        self.add_with(RegisterConfig::new(pins))
+    }
+
+    /// Adds a new shift register to this Shifter using the given *config* and
+    /// returns a handle to it.  This lets you declare everything about a shift
+    /// register up front:
+    ///
+    /// ```
+    /// let relays = shifter.add_with(RegisterConfig {
+    ///     pins: 8,
+    ///     invert: true, // Active-low relay board
+    ///     name: Some("relays".to_string()),
+    ///     ..RegisterConfig::default()
+    /// });
+    /// ```
+    pub fn add_with(&mut self, config: RegisterConfig) -> RegisterHandle {
+        let id = self.positions.len();
+        let sr = ShiftRegister::from_config(id, config);
+        self.shift_registers.push(sr);
+        self.positions.push(Some(self.shift_registers.len() - 1));
+        self.dirty = true;
+        RegisterHandle {
+            shifter: self.id,
+            id: id,
         }
-        if apply { self.apply(); }
     }
 
-    /// Sets the given *pin* HIGH on the shift register at the given *sr_index*.
-    /// If *apply* is `true` the change will be applied immediately.
-    pub fn set_pin_high(&mut self, sr_index: usize, pin: u8, apply: bool) {
-        for (i, sr) in self.shift_registers.iter_mut().enumerate() {
-            if i == sr_index {
-                let new_state = sr.data | 1 << pin;
-                sr.set(new_state);
-                break;
-            }
+    /// Returns the handle of the shift register that was given the *name* (via
+    /// `RegisterConfig`) or `None` if there's no such shift register.
+    pub fn register_named(&self, name: &str) -> Option<RegisterHandle> {
+        self.registers()
+            .find(|&(_, sr)| sr.name() == Some(name))
+            .map(|(handle, _)| handle)
+    }
+
+    /// Removes the given shift register (*sr*) from the chain.  All other
+    /// handles remain valid (and keep referring to the same shift registers)
+    /// but any further use of *sr* will result in a
+    /// `ShifterError::InvalidRegister`.  Names given to its pins (see
+    /// `name_pin()`) are forgotten so they can be reused and its pins are
+    /// dropped from every group (see `define_group()`).  The change to the
+    /// physical outputs won't be visible until the next `apply()`.
+    pub fn remove(&mut self, sr: RegisterHandle) -> Result<(), ShifterError> {
+        let position = self.position(sr)?;
+        self.shift_registers.remove(position);
+        self.positions[sr.id] = None;
+        self.blinks.retain(|&(handle, _), _| handle != sr);
+        self.names.retain(|_, &mut (handle, _)| handle != sr);
+        for group in self.groups.values_mut() {
+            group.pins.retain(|&(handle, _)| handle != sr);
         }
-        if apply { self.apply(); }
+        self.dirty = true;
+        self.update_positions(position);
+        Ok(())
     }
 
-    /// Sets the given *pin* LOW on the shift register at the given *sr_index*.
-    /// If *apply* is `true` the change will be applied immediately.
-    pub fn set_pin_low(&mut self, sr_index: usize, pin: u8, apply: bool) {
-        for (i, sr) in self.shift_registers.iter_mut().enumerate() {
-            if i == sr_index {
-                let new_state = sr.data & !(1 << pin);
-                sr.set(new_state);
-                break;
-            }
+    /// Inserts a new shift register (with the given number of *pins*) at the
+    /// given *position* in the chain and returns a handle to it.  Every shift
+    /// register at or after *position* moves one spot further down the chain
+    /// (their handles remain valid).  A *position* equal to the number of
+    /// shift registers is the same as calling `add()`.
+    pub fn insert(&mut self, position: usize, pins: u8) -> Result<RegisterHandle, ShifterError> {
+        if position > self.shift_registers.len() {
+            return Err(ShifterError::InvalidPosition(position));
         }
-        if apply { self.apply(); }
+        let id = self.positions.len();
+        let sr = ShiftRegister::new(id, pins);
+        self.shift_registers.insert(position, sr);
+        self.positions.push(Some(position));
+        self.dirty = true;
+        self.update_positions(position);
+        Ok(RegisterHandle {
+            shifter: self.id,
+            id: id,
+        })
     }
 
-    /// This function will invert all logic so that HIGH is LOW and LOW is HIGH.
-    /// Very convenient if you made a (very common) mistake in your wiring or
-    /// you need reversed logic for other reasons.
-    pub fn invert(&mut self) {
-        match self.invert {
-            true => self.invert = false,
-            false => self.invert = true,
+    /// Adds a new shift register to the *front* of the chain (the opposite of
+    /// `add()`) and returns a handle to it.  Use this when you physically
+    /// extend your chain at the far end (since, with the default
+    /// `Order::LastToFirst`, the *last* shift register in the chain is the
+    /// first one that gets added).
+    pub fn add_front(&mut self, pins: u8) -> RegisterHandle {
+        self.insert(0, pins).unwrap() // Position 0 is always valid
+    }
+
+    /// Changes the number of output *pins* on the given shift register (*sr*).
+    /// If the shift register shrinks, any data on the pins that no longer
+    /// exist is discarded.  Any pin remapping table (see `set_remap()`) gets
+    /// cleared since it no longer fits.  The change to the physical outputs
+    /// won't be visible until the next `apply()`.
+    pub fn resize(&mut self, sr: RegisterHandle, pins: u8) -> Result<(), ShifterError> {
+        {
+            let reg = self.register_mut(sr)?;
+            reg.resize(pins);
+            reg.remap = None;
         }
+        self.blinks.retain(|&(handle, pin), _| handle != sr || pin < pins);
+        Ok(())
     }
 
-    /// Applies all current shift register states by shifting out all the stored
-    /// data in each ShiftRegister object.
-    pub fn apply(&mut self) {
-        self.latch.low().unwrap();
+    /// Returns the current position of the given shift register (*sr*) in the
+    /// chain (0 being the first one that was added).
+    pub fn position(&self, sr: RegisterHandle) -> Result<usize, ShifterError> {
+        if sr.shifter != self.id {
+            return Err(ShifterError::InvalidRegister(sr));
+        }
+        match self.positions.get(sr.id) {
+            Some(&Some(position)) => Ok(position),
+            _ => Err(ShifterError::InvalidRegister(sr)),
+        }
+    }
+
+    /// Sets the *data* on the given shift register (*sr*).  If the shift
+    /// register has more pins than fit in a `usize` the extra pins are set
+    /// LOW; use `set_bytes()` to control all of them.
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn set(&mut self, sr: RegisterHandle, data: usize, apply: Apply) -> Result<(), ShifterError> {
+        self.register_mut(sr)?.set(data);
+        self.maybe_apply(apply)
+    }
+
+    /// Sets the *data* on the given shift register (*sr*) but only for the
+    /// bits that are set in *mask*; all other bits are left as-is.
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    ///
+    /// ```
+    /// // Set pins 2 and 4 HIGH and pins 3 and 5 LOW without touching the rest
+    /// shifter.set_masked(sr0, 0b00010100, 0b00111100, Apply::Now).unwrap();
+    /// ```
+    pub fn set_masked(&mut self, sr: RegisterHandle, data: usize, mask: usize, apply: Apply) -> Result<(), ShifterError> {
+        self.register_mut(sr)?.data.set_word_masked(data, mask);
+        self.maybe_apply(apply)
+    }
+
+    /// Returns the data currently being tracked for the given shift register
+    /// (*sr*), i.e. whatever was last given to `set()` and friends.  Note that
+    /// this is the state that *will* be shifted out on the next `apply()`; it
+    /// may not have been applied yet.
+    pub fn get(&self, sr: RegisterHandle) -> Result<usize, ShifterError> {
+        Ok(self.register(sr)?.data())
+    }
+
+    /// Sets the data on the given shift register (*sr*) from *bytes* (pin 0
+    /// being the least significant bit of the first byte).  Use this instead
+    /// of `set()` for shift registers with more pins than fit in a `usize`.
+    /// *bytes* must contain exactly enough bytes to cover every pin (e.g. 12
+    /// for a 96-pin shift register) or you'll get a
+    /// `ShifterError::InvalidLength`.
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn set_bytes(&mut self, sr: RegisterHandle, bytes: &[u8], apply: Apply) -> Result<(), ShifterError> {
+        let reg = self.register_mut(sr)?;
+        reg.check_bytes(bytes)?;
+        reg.data.set_bytes(bytes);
+        self.maybe_apply(apply)
+    }
+
+    /// Returns the data currently being tracked for the given shift register
+    /// (*sr*) as bytes (the inverse of `set_bytes()`).
+    pub fn get_bytes(&self, sr: RegisterHandle) -> Result<Vec<u8>, ShifterError> {
+        Ok(self.register(sr)?.bytes().to_vec())
+    }
+
+    /// Returns the number of output pins on the given shift register (*sr*)
+    /// as given to `add()`.
+    pub fn pin_count(&self, sr: RegisterHandle) -> Result<u8, ShifterError> {
+        Ok(self.register(sr)?.pins)
+    }
+
+    /// Returns `true` if the given *pin* is HIGH on the given shift register
+    /// (*sr*).  This is the *logical* state as tracked by the `Shifter`; the
+    /// `invert()` setting is not taken into account (so a pin you set HIGH
+    /// will always read back as HIGH, regardless of what level ends up on the
+    /// wire).
+    pub fn get_pin(&self, sr: RegisterHandle, pin: u8) -> Result<bool, ShifterError> {
+        let reg = self.register(sr)?;
+        reg.check_pin(pin)?;
+        Ok(reg.pin(pin))
+    }
+
+    /// Sets the given *pin* HIGH on the given shift register (*sr*).
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn set_pin_high(&mut self, sr: RegisterHandle, pin: u8, apply: Apply) -> Result<(), ShifterError> {
+        let reg = self.register_mut(sr)?;
+        reg.check_pin(pin)?;
+        reg.set_pin(pin, true);
+        self.maybe_apply(apply)
+    }
+
+    /// Sets the given *pin* LOW on the given shift register (*sr*).
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn set_pin_low(&mut self, sr: RegisterHandle, pin: u8, apply: Apply) -> Result<(), ShifterError> {
+        let reg = self.register_mut(sr)?;
+        reg.check_pin(pin)?;
+        reg.set_pin(pin, false);
+        self.maybe_apply(apply)
+    }
+
+    /// Just like `set()` but with a `bool` for *apply* (`true` being
+    /// `Apply::Now`) the way it used to be.
+    #[deprecated(note = "use set() with Apply::Now / Apply::Later")]
+    pub fn set_bool(&mut self, sr: RegisterHandle, data: usize, apply: bool) -> Result<(), ShifterError> {
+        self.set(sr, data, Apply::from_bool(apply))
+    }
+
+    /// Just like `set_pin_high()` but with a `bool` for *apply* (`true`
+    /// being `Apply::Now`) the way it used to be.
+    #[deprecated(note = "use set_pin_high() with Apply::Now / Apply::Later")]
+    pub fn set_pin_high_bool(&mut self, sr: RegisterHandle, pin: u8, apply: bool) -> Result<(), ShifterError> {
+        self.set_pin_high(sr, pin, Apply::from_bool(apply))
+    }
+
+    /// Just like `set_pin_low()` but with a `bool` for *apply* (`true` being
+    /// `Apply::Now`) the way it used to be.
+    #[deprecated(note = "use set_pin_low() with Apply::Now / Apply::Later")]
+    pub fn set_pin_low_bool(&mut self, sr: RegisterHandle, pin: u8, apply: bool) -> Result<(), ShifterError> {
+        self.set_pin_low(sr, pin, Apply::from_bool(apply))
+    }
+
+    /// Sets every pin from *start* up to (but not including) *end* HIGH (if
+    /// *state* is `true`) or LOW (if *state* is `false`) on the given shift
+    /// register (*sr*).  Just like a regular Rust range, so
+    /// `set_pin_range(sr0, 0, 8, true, Apply::Now)` sets pins 0 through 7 HIGH.
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn set_pin_range(&mut self, sr: RegisterHandle, start: u8, end: u8, state: bool, apply: Apply) -> Result<(), ShifterError> {
+        let reg = self.register_mut(sr)?;
+        if end > 0 {
+            reg.check_pin(end - 1)?;
+        }
+        for pin in start..end {
+            reg.set_pin(pin, state);
+        }
+        self.maybe_apply(apply)
+    }
+
+    /// Toggles the given *pin* on the given shift register (*sr*) (HIGH
+    /// becomes LOW and LOW becomes HIGH).
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn toggle_pin(&mut self, sr: RegisterHandle, pin: u8, apply: Apply) -> Result<(), ShifterError> {
+        let reg = self.register_mut(sr)?;
+        reg.check_pin(pin)?;
+        let state = reg.pin(pin);
+        reg.set_pin(pin, !state);
+        self.maybe_apply(apply)
+    }
+
+    /// Rotates the data on the given shift register (*sr*) *n* pins to the
+    /// left (towards the highest pin).  Pins that fall off the end wrap around
+    /// to pin 0 so with an 8-pin shift register `0b10000001` rotated left by 1
+    /// becomes `0b00000011`.  Handy for chaser effects.
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn rotate_left(&mut self, sr: RegisterHandle, n: usize, apply: Apply) -> Result<(), ShifterError> {
+        self.register_mut(sr)?.data.rotate_left(n);
+        self.maybe_apply(apply)
+    }
+
+    /// Rotates the data on the given shift register (*sr*) *n* pins to the
+    /// right (towards pin 0).  Pin 0 wraps around to the highest pin so with an
+    /// 8-pin shift register `0b10000001` rotated right by 1 becomes
+    /// `0b11000000`.
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn rotate_right(&mut self, sr: RegisterHandle, n: usize, apply: Apply) -> Result<(), ShifterError> {
+        let reg = self.register_mut(sr)?;
+        let pins = reg.pins as usize;
+        if pins > 0 {
+            reg.data.rotate_left(pins - n % pins);
+        }
+        self.maybe_apply(apply)
+    }
+
+    /// Shifts the data on the given shift register (*sr*) one pin to the left
+    /// (towards the highest pin, which is dropped) and sets pin 0 HIGH (if
+    /// *state* is `true`) or LOW (if *state* is `false`).  Just like what
+    /// happens inside the shift register itself.
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn shift_in(&mut self, sr: RegisterHandle, state: bool, apply: Apply) -> Result<(), ShifterError> {
+        self.register_mut(sr)?.data.shift_in(state);
+        self.maybe_apply(apply)
+    }
+
+    /// Sets the given *pin* HIGH on the given shift register (*sr*), applies
+    /// the change, waits for *duration*, then sets it LOW and applies again.
+    /// Handy for things like garage door openers and reset lines.  Since this
+    /// works on the logical state of the pin the `invert()` settings are
+    /// respected (an inverted pin will be pulsed LOW on the wire).  Note that
+    /// this blocks the current thread for *duration*.
+    pub fn pulse(&mut self, sr: RegisterHandle, pin: u8, duration: Duration) -> Result<(), ShifterError> {
+        self.set_pin_high(sr, pin, Apply::Now)?;
+        thread::sleep(duration);
+        self.set_pin_low(sr, pin, Apply::Now)
+    }
+
+    /// Makes the given *pin* on the given shift register (*sr*) blink *hz*
+    /// times per second (e.g. `2.0` means it'll turn on twice every second).
+    /// Every pin can blink at its own rate.  The `Shifter` doesn't spawn any
+    /// threads to do this; you have to call `tick()` regularly (e.g. from your
+    /// main loop) to keep things blinking:
+    ///
+    /// ```
+    /// shifter.blink(sr0, 3, 2.0).unwrap();
+    /// shifter.blink(sr1, 0, 0.5).unwrap();
+    /// loop {
+    ///     shifter.tick().unwrap();
+    ///     thread::sleep(Duration::from_millis(10));
+    /// }
+    /// ```
+    ///
+    /// Calling `blink()` on a pin that's already blinking just changes its
+    /// rate.  If *hz* isn't a positive number you'll get a
+    /// `ShifterError::InvalidRate`.
+    pub fn blink(&mut self, sr: RegisterHandle, pin: u8, hz: f64) -> Result<(), ShifterError> {
+        self.register(sr)?.check_pin(pin)?;
+        let blink = match Blink::new(hz, Instant::now()) {
+            Some(blink) => blink,
+            None => return Err(ShifterError::InvalidRate(hz)),
+        };
+        self.blinks.insert((sr, pin), blink);
+        Ok(())
+    }
+
+    /// Pauses the blinking of the given *pin* on the given shift register
+    /// (*sr*), leaving it in whatever state it's currently in.  Use
+    /// `start_blink()` to resume.  Does nothing if the pin isn't blinking.
+    pub fn stop_blink(&mut self, sr: RegisterHandle, pin: u8) {
+        if let Some(blink) = self.blinks.get_mut(&(sr, pin)) {
+            blink.stop();
+        }
+    }
+
+    /// Resumes the blinking of the given *pin* on the given shift register
+    /// (*sr*) after a `stop_blink()`.  Does nothing if `blink()` was never
+    /// called for this pin (or it was removed with `remove_blink()`).
+    pub fn start_blink(&mut self, sr: RegisterHandle, pin: u8) {
+        if let Some(blink) = self.blinks.get_mut(&(sr, pin)) {
+            blink.start(Instant::now());
+        }
+    }
+
+    /// Stops the given *pin* on the given shift register (*sr*) from blinking
+    /// entirely (it's left in whatever state it's currently in).
+    pub fn remove_blink(&mut self, sr: RegisterHandle, pin: u8) {
+        self.blinks.remove(&(sr, pin));
+    }
+
+    /// Toggles every blinking pin (see `blink()`) that's due, updates any
+    /// running animations (see `start_animation()`), carries out the next
+    /// step of the sequence that's playing (see `play()`) if it's due,
+    /// carries out any scheduled commands that are due (see
+    /// `set_schedule()`), and applies the result
+    /// (along with any apply held back by `set_coalesce_window()` or
+    /// `set_max_apply_rate()` that's due).  Also re-applies the current state
+    /// if the watchdog is due (see `set_watchdog()`).  Returns `true` if
+    /// anything was applied.  Call this at least twice as often as your
+    /// fastest blink rate.
+    pub fn tick(&mut self) -> Result<bool, ShifterError> {
+        let now = Instant::now();
+        let due: Vec<(RegisterHandle, u8)> = self.blinks.iter_mut()
+            .filter_map(|(&key, blink)| if blink.due(now) { Some(key) } else { None })
+            .collect();
+        let flush = self.next_apply_due().is_some_and(|due| due <= now)
+            || self.next_watchdog_due().is_some_and(|due| due <= now);
+        let animated = self.animate(now);
+        let played = self.play_steps(now)?;
+        let scheduled = self.run_schedule(SystemTime::now())?;
+        if due.is_empty() && !flush && !animated && !played && !scheduled {
+            return Ok(false);
+        }
+        for (sr, pin) in due {
+            self.toggle_pin(sr, pin, Apply::Later)?;
+        }
+        self.apply()?;
+        Ok(true)
+    }
+
+    /// Sets the data on *every* shift register in the chain at once from
+    /// *bytes*.  The bits are consumed in the order in which the shift
+    /// registers were added (pin 0 of the first shift register being the least
+    /// significant bit of the first byte) with no padding between shift
+    /// registers, so with a chain of 8-pin shift registers each byte maps to
+    /// exactly one shift register.  *bytes* must contain exactly enough bytes
+    /// to cover `total_pins()` or you'll get a `ShifterError::InvalidLength`.
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    ///
+    /// This is the fastest way to push precomputed frames to a large chain:
+    ///
+    /// ```
+    /// let frame = [0b11110000, 0b00001111, 0b10101010];
+    /// shifter.set_chain(&frame, Apply::Now).unwrap();
+    /// ```
+    pub fn set_chain(&mut self, bytes: &[u8], apply: Apply) -> Result<(), ShifterError> {
+        let expected = self.total_pins().div_ceil(8);
+        if bytes.len() != expected {
+            return Err(ShifterError::InvalidLength { expected: expected, actual: bytes.len() });
+        }
+        let mut bit = 0;
+        self.dirty = true;
+        for sr in self.shift_registers.iter_mut() {
+            for pin in 0..sr.pins {
+                sr.set_pin(pin, bytes[bit / 8] >> (bit % 8) & 1 == 1);
+                bit += 1;
+            }
+        }
+        self.maybe_apply(apply)
+    }
+
+    /// Returns the data currently being tracked for *every* shift register in
+    /// the chain packed into bytes (the exact inverse of `set_chain()`).
+    /// Handy for taking snapshots of the current frame or mirroring it
+    /// somewhere else.
+    pub fn get_chain(&self) -> Vec<u8> {
+        let mut bytes = vec![0; self.total_pins().div_ceil(8)];
+        let mut bit = 0;
         for sr in self.shift_registers.iter() {
-            for n in 0..sr.pins {
-                self.clock.low().unwrap();
-                if self.invert {
-                    match sr.data >> n & 1 {
-                        1 => self.data.low().unwrap(),
-                        0 => self.data.high().unwrap(),
-                        _ => unreachable!(),
-                    }
-                } else {
-                    match sr.data >> n & 1 {
-                        0 => self.data.low().unwrap(),
-                        1 => self.data.high().unwrap(),
-                        _ => unreachable!(),
-                    }
+            for pin in 0..sr.pins {
+                if sr.pin(pin) {
+                    bytes[bit / 8] |= 1 << (bit % 8);
                 }
-                self.clock.high().unwrap();
+                bit += 1;
             }
         }
-        self.latch.high().unwrap();
+        bytes
     }
 
-}
+    /// Sets the pin at the given *index* HIGH (if *state* is `true`) or LOW (if
+    /// *state* is `false`) where *index* counts every pin in the chain as if it
+    /// were one big shift register (in the same order as `set_chain()`).  So
+    /// with a chain of 8-pin shift registers index 137 is pin 1 of the 18th
+    /// shift register.  If *index* is past the end of the chain you'll get a
+    /// `ShifterError::InvalidPosition`.
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn set_chain_pin(&mut self, index: usize, state: bool, apply: Apply) -> Result<(), ShifterError> {
+        let (position, pin) = self.chain_pin(index)?;
+        self.dirty = true;
+        self.shift_registers[position].set_pin(pin, state);
+        self.maybe_apply(apply)
+    }
 
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
+    /// Returns `true` if the pin at the given *index* (see `set_chain_pin()`)
+    /// is HIGH.
+    pub fn get_chain_pin(&self, index: usize) -> Result<bool, ShifterError> {
+        let (position, pin) = self.chain_pin(index)?;
+        Ok(self.shift_registers[position].pin(pin))
+    }
+
+    /// Gives the given *pin* on the given shift register (*sr*) a
+    /// human-readable *name* so that it can be controlled via `set_named()`
+    /// instead of having to keep track of `(sr, pin)` pairs:
+    ///
+    /// ```
+    /// shifter.name_pin(sr1, 3, "porch_lights").unwrap();
+    /// shifter.set_named("porch_lights", true, Apply::Now).unwrap();
+    /// ```
+    ///
+    /// Naming a pin with a name that's already in use moves the name to the
+    /// new pin.
+    pub fn name_pin(&mut self, sr: RegisterHandle, pin: u8, name: &str) -> Result<(), ShifterError> {
+        self.register(sr)?.check_pin(pin)?;
+        self.names.insert(name.to_string(), (sr, pin));
+        Ok(())
+    }
+
+    /// Returns the shift register handle and pin that were given the *name*
+    /// (via `name_pin()`) or `None` if there's no such pin.
+    pub fn named_pin(&self, name: &str) -> Option<(RegisterHandle, u8)> {
+        self.names.get(name).cloned()
+    }
+
+    /// Sets the pin with the given *name* (see `name_pin()`) HIGH (if *state*
+    /// is `true`) or LOW (if *state* is `false`).
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn set_named(&mut self, name: &str, state: bool, apply: Apply) -> Result<(), ShifterError> {
+        let (sr, pin) = self.lookup_name(name)?;
+        if state {
+            self.set_pin_high(sr, pin, apply)
+        } else {
+            self.set_pin_low(sr, pin, apply)
+        }
+    }
+
+    /// Returns `true` if the pin with the given *name* (see `name_pin()`) is
+    /// HIGH.  Just like `get_pin()` this is the *logical* state.
+    pub fn get_named(&self, name: &str) -> Result<bool, ShifterError> {
+        let (sr, pin) = self.lookup_name(name)?;
+        self.get_pin(sr, pin)
+    }
+
+    /// Defines a group of *pins* (which may span multiple shift registers)
+    /// that can be controlled together by *name* via `set_group()`,
+    /// `toggle_group()`, and `pulse_group()`:
+    ///
+    /// ```
+    /// shifter.define_group("reds", &[(sr0, 0), (sr0, 3), (sr1, 2)]).unwrap();
+    /// shifter.set_group("reds", true, Apply::Now).unwrap(); // All reds HIGH
+    /// ```
+    ///
+    /// Defining a group with a name that's already in use replaces it.
+    pub fn define_group(&mut self, name: &str, pins: &[(RegisterHandle, u8)]) -> Result<(), ShifterError> {
+        for &(sr, pin) in pins {
+            self.register(sr)?.check_pin(pin)?;
+        }
+        self.groups.insert(name.to_string(), PinGroup { pins: pins.to_vec() });
+        Ok(())
+    }
+
+    /// Returns the group with the given *name* (see `define_group()`) or
+    /// `None` if there's no such group.
+    pub fn group(&self, name: &str) -> Option<&PinGroup> {
+        self.groups.get(name)
+    }
+
+    /// Sets every pin in the group with the given *name* HIGH (if *state* is
+    /// `true`) or LOW (if *state* is `false`).
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn set_group(&mut self, name: &str, state: bool, apply: Apply) -> Result<(), ShifterError> {
+        for (sr, pin) in self.lookup_group(name)? {
+            self.register_mut(sr)?.set_pin(pin, state);
+        }
+        self.maybe_apply(apply)
+    }
+
+    /// Toggles every pin in the group with the given *name* (HIGH becomes LOW
+    /// and LOW becomes HIGH).
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn toggle_group(&mut self, name: &str, apply: Apply) -> Result<(), ShifterError> {
+        for (sr, pin) in self.lookup_group(name)? {
+            let reg = self.register_mut(sr)?;
+            let state = reg.pin(pin);
+            reg.set_pin(pin, !state);
+        }
+        self.maybe_apply(apply)
+    }
+
+    /// Sets every pin in the group with the given *name* HIGH, applies the
+    /// change, waits for *duration*, then sets them LOW and applies again.
+    /// Note that this blocks the current thread for *duration*.
+    pub fn pulse_group(&mut self, name: &str, duration: Duration) -> Result<(), ShifterError> {
+        self.set_group(name, true, Apply::Now)?;
+        thread::sleep(duration);
+        self.set_group(name, false, Apply::Now)
+    }
+
+    /// Controls whether changes made with `Apply::Auto` (which includes
+    /// `write()`, `write_pin()`, and `toggle()`) are applied immediately
+    /// (`true`) or left for an explicit `apply()` (`false`, the default).  This
+    /// lets you pick a mode once instead of deciding on every call.
+    pub fn set_auto_apply(&mut self, auto_apply: bool) {
+        self.auto_apply = auto_apply;
+    }
+
+    /// Coalesces applies:  Instead of shifting out the whole chain every time
+    /// a change is made with `Apply::Now` (or `Apply::Auto`), wait until
+    /// *window* has passed since the first such request and then apply
+    /// everything at once.  Handy when lots of independent bits of code each
+    /// apply their own changes.  `Duration::ZERO` (the default) turns this
+    /// off.
+    ///
+    /// Nothing happens in the background so something has to call `tick()`
+    /// (or `apply_if_changed()`) once the window is up; `next_apply_due()`
+    /// says when that is.  Calling `apply()` directly always applies
+    /// immediately.
+    pub fn set_coalesce_window(&mut self, window: Duration) {
+        self.coalesce = window;
+    }
+
+    /// Returns the current coalescing window (see `set_coalesce_window()`).
+    pub fn coalesce_window(&self) -> Duration {
+        self.coalesce
+    }
+
+    /// Returns when the apply that's being held back by
+    /// `set_coalesce_window()` is due (or `None` if there isn't one).  Call
+    /// `tick()` at (or after) that time to get it out.
+    pub fn next_apply_due(&self) -> Option<Instant> {
+        self.pending
+    }
+
+    /// Sets up a watchdog that re-applies the current state whenever
+    /// *interval* passes without an apply.  Electrical noise can flip bits in
+    /// the shift registers so the outputs drift from what this `Shifter`
+    /// thinks they are; re-applying now and then puts them right.  Chains
+    /// that get applied often anyway (e.g. by an animation or the refresh
+    /// thread) never need it.  `Duration::ZERO` (the default) turns this off.
+    ///
+    /// Just like with `set_coalesce_window()` something has to call `tick()`
+    /// for the watchdog to do anything (`spawn()` takes care of that).
+    /// Nothing gets re-applied until the first apply (so the chain isn't
+    /// touched before it's been set up).
+    pub fn set_watchdog(&mut self, interval: Duration) {
+        self.watchdog = interval;
+    }
+
+    /// Returns the current watchdog interval (see `set_watchdog()`).
+    pub fn watchdog(&self) -> Duration {
+        self.watchdog
+    }
+
+    /// Returns when the watchdog (see `set_watchdog()`) will next re-apply
+    /// the current state if nothing else gets applied before then (or `None`
+    /// if it's off or nothing has been applied yet).
+    pub fn next_watchdog_due(&self) -> Option<Instant> {
+        if self.watchdog == Duration::ZERO {
+            return None;
+        }
+        self.last_apply.map(|last| last + self.watchdog)
+    }
+
+    // Returns the soonest of next_apply_due(), next_animation_due(),
+    // next_step_due(), the next scheduled command, and next_watchdog_due()
+    // (i.e. when tick() next has something to do apart from blinking)
+    pub(crate) fn next_wakeup(&self) -> Option<Instant> {
+        [self.next_apply_due(), self.next_animation_due(), self.next_step_due(), self.next_scheduled(), self.next_watchdog_due()]
+            .iter()
+            .filter_map(|&due| due)
+            .min()
+    }
+
+    /// Limits how often changes made with `Apply::Now` (or `Apply::Auto`)
+    /// get applied to *hz* times per second.  A change that comes in too
+    /// soon after the last apply is held back and goes out (along with any
+    /// changes made in the meantime, so it's always the most recent state)
+    /// once enough time has passed.  This keeps long chains from being
+    /// hammered and CPU usage (and EMI) predictable.  `f64::INFINITY` (the
+    /// default) means no limit.
+    ///
+    /// Just like with `set_coalesce_window()` something has to call `tick()`
+    /// to get held back changes out.  Calling `apply()` directly always
+    /// applies immediately (but counts towards the limit).
+    ///
+    /// Returns a `ShifterError::InvalidRate` if *hz* isn't a positive number.
+    pub fn set_max_apply_rate(&mut self, hz: f64) -> Result<(), ShifterError> {
+        self.min_interval = if hz == f64::INFINITY {
+            Duration::ZERO // No limit
+        } else {
+            period_from_hz(hz)?
+        };
+        Ok(())
+    }
+
+    /// Returns the current limit on how often changes get applied (see
+    /// `set_max_apply_rate()`).
+    pub fn max_apply_rate(&self) -> f64 {
+        if self.min_interval > Duration::ZERO {
+            1.0 / self.min_interval.as_secs_f64()
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    /// Just like `set()` but whether or not the change gets applied
+    /// immediately is controlled by `set_auto_apply()`.
+    pub fn write(&mut self, sr: RegisterHandle, data: usize) -> Result<(), ShifterError> {
+        self.set(sr, data, Apply::Auto)
+    }
+
+    /// Sets the given *pin* HIGH (if *state* is `true`) or LOW (if *state* is
+    /// `false`) on the given shift register (*sr*).  Whether or not the change
+    /// gets applied immediately is controlled by `set_auto_apply()`.
+    pub fn write_pin(&mut self, sr: RegisterHandle, pin: u8, state: bool) -> Result<(), ShifterError> {
+        if state {
+            self.set_pin_high(sr, pin, Apply::Auto)
+        } else {
+            self.set_pin_low(sr, pin, Apply::Auto)
+        }
+    }
+
+    /// Just like `toggle_pin()` but whether or not the change gets applied
+    /// immediately is controlled by `set_auto_apply()`.
+    pub fn toggle(&mut self, sr: RegisterHandle, pin: u8) -> Result<(), ShifterError> {
+        self.toggle_pin(sr, pin, Apply::Auto)
+    }
+
+    /// Sets every pin on every shift register HIGH.
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn all_high(&mut self, apply: Apply) -> Result<(), ShifterError> {
+        self.dirty = true;
+        for sr in self.shift_registers.iter_mut() {
+            sr.data.fill(true);
+        }
+        self.maybe_apply(apply)
+    }
+
+    /// Sets every pin on every shift register LOW.
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn all_low(&mut self, apply: Apply) -> Result<(), ShifterError> {
+        self.dirty = true;
+        for sr in self.shift_registers.iter_mut() {
+            sr.data.fill(false);
+        }
+        self.maybe_apply(apply)
+    }
+
+    /// Sets every pin on the given shift register (*sr*) according to the
+    /// given *pattern* (e.g. `Pattern::Alternating`).
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn fill(&mut self, sr: RegisterHandle, pattern: Pattern, apply: Apply) -> Result<(), ShifterError> {
+        {
+            let reg = self.register_mut(sr)?;
+            let pins = reg.pins as usize;
+            for pin in 0..pins {
+                reg.data.set(pin, pattern.state(pin, pins));
+            }
+        }
+        self.maybe_apply(apply)
+    }
+
+    /// Sets every pin on every shift register according to the given
+    /// *pattern* as if the whole chain were one big shift register (pins are
+    /// counted in the same order as `set_chain()`).  So `Pattern::Threshold()`
+    /// gives you a bar graph spanning the entire chain.
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn fill_chain(&mut self, pattern: Pattern, apply: Apply) -> Result<(), ShifterError> {
+        let total = self.total_pins();
+        let mut bit = 0;
+        self.dirty = true;
+        for sr in self.shift_registers.iter_mut() {
+            for pin in 0..sr.pins {
+                sr.set_pin(pin, pattern.state(bit, total));
+                bit += 1;
+            }
+        }
+        self.maybe_apply(apply)
+    }
+
+    /// This function will invert all logic so that HIGH is LOW and LOW is HIGH.
+    /// Very convenient if you made a (very common) mistake in your wiring or
+    /// you need reversed logic for other reasons.
+    pub fn invert(&mut self) {
+        match self.invert {
+            true => self.invert = false,
+            false => self.invert = true,
+        }
+        self.dirty = true;
+    }
+
+    /// Inverts the logic of just the given shift register (*sr*) so that HIGH
+    /// is LOW and LOW is HIGH when its data gets shifted out.  Use this when
+    /// your chain mixes boards that need different logic (e.g. LEDs and
+    /// active-low relays).  This is applied on top of the global `invert()`
+    /// setting so if both are enabled they cancel each other out.
+    pub fn set_invert(&mut self, sr: RegisterHandle, invert: bool) -> Result<(), ShifterError> {
+        self.register_mut(sr)?.invert = invert;
+        Ok(())
+    }
+
+    /// Sets the *bit_order* in which the data for the given shift register
+    /// (*sr*) gets shifted out.  The default (`BitOrder::LsbFirst`) shifts out
+    /// pin 0 first.
+    pub fn set_bit_order(&mut self, sr: RegisterHandle, bit_order: BitOrder) -> Result<(), ShifterError> {
+        self.register_mut(sr)?.bit_order = bit_order;
+        Ok(())
+    }
+
+    /// Inverts the logic of individual pins on the given shift register (*sr*):
+    /// Every pin that's set in *mask* will be flipped (HIGH becomes LOW and LOW
+    /// becomes HIGH) when the data gets shifted out while all the others pass
+    /// through as-is.  Handy when a single board has both active-low outputs
+    /// (e.g. relays) and regular ones (e.g. LEDs):
+    ///
+    /// ```
+    /// // Pins 0-3 drive active-low relays; pins 4-7 drive LEDs
+    /// shifter.set_invert_mask(sr0, 0b00001111).unwrap();
+    /// ```
+    ///
+    /// Like `set_invert()` this is applied on top of the other invert settings.
+    pub fn set_invert_mask(&mut self, sr: RegisterHandle, mask: usize) -> Result<(), ShifterError> {
+        self.register_mut(sr)?.invert_mask.set_word(mask);
+        Ok(())
+    }
+
+    /// Attaches a pin remapping table to the given shift register (*sr*) for
+    /// when the outputs aren't wired in sequential order (e.g. your PCB routes
+    /// Q5 to connector pin 0).  Each entry in *remap* gives the physical output
+    /// that the corresponding logical pin is connected to, so with the
+    /// following:
+    ///
+    /// ```
+    /// shifter.set_remap(sr0, &[5, 4, 3, 2, 1, 0, 6, 7]).unwrap();
+    /// shifter.set_pin_high(sr0, 0, Apply::Now).unwrap(); // Physical output 5 goes HIGH
+    /// ```
+    ///
+    /// ...every other method (`set()`, `set_pin_high()`, `get_pin()`, etc)
+    /// keeps working in terms of *logical* pins and `apply()` takes care of
+    /// rearranging the bits before they're shifted out.  *remap* must contain
+    /// every pin on the shift register exactly once or you'll get a
+    /// `ShifterError::InvalidRemap`.
+    pub fn set_remap(&mut self, sr: RegisterHandle, remap: &[u8]) -> Result<(), ShifterError> {
+        let reg = self.register_mut(sr)?;
+        reg.remap = Some(physical_to_logical(remap, reg.pins)?);
+        Ok(())
+    }
+
+    /// Sets the `ShiftProtocol` used to shift out the given shift register
+    /// (*sr*) for chips that don't work like a 74HC595:
+    ///
+    /// ```
+    /// let display = shifter.add(64);
+    /// shifter.set_protocol(display, Max7219 { intensity: 4 })?;
+    /// ```
+    ///
+    /// The protocol's `init_frames()` (if any) get sent on the next `apply()`.
+    pub fn set_protocol<P: ShiftProtocol + 'static>(&mut self, sr: RegisterHandle, protocol: P) -> Result<(), ShifterError> {
+        let reg = self.register_mut(sr)?;
+        reg.protocol = Some(Box::new(protocol));
+        reg.needs_init = true;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Goes back to shifting out the given shift register (*sr*) like a
+    /// 74HC595 (see `set_protocol()`).
+    pub fn clear_protocol(&mut self, sr: RegisterHandle) -> Result<(), ShifterError> {
+        self.register_mut(sr)?.protocol = None;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Removes the pin remapping table (if any) from the given shift register
+    /// (*sr*) so that logical pins once again match the physical outputs.
+    pub fn clear_remap(&mut self, sr: RegisterHandle) -> Result<(), ShifterError> {
+        self.register_mut(sr)?.remap = None;
+        Ok(())
+    }
+
+    /// Returns the number of shift registers in the chain.
+    pub fn len(&self) -> usize {
+        self.shift_registers.len()
+    }
+
+    /// Returns `true` if no shift registers have been added yet.
+    pub fn is_empty(&self) -> bool {
+        self.shift_registers.is_empty()
+    }
+
+    /// Returns the total number of output pins across every shift register in
+    /// the chain.
+    pub fn total_pins(&self) -> usize {
+        self.shift_registers.iter().map(|sr| sr.pins as usize).sum()
+    }
+
+    /// Returns an iterator over every shift register in the chain (in the
+    /// order in which they were added) yielding a handle to each one along
+    /// with its current state:
+    ///
+    /// ```
+    /// for (sr, state) in shifter.registers() {
+    ///     println!("{:?}: {} pins, data: {}", sr, state.pins(), state);
+    /// }
+    /// ```
+    pub fn registers<'a>(&'a self) -> impl Iterator<Item=(RegisterHandle, &'a ShiftRegister)> + 'a {
+        let shifter = self.id;
+        self.shift_registers.iter().map(move |sr| {
+            (RegisterHandle { shifter: shifter, id: sr.id }, sr)
+        })
+    }
+
+    /// Returns an iterator over every pin on every shift register (in the
+    /// same order as `registers()`) yielding `(sr, pin, state)` tuples where
+    /// *state* is `true` if the pin is HIGH.  Great for status displays and
+    /// test assertions:
+    ///
+    /// ```
+    /// for (sr, pin, state) in shifter.pins() {
+    ///     println!("{:?} pin {}: {}", sr, pin, if state { "HIGH" } else { "LOW" });
+    /// }
+    /// ```
+    pub fn pins<'a>(&'a self) -> impl Iterator<Item=(RegisterHandle, u8, bool)> + 'a {
+        self.registers().flat_map(|(handle, sr)| {
+            (0..sr.pins).map(move |pin| (handle, pin, sr.pin(pin)))
+        })
+    }
+
+    /// Just like `pins()` but only for the given shift register (*sr*).
+    pub fn register_pins<'a>(&'a self, sr: RegisterHandle) -> Result<impl Iterator<Item=(RegisterHandle, u8, bool)> + 'a, ShifterError> {
+        let reg = self.register(sr)?;
+        Ok((0..reg.pins).map(move |pin| (sr, pin, reg.pin(pin))))
+    }
+
+    /// Calls *f* with a `Batch` through which you can make any number of
+    /// changes and then applies them all at once (exactly one `apply()`) when
+    /// *f* returns.  This prevents the flickering you'd get from applying
+    /// every change individually without having to remember to pass `false`
+    /// everywhere:
+    ///
+    /// ```
+    /// shifter.batch(|tx| {
+    ///     tx.set_pin_high(sr0, 3)?;
+    ///     tx.set(sr1, 0xFF)
+    /// }).unwrap();
+    /// ```
+    ///
+    /// If *f* returns an error nothing gets applied (though any changes made
+    /// before the error will still be tracked and go out with the next
+    /// `apply()`).
+    pub fn batch<F, R>(&mut self, f: F) -> Result<R, ShifterError>
+        where F: FnOnce(&mut Batch) -> Result<R, ShifterError>
+    {
+        let result = f(&mut Batch::new(self))?;
+        self.apply()?;
+        Ok(result)
+    }
+
+    /// Returns a guard through which you can make any number of changes that
+    /// will all be applied at once (exactly one `apply()`) when the guard goes
+    /// out of scope.  Unlike `batch()` this works with early returns and `?`
+    /// in your own functions:
+    ///
+    /// ```
+    /// fn update(shifter: &mut Shifter, sr0: RegisterHandle) -> Result<(), ShifterError> {
+    ///     let mut tx = shifter.deferred();
+    ///     tx.set_pin_high(sr0, 3)?; // Even if this fails...
+    ///     tx.set_pin_low(sr0, 4)?;
+    ///     Ok(())
+    /// } // ...whatever was changed gets applied here
+    /// ```
+    ///
+    /// See `Deferred` for details about error handling.
+    pub fn deferred<'a>(&'a mut self) -> Deferred<'a> {
+        Deferred::new(self)
+    }
+
+    /// Sets the *order* in which shift registers are declared via `add()`.  By
+    /// default (`Order::LastToFirst`) the *last* shift register in the chain
+    /// must be added first.  With `Order::FirstToLast` you can add them in the
+    /// same order in which they're wired (the one connected to the Raspberry
+    /// Pi first) and `apply()` will take care of shifting them out in reverse.
+    pub fn set_order(&mut self, order: Order) {
+        self.order = order;
+        self.dirty = true;
+    }
+
+    /// Applies all current shift register states by shifting out all the stored
+    /// data in each ShiftRegister object.  Returns some statistics about the
+    /// shift-out (see `ApplyStats`) which can be handy for making sure a long
+    /// chain can be refreshed fast enough.
+    pub fn apply(&mut self) -> Result<ApplyStats, ShifterError> {
+        let start = Instant::now();
+        self.pending = None; // Even if this fails (so tick() doesn't keep retrying)
+        self.last_apply = Some(start);
+        if self.shift_registers.iter().any(|sr| sr.protocol.is_some()) {
+            if self.inputs.is_some() {
+                return Err(ShifterError::Unsupported("inputs can't be read along with a ShiftProtocol"));
+            }
+            let bits = self.apply_frames()?;
+            self.dirty = false;
+            return Ok(ApplyStats { bits: bits, duration: start.elapsed() });
+        }
+        let padding = self.render();
+        let mut inputs = self.inputs.take();
+        let result = self.shift_out_chain(padding, inputs.as_mut());
+        self.inputs = inputs;
+        let bits = result?;
+        self.dirty = false;
+        Ok(ApplyStats { bits: bits, duration: start.elapsed() })
+    }
+
+    /// Clocks out the first *count* bits of *bits* (most significant bit of
+    /// the first byte first) using the same pins, `Timing`, and `ClockMode` as
+    /// `apply()` but without touching the latch or any of the shift registers
+    /// this `Shifter` is keeping track of.  Use this (along with
+    /// `pulse_latch()`) to implement custom protocols such as configuration
+    /// preambles for LED drivers:
+    ///
+    /// ```
+    /// shifter.shift_out(&[0b10110000], 4)?; // Shifts out 1, 0, 1, 1
+    /// shifter.pulse_latch()?;
+    /// ```
+    ///
+    /// Returns a `ShifterError::InvalidLength` if *bits* has fewer than
+    /// *count* bits.  If this `Shifter` uses a `ShiftBus` *count* must be a
+    /// multiple of 8 (or you'll get a `ShifterError::Unsupported`).
+    pub fn shift_out(&mut self, bits: &[u8], count: usize) -> Result<(), ShifterError> {
+        let expected = count.div_ceil(8);
+        if bits.len() < expected {
+            return Err(ShifterError::InvalidLength { expected: expected, actual: bits.len() });
+        }
+        self.shift_bits(bits, 0, count)
+    }
+
+    /// Latches whatever has been shifted in (e.g. via `shift_out()`) the same
+    /// way `apply()` does (see `LatchMode`).
+    pub fn pulse_latch(&mut self) -> Result<(), ShifterError> {
+        self.start_latch()?;
+        self.finish_latch()
+    }
+
+    // Shifts out and latches what render() put in self.buffer (after
+    // *padding* bits), reading *inputs* (if any) along the way.  Returns the
+    // number of bits shifted out.
+    fn shift_out_chain(&mut self, padding: usize, mut inputs: Option<&mut ShifterIn>) -> Result<usize, ShifterError> {
+        let bits = self.total_pins();
+        // Input shift registers sharing the clock need at least one clock per
+        // input; any extra bits fall off the far end of the outputs
+        let lead = inputs.as_ref().map_or(0, |inputs| inputs.total_pins().saturating_sub(bits));
+        if let Some(inputs) = inputs.as_deref_mut() {
+            inputs.load()?;
+        }
+        self.start_latch()?;
+        let buffer = std::mem::take(&mut self.buffer);
+        let result = self.shift_bits_with(&buffer, padding, bits, lead, inputs);
+        self.buffer = buffer;
+        result?;
+        self.finish_latch()?;
+        Ok(lead + bits)
+    }
+
+    // Clocks out *count* bits of *bytes* starting at bit number *skip*.  A
+    // ShiftBus can only send whole bytes so with one of those any bits before
+    // *skip* in the first byte (e.g. apply()'s padding) go out too.
+    fn shift_bits(&mut self, bytes: &[u8], skip: usize, count: usize) -> Result<(), ShifterError> {
+        self.shift_bits_with(bytes, skip, count, 0, None)
+    }
+
+    // Does what shift_bits() does after clocking out *lead* LOW bits, reading
+    // an input from *inputs* (if any) just before each sampling edge
+    fn shift_bits_with(&mut self, bytes: &[u8], skip: usize, count: usize, lead: usize,
+                       mut inputs: Option<&mut ShifterIn>) -> Result<(), ShifterError> {
+        if let Some(ref mut bus) = self.bus {
+            if inputs.is_some() {
+                return Err(ShifterError::Unsupported("inputs can't be read through a ShiftBus"));
+            }
+            if !(skip + count).is_multiple_of(8) {
+                return Err(ShifterError::Unsupported("a ShiftBus can only send whole bytes"));
+            }
+            return bus.write(&bytes[skip / 8..(skip + count) / 8]);
+        }
+        let idle = self.clock_mode.cpol();
+        let cpha = self.clock_mode.cpha();
+        if !cpha {
+            // The first edge does the sampling so we'd better be idle
+            self.clock.set_state(idle)?;
+        }
+        let order = inputs.as_ref().map(|inputs| inputs.read_order()).unwrap_or_default();
+        for n in 0..lead + count {
+            let high = n >= lead && {
+                let bit = skip + n - lead;
+                bytes[bit / 8] & (0x80 >> (bit % 8)) != 0
+            };
+            if cpha {
+                self.clock.set_state(!idle)?;
+                self.data.set_state(high)?;
+                self.wait(self.timing.setup);
+            } else {
+                self.data.set_state(high)?;
+                self.wait(self.timing.setup);
+            }
+            if let (Some(inputs), Some(&input)) = (inputs.as_deref_mut(), order.get(n)) {
+                inputs.sample(input)?;
+            }
+            if cpha {
+                self.clock.set_state(idle)?; // Sample
+                self.wait(self.timing.hold);
+            } else {
+                self.clock.set_state(!idle)?; // Sample
+                self.wait(self.timing.hold);
+                self.clock.set_state(idle)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Does what apply() does when some of the shift registers have a
+    // ShiftProtocol:  Shifts out (and latches) as many frames as it takes to
+    // give every shift register what it needs.  Returns the number of bits
+    // shifted out.
+    fn apply_frames(&mut self) -> Result<usize, ShifterError> {
+        // Work out the (init frames, frames, idle frame) of every shift
+        // register in the order they get shifted out:
+        let mut registers = Vec::with_capacity(self.shift_registers.len());
+        for i in 0..self.shift_registers.len() {
+            let sr = &self.shift_registers[self.shift_position(i)];
+            let outputs = sr.output_bits(self.invert);
+            registers.push(match sr.protocol {
+                Some(ref protocol) => (
+                    if sr.needs_init { protocol.init_frames() } else { Vec::new() },
+                    protocol.frames(&outputs),
+                    protocol.idle_frame(&outputs),
+                ),
+                None => (Vec::new(), vec![outputs.clone()], outputs),
+            });
+        }
+        let mut bits = 0;
+        for init in &[true, false] {
+            let frames = |register: &(Vec<Vec<bool>>, Vec<Vec<bool>>, Vec<bool>)| {
+                if *init { register.0.len() } else { register.1.len() }
+            };
+            let count = registers.iter().map(&frames).max().unwrap_or(0);
+            for n in 0..count {
+                let mut frame = Vec::new();
+                for register in registers.iter() {
+                    let list = if *init { &register.0 } else { &register.1 };
+                    frame.extend_from_slice(list.get(n).unwrap_or(&register.2));
+                }
+                self.shift_frame(&frame)?;
+                bits += frame.len();
+            }
+        }
+        for sr in self.shift_registers.iter_mut() {
+            sr.needs_init = false;
+        }
+        Ok(bits)
+    }
+
+    // Shifts out and latches the given *frame* (first bit first)
+    fn shift_frame(&mut self, frame: &[bool]) -> Result<(), ShifterError> {
+        let padding = (8 - frame.len() % 8) % 8;
+        let mut bytes = vec![0; (frame.len() + padding) / 8];
+        for (i, &high) in frame.iter().enumerate() {
+            if high {
+                bytes[(padding + i) / 8] |= 0x80 >> ((padding + i) % 8);
+            }
+        }
+        self.start_latch()?;
+        self.shift_bits(&bytes, padding, frame.len())?;
+        self.finish_latch()
+    }
+
+    // Gets the latch ready for shifting (see LatchMode)
+    fn start_latch(&mut self) -> Result<(), ShifterError> {
+        if self.latch_mode == LatchMode::None {
+            return Ok(());
+        }
+        let active = self.latch_polarity == Polarity::ActiveHigh;
+        self.latch.set_state(active == (self.latch_mode == LatchMode::Held))
+    }
+
+    // Latches whatever was shifted in since start_latch()
+    fn finish_latch(&mut self) -> Result<(), ShifterError> {
+        if self.latch_mode == LatchMode::None {
+            return Ok(());
+        }
+        let active = self.latch_polarity == Polarity::ActiveHigh;
+        self.latch.set_state(active)?;
+        self.wait(self.timing.latch);
+        if self.latch_mode == LatchMode::Pulse {
+            self.latch.set_state(!active)?;
+        }
+        Ok(())
+    }
+
+    /// Gives this `Shifter` control of the shift registers' clear (aka master
+    /// reset or SRCLR) pin which is active at the given *polarity*
+    /// (`Polarity::ActiveLow` for a 74HC595).  See `hard_clear()`.
+    pub fn set_clear_pin<P: OutputPin + 'static>(&mut self, pin: P, polarity: Polarity) -> Result<(), ShifterError> {
+        let mut pin: Box<dyn OutputPin> = Box::new(pin);
+        pin.set_state(polarity == Polarity::ActiveLow)?; // Inactive
+        self.clear = Some((pin, polarity));
+        Ok(())
+    }
+
+    /// Sets every pin on every shift register LOW by pulsing the clear pin
+    /// (see `set_clear_pin()`) which wipes the whole chain at once instead of
+    /// shifting out zeroes one bit at a time.  Returns a
+    /// `ShifterError::MissingPin` if there's no clear pin.
+    /// If *apply* is `Apply::Now` the change will be applied immediately
+    /// (which only takes a single latch pulse).
+    ///
+    /// Note that the clear pin sets the *physical* outputs LOW so if any pins
+    /// are inverted (or `invert()` is in effect) the next `apply()` shifts
+    /// everything out as usual to put things right.
+    pub fn hard_clear(&mut self, apply: Apply) -> Result<(), ShifterError> {
+        let (mut pin, polarity) = self.clear.take().ok_or(ShifterError::MissingPin("clear"))?;
+        let active = polarity == Polarity::ActiveHigh;
+        let result = pin.set_state(active)
+            .map(|_| self.wait(self.timing.latch))
+            .and_then(|_| pin.set_state(!active));
+        self.clear = Some((pin, polarity));
+        result?;
+        for sr in self.shift_registers.iter_mut() {
+            sr.data.fill(false);
+        }
+        self.render();
+        if !self.buffer.iter().all(|&byte| byte == 0) {
+            // The hardware doesn't match what we'd shift out
+            self.dirty = true;
+            return self.maybe_apply(apply);
+        }
+        if self.should_apply(apply) {
+            self.start_latch()?;
+            self.finish_latch()?;
+            self.dirty = false;
+        } else {
+            self.dirty = true;
+        }
+        Ok(())
+    }
+
+    /// Gives this `Shifter` control of the shift registers' output enable (OE)
+    /// pin which is active at the given *polarity* (`Polarity::ActiveLow` for
+    /// a 74HC595).  The outputs are disabled straight away (so nothing shows
+    /// up until you're ready) and stay that way until `enable_outputs()` is
+    /// called:
+    ///
+    /// ```
+    /// shifter.set_output_enable_pin(oe_pin, Polarity::ActiveLow)?;
+    /// shifter.set(sr0, 0b00000000, Apply::Now)?; // Get everything into a known state
+    /// shifter.enable_outputs()?;
+    /// ```
+    pub fn set_output_enable_pin<P: OutputPin + 'static>(&mut self, pin: P, polarity: Polarity) -> Result<(), ShifterError> {
+        self.oe = Some((Box::new(pin), polarity));
+        self.oe_pwm = None;
+        self.disable_outputs()
+    }
+
+    /// Just like `set_output_enable_pin()` but for an output enable pin
+    /// driven by a hardware PWM peripheral (e.g. rppal's `Pwm`) so that
+    /// `set_global_brightness()` can dim the whole chain without any help
+    /// from the CPU.
+    pub fn set_output_enable_pwm<P: PwmPin + 'static>(&mut self, pin: P, polarity: Polarity) -> Result<(), ShifterError> {
+        self.oe_pwm = Some((Box::new(pin), polarity));
+        self.oe = None;
+        self.disable_outputs()
+    }
+
+    /// Turns on the outputs of every shift register in the chain via the
+    /// output enable pin.  Returns a `ShifterError::MissingPin` if no output
+    /// enable pin was given (see `set_output_enable_pin()`).
+    pub fn enable_outputs(&mut self) -> Result<(), ShifterError> {
+        self.set_outputs_enabled(true)
+    }
+
+    /// Turns off (tristates) the outputs of every shift register in the chain
+    /// via the output enable pin.  The shift registers keep their data so
+    /// `enable_outputs()` brings everything back as it was.  Returns a
+    /// `ShifterError::MissingPin` if no output enable pin was given (see
+    /// `set_output_enable_pin()`).
+    pub fn disable_outputs(&mut self) -> Result<(), ShifterError> {
+        self.set_outputs_enabled(false)
+    }
+
+    /// Returns `true` if the outputs are enabled (which is always the case if
+    /// there's no output enable pin).
+    pub fn outputs_enabled(&self) -> bool {
+        self.outputs_enabled
+    }
+
+    fn set_outputs_enabled(&mut self, enabled: bool) -> Result<(), ShifterError> {
+        let brightness = if enabled { self.dimmer.global_duty() } else { 0.0 };
+        match (&mut self.oe, &mut self.oe_pwm) {
+            (_, &mut Some((ref mut pin, polarity))) => pin.set_duty_cycle(match polarity {
+                Polarity::ActiveHigh => brightness,
+                Polarity::ActiveLow => 1.0 - brightness,
+            })?,
+            (&mut Some((ref mut pin, polarity)), _) => pin.set_state(enabled == (polarity == Polarity::ActiveHigh))?,
+            (&mut None, &mut None) => return Err(ShifterError::MissingPin("output enable")),
+        }
+        self.outputs_enabled = enabled;
+        Ok(())
+    }
+
+    /// Sets how long `apply()` pauses between edges (see `Timing`).  Use this
+    /// if your chain glitches because the GPIO pins toggle faster than the
+    /// chips (or the wiring) can handle.
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+    }
+
+    /// Makes `apply()` pause for *ns* nanoseconds between every clock edge
+    /// (both the `setup` and `hold` times of `Timing`).  This roughly limits
+    /// the clock to 1 / (2 * *ns*) Hz which can clean up signals that have to
+    /// travel over long cables:
+    ///
+    /// ```
+    /// shifter.set_clock_delay_ns(500); // No faster than ~1MHz
+    /// ```
+    ///
+    /// Use 0 to go back to going as fast as possible.
+    pub fn set_clock_delay_ns(&mut self, ns: u32) {
+        let delay = Duration::new(0, ns);
+        self.timing.setup = delay;
+        self.timing.hold = delay;
+    }
+
+    /// Sets which level of the latch pin makes the shift registers latch (see
+    /// `Polarity`).  The pulse width can be set via `Timing.latch`.
+    pub fn set_latch_polarity(&mut self, polarity: Polarity) {
+        self.latch_polarity = polarity;
+    }
+
+    /// Sets how `apply()` drives the latch (aka strobe) pin (see `LatchMode`).
+    /// Combine `LatchMode::Pulse` with `Polarity::ActiveHigh` for a CD4094 or
+    /// similar.
+    pub fn set_latch_mode(&mut self, mode: LatchMode) {
+        self.latch_mode = mode;
+    }
+
+    /// Sets the clock polarity and phase used by `apply()` (see `ClockMode`).
+    /// Use this for chips that sample data on the falling edge or expect the
+    /// clock to idle LOW.  Note that this doesn't affect `Shifter::from_bus()`
+    /// (configure the bus itself instead).
+    pub fn set_clock_mode(&mut self, mode: ClockMode) {
+        self.clock_mode = mode;
+    }
+
+    /// Returns the current `Timing` settings.
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    /// Sets what `apply()` uses to pause between edges (`SpinDelay` by
+    /// default).  Supply your own to use a hardware timer or `NoDelay` to skip
+    /// the pauses entirely (e.g. in tests).
+    pub fn set_delay<D: Delay + 'static>(&mut self, delay: D) {
+        self.delay = Box::new(delay);
+    }
+
+    /// Returns `true` if anything has changed since the last `apply()` (or if
+    /// nothing has been applied yet).
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Just like `apply()` but skips shifting anything out if nothing has
+    /// changed since the last `apply()`.  Returns `None` if nothing was
+    /// applied.  Ideal for render loops that would otherwise call `apply()`
+    /// every frame.
+    pub fn apply_if_changed(&mut self) -> Result<Option<ApplyStats>, ShifterError> {
+        if !self.dirty {
+            return Ok(None);
+        }
+        self.apply().map(Some)
+    }
+
+    // Pauses for the given *duration* via self.delay (if there's anything to
+    // wait for)
+    fn wait(&mut self, duration: Duration) {
+        if duration > Duration::from_secs(0) {
+            self.delay.delay(duration);
+        }
+    }
+
+    // Returns the position (in self.shift_registers) of the shift register
+    // that gets shifted out *i*th
+    fn shift_position(&self, i: usize) -> usize {
+        match self.order {
+            Order::LastToFirst => i,
+            Order::FirstToLast => self.shift_registers.len() - 1 - i,
+        }
+    }
+
+    // Renders everything that apply() shifts out into self.buffer (most
+    // significant bit first, inversion/bit order/remapping already taken care
+    // of) so it can be streamed out in one go.  If the total isn't a multiple
+    // of 8 the first byte is padded at the front (those extra bits get pushed
+    // right off the end of the chain); returns the number of padding bits.
+    fn render(&mut self) -> usize {
+        let total = self.total_pins();
+        let padding = (8 - total % 8) % 8;
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.clear();
+        buffer.resize((total + padding) / 8, 0);
+        let mut bit = padding;
+        for i in 0..self.shift_registers.len() {
+            let sr = &self.shift_registers[self.shift_position(i)];
+            for i in 0..sr.pins {
+                if sr.output_bit(i, self.invert) {
+                    buffer[bit / 8] |= 0x80 >> (bit % 8);
+                }
+                bit += 1;
+            }
+        }
+        self.buffer = buffer;
+        padding
+    }
+
+    // Brings self.positions up to date for every shift register from *start*
+    // onward (after something was inserted or removed)
+    fn update_positions(&mut self, start: usize) {
+        for (i, reg) in self.shift_registers.iter().enumerate().skip(start) {
+            self.positions[reg.id] = Some(i);
+        }
+    }
+
+    // Applies the current state (or not) depending on *apply*
+    fn maybe_apply(&mut self, apply: Apply) -> Result<(), ShifterError> {
+        if !self.should_apply(apply) {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let due = match (self.pending, self.last_apply) {
+            (Some(due), _) => due,
+            (None, Some(last)) => std::cmp::max(now + self.coalesce, last + self.min_interval),
+            (None, None) => now + self.coalesce,
+        };
+        if due > now {
+            self.pending = Some(due);
+            return Ok(()); // tick() will take care of it
+        }
+        self.apply()?;
+        Ok(())
+    }
+
+    // Returns true if *apply* means the change should be applied right away
+    fn should_apply(&self, apply: Apply) -> bool {
+        match apply {
+            Apply::Now => true,
+            Apply::Later => false,
+            Apply::Auto => self.auto_apply,
+        }
+    }
+
+    // Converts a chain-wide pin *index* into a position in self.shift_registers
+    // and the pin on that shift register
+    fn chain_pin(&self, index: usize) -> Result<(usize, u8), ShifterError> {
+        let mut remaining = index;
+        for (position, sr) in self.shift_registers.iter().enumerate() {
+            if remaining < sr.pins as usize {
+                return Ok((position, remaining as u8));
+            }
+            remaining -= sr.pins as usize;
+        }
+        Err(ShifterError::InvalidPosition(index))
+    }
+
+    fn lookup_name(&self, name: &str) -> Result<(RegisterHandle, u8), ShifterError> {
+        match self.names.get(name) {
+            Some(&named) => Ok(named),
+            None => Err(ShifterError::UnknownName(name.to_string())),
+        }
+    }
+
+    // Returns the pins in the group with the given name after making sure they
+    // all still exist (so a group is never left half-changed)
+    fn lookup_group(&self, name: &str) -> Result<Vec<(RegisterHandle, u8)>, ShifterError> {
+        let group = match self.groups.get(name) {
+            Some(group) => group,
+            None => return Err(ShifterError::UnknownName(name.to_string())),
+        };
+        for &(sr, pin) in group.pins.iter() {
+            self.register(sr)?.check_pin(pin)?;
+        }
+        Ok(group.pins.clone())
+    }
+
+    // Returns the shift register the given handle refers to (or an error if
+    // the handle belongs to a different Shifter or was removed)
+    fn register(&self, sr: RegisterHandle) -> Result<&ShiftRegister, ShifterError> {
+        let position = self.position(sr)?;
+        Ok(&self.shift_registers[position])
+    }
+
+    // (Every change to a shift register goes through here so this is where we
+    // keep track of whether or not there's anything new to apply)
+    fn register_mut(&mut self, sr: RegisterHandle) -> Result<&mut ShiftRegister, ShifterError> {
+        let position = self.position(sr)?;
+        self.dirty = true;
+        Ok(&mut self.shift_registers[position])
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::{Duration, Instant, SystemTime};
+    use super::{Shifter, ShifterError, InputChange, Debounce, Gestures, Keypad, Command, DimmingMode, FrameLoop, Animation, Target, Easing, Sequence, Repeat, Schedule, TimeOfDay, Recurrence, Weekday, SunEvent, DropPolicy, ShifterIn, Order, OutputPin, PwmPin, ShiftBus, Apply, BitOrder, PinNumbering, Signal, Timing, Polarity, LatchMode, ClockMode, Hc595, Max7219, InputPin};
+    use register::{Bits, ShiftRegister};
+    use blink::Blink;
+    use persist;
+    use recurrence;
+    use schedule;
+    use sun;
+
+    #[test]
+    fn it_works() {
+    }
+
+    #[test]
+    fn bits_wider_than_a_word() {
+        let mut bits = Bits::new(96);
+        assert_eq!(bits.bytes.len(), 12);
+        bits.set(95, true);
+        bits.set(3, true);
+        assert!(bits.get(95));
+        assert_eq!(bits.word(), 0b1000);
+        bits.set_word(0b1);
+        assert!(!bits.get(95)); // set_word() clears everything else
+        assert_eq!(bits.word(), 0b1);
+    }
+
+    #[test]
+    fn bits_masked_and_resized() {
+        let mut bits = Bits::new(12);
+        bits.fill(true);
+        assert_eq!(bits.bytes, vec![0xFF, 0x0F]);
+        bits.set_word_masked(0b0000, 0b0110);
+        assert_eq!(bits.word(), 0b1111_1111_1001);
+        bits.resize(6);
+        assert_eq!(bits.bytes, vec![0b11_1001]);
+        bits.resize(10);
+        assert_eq!(bits.word(), 0b11_1001);
+    }
+
+    #[test]
+    fn blink_schedule() {
+        let start = Instant::now();
+        assert!(Blink::new(0.0, start).is_none());
+        assert!(Blink::new(1e-300, start).is_none()); // Too slow for a Duration
+        let mut blink = Blink::new(2.0, start).unwrap(); // Toggles every 250ms
+        assert!(!blink.due(start));
+        assert!(blink.due(start + Duration::from_millis(250)));
+        assert!(!blink.due(start + Duration::from_millis(300)));
+        assert!(blink.due(start + Duration::from_millis(500)));
+        blink.stop();
+        assert!(!blink.due(start + Duration::from_secs(10)));
+        blink.start(start + Duration::from_secs(10));
+        assert!(blink.due(start + Duration::from_millis(10250)));
+    }
+
+    #[test]
+    fn bits_rotated_and_shifted() {
+        let mut bits = Bits::new(8);
+        bits.set_word(0b1000_0001);
+        bits.rotate_left(1);
+        assert_eq!(bits.word(), 0b0000_0011);
+        bits.rotate_left(7); // Same as rotating right by 1
+        assert_eq!(bits.word(), 0b1000_0001);
+        bits.shift_in(false);
+        assert_eq!(bits.word(), 0b0000_0010);
+        bits.shift_in(true);
+        assert_eq!(bits.word(), 0b0000_0101);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn snapshot_serde_round_trip() {
+        extern crate serde_json;
+        use super::{Snapshot, RegisterSnapshot, Order, BitOrder};
+        let snapshot = Snapshot {
+            invert: false,
+            order: Order::FirstToLast,
+            registers: vec![RegisterSnapshot {
+                pins: 8,
+                name: Some("relays".to_string()),
+                data: vec![0b1010_0101],
+                invert: true,
+                invert_mask: vec![0],
+                remap: Some(vec![7, 6, 5, 4, 3, 2, 1, 0]),
+                bit_order: BitOrder::MsbFirst,
+            }],
+        };
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(serde_json::from_str::<Snapshot>(&json).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn state_file_round_trip() {
+        let contents = persist::encode(&[(8, &[0xA5]), (12, &[0xFF, 0x01])]);
+        assert_eq!(contents, "# cupi_shift state\n8 a5\n12 ff 01\n");
+        assert_eq!(persist::decode(&contents).unwrap(), vec![(8, vec![0xA5]), (12, vec![0xFF, 0x01])]);
+        assert!(persist::decode("8 zz\n").is_err());
+    }
+
+    // Minimal pins for watching what apply() shifts out: DataPin keeps track of
+    // the level of the data line and ClockPin records it on every rising edge
+    struct DataPin(Arc<AtomicBool>);
+    struct ClockPin(Arc<AtomicBool>, Arc<Mutex<Vec<bool>>>);
+    struct NullPin;
+
+    impl OutputPin for DataPin {
+        fn set_high(&mut self) -> Result<(), ShifterError> { self.0.store(true, Ordering::SeqCst); Ok(()) }
+        fn set_low(&mut self) -> Result<(), ShifterError> { self.0.store(false, Ordering::SeqCst); Ok(()) }
+    }
+
+    impl OutputPin for ClockPin {
+        fn set_high(&mut self) -> Result<(), ShifterError> {
+            self.1.lock().unwrap().push(self.0.load(Ordering::SeqCst));
+            Ok(())
+        }
+        fn set_low(&mut self) -> Result<(), ShifterError> { Ok(()) }
+    }
+
+    impl OutputPin for NullPin {
+        fn set_high(&mut self) -> Result<(), ShifterError> { Ok(()) }
+        fn set_low(&mut self) -> Result<(), ShifterError> { Ok(()) }
+    }
+
+    #[test]
+    fn apply_shifts_out_bits() {
+        let level = Arc::new(AtomicBool::new(false));
+        let bits = Arc::new(Mutex::new(Vec::new()));
+        let mut shifter = Shifter::from_output_pins(
+            DataPin(level.clone()), NullPin, ClockPin(level.clone(), bits.clone()));
+        let sr0 = shifter.add(4);
+        let sr1 = shifter.add(4);
+        shifter.set(sr0, 0b0001, Apply::Later).unwrap();
+        shifter.set(sr1, 0b1000, Apply::Later).unwrap();
+        assert_eq!(shifter.apply().unwrap().bits, 8);
+        assert_eq!(*bits.lock().unwrap(), vec![true, false, false, false, false, false, false, true]);
+    }
+
+    struct VecBus(Arc<Mutex<Vec<u8>>>);
+
+    impl ShiftBus for VecBus {
+        fn write(&mut self, bytes: &[u8]) -> Result<(), ShifterError> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn apply_writes_bytes_to_bus() {
+        let bytes = Arc::new(Mutex::new(Vec::new()));
+        let mut shifter = Shifter::from_bus(VecBus(bytes.clone()), NullPin);
+        let sr0 = shifter.add(4);
+        let sr1 = shifter.add(8);
+        shifter.set(sr0, 0b0001, Apply::Later).unwrap();
+        shifter.set(sr1, 0b1000_0001, Apply::Later).unwrap();
+        assert_eq!(shifter.apply().unwrap().bits, 12);
+        // Four bits of padding then the same bits apply_shifts_out_bits() sees
+        assert_eq!(*bytes.lock().unwrap(), vec![0b0000_1000, 0b1000_0001]);
+    }
+
+    #[test]
+    fn bus_rejects_partial_bytes() {
+        let bytes = Arc::new(Mutex::new(Vec::new()));
+        let mut shifter = Shifter::from_bus(VecBus(bytes.clone()), NullPin);
+        match shifter.shift_out(&[0b1011_0000], 4) {
+            Err(ShifterError::Unsupported(_)) => {},
+            other => panic!("Expected Unsupported, got {:?}", other),
+        }
+        shifter.shift_out(&[0b1011_0000], 8).unwrap();
+        assert_eq!(*bytes.lock().unwrap(), vec![0b1011_0000]);
+    }
+
+    struct LowPin;
+
+    impl InputPin for LowPin {
+        fn is_high(&mut self) -> Result<bool, ShifterError> { Ok(false) }
+    }
+
+    #[test]
+    fn bus_cant_read_inputs() {
+        let mut shifter = Shifter::from_bus(VecBus(Arc::new(Mutex::new(Vec::new()))), NullPin);
+        shifter.add(8);
+        shifter.attach_inputs(LowPin, NullPin).add(8);
+        match shifter.apply() {
+            Err(ShifterError::Unsupported(_)) => {},
+            other => panic!("Expected Unsupported, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn apply_waveform() {
+        let (mut shifter, recording) = Shifter::recording();
+        let sr0 = shifter.add(4);
+        let sr1 = shifter.add(8);
+        shifter.set(sr0, 0b0110, Apply::Later).unwrap();
+        shifter.set(sr1, 0b10000001, Apply::Now).unwrap();
+        shifter.apply().unwrap();
+        recording.assert_latch_pulses(2);
+        recording.assert_clocked_while_latch_low();
+        recording.assert_data_stable_while_clock_high();
+        assert_eq!(recording.rising_edges(Signal::Clock), 24);
+        let frame = vec![false, true, true, false, true, false, false, false, false, false, false, true];
+        assert_eq!(recording.frames(), vec![frame.clone(), frame]);
+        let events = recording.events();
+        assert!(events.windows(2).all(|pair| pair[0].time <= pair[1].time));
+        assert_eq!(events.last().map(|event| (event.signal, event.high)), Some((Signal::Latch, true)));
+        let mut vcd = Vec::new();
+        recording.write_vcd(&mut vcd).unwrap();
+        let vcd = String::from_utf8(vcd).unwrap();
+        assert!(vcd.contains("$var wire 1 l latch $end"));
+        assert_eq!(vcd.lines().filter(|line| *line == "1l").count(), 2);
+        recording.clear();
+        assert!(recording.frames().is_empty());
+    }
+
+    #[test]
+    fn apply_delays() {
+        let (mut shifter, recording) = Shifter::recording();
+        let delays = Arc::new(Mutex::new(Vec::new()));
+        let log = delays.clone();
+        shifter.set_delay(move |duration| log.lock().unwrap().push(duration));
+        shifter.add(2);
+        shifter.apply().unwrap();
+        assert!(delays.lock().unwrap().is_empty()); // No pauses by default
+        let setup = Duration::from_nanos(500);
+        let hold = Duration::from_micros(1);
+        shifter.set_timing(Timing { setup: setup, hold: hold, ..Timing::default() });
+        shifter.apply().unwrap();
+        assert_eq!(*delays.lock().unwrap(), vec![setup, hold, setup, hold]);
+        delays.lock().unwrap().clear();
+        shifter.set_clock_delay_ns(250);
+        shifter.apply().unwrap();
+        assert_eq!(*delays.lock().unwrap(), vec![Duration::from_nanos(250); 4]);
+        recording.assert_latch_pulses(3);
+        delays.lock().unwrap().clear();
+        shifter.set_clock_delay_ns(0);
+        shifter.set_timing(Timing { latch: hold, ..shifter.timing() });
+        shifter.set_latch_polarity(Polarity::ActiveLow);
+        shifter.apply().unwrap();
+        assert_eq!(*delays.lock().unwrap(), vec![hold]);
+        let latch: Vec<bool> = recording.events().iter()
+            .filter(|event| event.signal == Signal::Latch)
+            .map(|event| event.high)
+            .collect();
+        assert_eq!(&latch[latch.len() - 2..], &[true, false]);
+    }
+
+    #[test]
+    fn clock_modes() {
+        let (mut shifter, recording) = Shifter::recording();
+        let sr0 = shifter.add(3);
+        shifter.set(sr0, 0b011, Apply::Later).unwrap();
+        for &mode in &[ClockMode::Mode0, ClockMode::Mode1, ClockMode::Mode2, ClockMode::Mode3] {
+            assert_eq!(ClockMode::new(mode.cpol(), mode.cpha()), mode);
+            recording.clear();
+            shifter.set_clock_mode(mode);
+            shifter.apply().unwrap();
+            // Figure out what got sampled on the right edge of the clock:
+            let sample_rising = mode.cpol() == mode.cpha();
+            let (mut data, mut clock, mut bits) = (false, !sample_rising, Vec::new());
+            for event in recording.events() {
+                match event.signal {
+                    Signal::Data => data = event.high,
+                    Signal::Clock => {
+                        if event.high == sample_rising && clock != sample_rising {
+                            bits.push(data);
+                        }
+                        clock = event.high;
+                    },
+                    Signal::Latch => {},
+                }
+            }
+            assert_eq!(bits, vec![true, true, false], "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn latch_modes() {
+        let (mut shifter, recording) = Shifter::recording();
+        shifter.add(8);
+        let latch_levels = || -> Vec<bool> {
+            recording.events().iter()
+                .filter(|event| event.signal == Signal::Latch)
+                .map(|event| event.high)
+                .collect()
+        };
+        shifter.set_latch_mode(LatchMode::Pulse);
+        shifter.apply().unwrap();
+        shifter.apply().unwrap();
+        assert_eq!(latch_levels(), vec![false, true, false, true, false]);
+        recording.clear();
+        shifter.set_latch_mode(LatchMode::Held);
+        shifter.apply().unwrap();
+        assert_eq!(latch_levels(), vec![true]); // Never goes inactive
+        recording.clear();
+        shifter.set_latch_polarity(Polarity::ActiveLow);
+        shifter.apply().unwrap();
+        assert_eq!(latch_levels(), vec![false]);
+        recording.clear();
+        shifter.set_latch_mode(LatchMode::None);
+        shifter.apply().unwrap();
+        assert!(latch_levels().is_empty());
+        assert_eq!(recording.rising_edges(Signal::Clock), 8);
+    }
+
+    #[test]
+    fn output_enable() {
+        let (mut shifter, _) = Shifter::mock();
+        assert!(shifter.outputs_enabled());
+        assert!(shifter.enable_outputs().is_err());
+        let level = Arc::new(AtomicBool::new(false));
+        shifter.set_output_enable_pin(DataPin(level.clone()), Polarity::ActiveLow).unwrap();
+        assert!(!shifter.outputs_enabled());
+        assert!(level.load(Ordering::SeqCst)); // Active-low so HIGH is disabled
+        shifter.enable_outputs().unwrap();
+        assert!(shifter.outputs_enabled());
+        assert!(!level.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn hard_clear() {
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(8);
+        shifter.set(sr0, 0b11111111, Apply::Now).unwrap();
+        assert!(shifter.hard_clear(Apply::Now).is_err());
+        let level = Arc::new(AtomicBool::new(false));
+        shifter.set_clear_pin(DataPin(level.clone()), Polarity::ActiveLow).unwrap();
+        assert!(level.load(Ordering::SeqCst));
+        shifter.hard_clear(Apply::Now).unwrap();
+        assert!(level.load(Ordering::SeqCst)); // Back to inactive after the pulse
+        assert_eq!(shifter.get(sr0).unwrap(), 0);
+        assert!(!shifter.is_dirty());
+        assert_eq!(mock.apply_count(), 2); // Latched without shifting anything
+        shifter.invert();
+        shifter.hard_clear(Apply::Later).unwrap();
+        assert!(shifter.is_dirty());
+    }
+
+    #[test]
+    fn raw_shift_out() {
+        let (mut shifter, recording) = Shifter::recording();
+        shifter.shift_out(&[0b10110000], 4).unwrap();
+        recording.assert_latch_pulses(0);
+        shifter.pulse_latch().unwrap();
+        assert_eq!(recording.frames(), vec![vec![true, false, true, true]]);
+        assert!(shifter.shift_out(&[0xFF], 9).is_err());
+    }
+
+    #[test]
+    fn protocols() {
+        let (mut shifter, recording) = Shifter::recording();
+        let sr0 = shifter.add(4);
+        let display = shifter.add(16); // Just the first two digits
+        shifter.set(sr0, 0b0011, Apply::Later).unwrap();
+        shifter.set(display, 0b10000000_00000001, Apply::Later).unwrap();
+        shifter.set_protocol(sr0, Hc595).unwrap();
+        shifter.apply().unwrap();
+        let mut frame = vec![true, true, false, false, true];
+        frame.extend_from_slice(&[false; 14]);
+        frame.push(true);
+        assert_eq!(recording.frames(), vec![frame]);
+        recording.clear();
+        shifter.set_protocol(display, Max7219::default()).unwrap();
+        assert_eq!(shifter.apply().unwrap().bits, (5 + 2) * 20);
+        let frames = recording.frames();
+        assert_eq!(frames.len(), 7); // Five init commands then two digits
+        let command = |frame: &Vec<bool>| frame[4..].iter().fold(0u16, |word, &bit| word << 1 | bit as u16);
+        assert_eq!(command(&frames[0]), 0x0F00);
+        assert_eq!(command(&frames[5]), 0x0101);
+        assert_eq!(command(&frames[6]), 0x0280);
+        assert!(frames.iter().all(|frame| frame[..4] == [true, true, false, false]));
+        recording.clear();
+        shifter.apply().unwrap();
+        assert_eq!(recording.frames().len(), 2); // No more init
+    }
+
+    #[test]
+    fn calibration() {
+        let (mut shifter, recording) = Shifter::recording();
+        shifter.add(8);
+        shifter.set_clock_delay_ns(50_000);
+        let calibration = shifter.calibrate(3).unwrap();
+        recording.assert_latch_pulses(3);
+        assert_eq!(calibration.bits, 8);
+        assert!(calibration.fastest >= Duration::from_micros(800));
+        assert!(calibration.fastest <= calibration.average && calibration.average <= calibration.slowest);
+        assert!(calibration.clock_rate() < 10_000.0);
+        assert!(calibration.check_refresh_rate(1.0).is_ok());
+        assert!(calibration.check_refresh_rate(1e6).is_err());
+        assert!(shifter.calibrate(0).is_err());
+    }
+
+    #[test]
+    fn mock_chain() {
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(4);
+        let sr1 = shifter.add(4);
+        shifter.set(sr0, 0b0001, Apply::Later).unwrap();
+        shifter.set(sr1, 0b1000, Apply::Now).unwrap();
+        assert_eq!(mock.outputs(), vec![true, false, false, false, false, false, false, true]);
+        shifter.invert();
+        shifter.apply().unwrap();
+        assert_eq!(mock.output(0), Some(false));
+        assert_eq!(mock.output(8), None);
+        assert_eq!(mock.apply_count(), 2);
+        mock.clear_frames();
+        assert!(mock.frames().is_empty());
+    }
+
+    #[test]
+    fn remove_registers() {
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(4);
+        let sr1 = shifter.add(4);
+        let sr2 = shifter.add(4);
+        shifter.remove(sr1).unwrap();
+        assert!(shifter.get(sr1).is_err());
+        assert!(shifter.remove(sr1).is_err());
+        assert_eq!(shifter.position(sr2).unwrap(), 1);
+        shifter.set(sr0, 0b0001, Apply::Later).unwrap();
+        shifter.set(sr2, 0b1000, Apply::Later).unwrap();
+        assert_eq!(shifter.apply().unwrap().bits, 8);
+        assert_eq!(mock.outputs(), vec![true, false, false, false, false, false, false, true]);
+    }
+
+    #[test]
+    fn remove_forgets_pin_names() {
+        let (mut shifter, _) = Shifter::mock();
+        let sr0 = shifter.add(8);
+        let sr1 = shifter.add(8);
+        shifter.name_pin(sr0, 1, "porch").unwrap();
+        shifter.name_pin(sr1, 2, "garage").unwrap();
+        shifter.remove(sr0).unwrap();
+        assert_eq!(shifter.named_pin("porch"), None);
+        match shifter.set_named("porch", true, Apply::Later) {
+            Err(ShifterError::UnknownName(_)) => {},
+            other => panic!("Expected UnknownName, got {:?}", other),
+        }
+        shifter.set_named("garage", true, Apply::Later).unwrap();
+        shifter.name_pin(sr1, 3, "porch").unwrap(); // The name can be reused
+        assert!(!shifter.get_named("porch").unwrap());
+    }
+
+    #[test]
+    fn remove_prunes_groups() {
+        let (mut shifter, _) = Shifter::mock();
+        let sr0 = shifter.add(8);
+        let sr1 = shifter.add(8);
+        shifter.define_group("reds", &[(sr0, 0), (sr1, 2), (sr1, 5)]).unwrap();
+        shifter.remove(sr0).unwrap();
+        assert_eq!(shifter.group("reds").unwrap().pins(), &[(sr1, 2), (sr1, 5)][..]);
+        shifter.set_group("reds", true, Apply::Later).unwrap();
+        assert_eq!(shifter.get(sr1).unwrap(), 0b0010_0100);
+        shifter.toggle_group("reds", Apply::Later).unwrap();
+        assert_eq!(shifter.get(sr1).unwrap(), 0);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn bool_apply_shims() {
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(4);
+        shifter.set_bool(sr0, 0b0001, false).unwrap();
+        assert_eq!(mock.apply_count(), 0);
+        shifter.set_pin_high_bool(sr0, 3, true).unwrap();
+        assert_eq!(mock.outputs(), vec![true, false, false, true]);
+        shifter.set_pin_low_bool(sr0, 0, true).unwrap();
+        assert_eq!(mock.outputs(), vec![false, false, false, true]);
+        assert_eq!(mock.apply_count(), 2);
+    }
+
+    #[test]
+    fn insert_registers() {
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(4);
+        let sr1 = shifter.add(4);
+        shifter.set(sr0, 0b0001, Apply::Later).unwrap();
+        shifter.set(sr1, 0b1000, Apply::Now).unwrap();
+        assert_eq!(mock.outputs(), vec![true, false, false, false, false, false, false, true]);
+        let middle = shifter.insert(1, 2).unwrap();
+        let front = shifter.add_front(2);
+        assert!(shifter.insert(5, 2).is_err());
+        assert_eq!(shifter.position(front).unwrap(), 0);
+        assert_eq!(shifter.position(sr0).unwrap(), 1);
+        assert_eq!(shifter.position(middle).unwrap(), 2);
+        assert_eq!(shifter.position(sr1).unwrap(), 3);
+        assert_eq!(shifter.get(sr1).unwrap(), 0b1000); // Still the same register
+        shifter.set(front, 0b11, Apply::Later).unwrap();
+        shifter.set(middle, 0b10, Apply::Now).unwrap();
+        assert_eq!(mock.outputs(), vec![
+            true, true, // front
+            true, false, false, false, // sr0
+            false, true, // middle
+            false, false, false, true, // sr1
+        ]);
+    }
+
+    #[test]
+    fn first_to_last_order() {
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(4);
+        let sr1 = shifter.add(4);
+        shifter.set(sr0, 0b0001, Apply::Later).unwrap();
+        shifter.set(sr1, 0b1000, Apply::Now).unwrap();
+        assert_eq!(mock.outputs(), vec![true, false, false, false, false, false, false, true]);
+        shifter.set_order(Order::FirstToLast); // sr0 is now nearest the Raspberry Pi
+        shifter.apply().unwrap();
+        assert_eq!(mock.outputs(), vec![false, false, false, true, true, false, false, false]);
+    }
+
+    #[test]
+    fn chain_round_trip() {
+        let (mut shifter, _) = Shifter::mock();
+        let sr0 = shifter.add(3);
+        let sr1 = shifter.add(7);
+        let sr2 = shifter.add(5);
+        assert_eq!(shifter.get_chain(), vec![0, 0]);
+        // Registers straddle byte boundaries: sr1 starts at bit 3, sr2 at bit 10
+        let frame = [0b1010_1101, 0b0101_0110];
+        shifter.set_chain(&frame, Apply::Later).unwrap();
+        assert_eq!(shifter.get(sr0).unwrap(), 0b101);
+        assert_eq!(shifter.get(sr1).unwrap(), 0b101_0101);
+        assert_eq!(shifter.get(sr2).unwrap(), 0b1_0101);
+        assert_eq!(shifter.get_chain(), frame.to_vec());
+        assert!(shifter.set_chain(&[0xFF], Apply::Later).is_err());
+        assert!(shifter.set_chain(&[0xFF; 3], Apply::Later).is_err());
+    }
+
+    #[test]
+    fn batch_applies_once() {
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(4);
+        let sr1 = shifter.add(4);
+        shifter.batch(|tx| {
+            tx.set_pin_high(sr0, 0)?;
+            tx.set(sr1, 0b1000)?;
+            assert_eq!(mock.apply_count(), 0); // Nothing shifted out yet
+            Ok(())
+        }).unwrap();
+        assert_eq!(mock.frames(), vec![vec![true, false, false, false, false, false, false, true]]);
+        assert!(shifter.batch(|tx| tx.set(sr0, 0b0010).and_then(|_| tx.set_pin_high(sr1, 9))).is_err());
+        assert_eq!(mock.apply_count(), 1); // A failed batch doesn't apply
+    }
+
+    #[test]
+    fn deferred_applies_on_drop() {
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(4);
+        {
+            let mut tx = shifter.deferred();
+            tx.set_pin_high(sr0, 0).unwrap();
+            tx.set_pin_high(sr0, 3).unwrap();
+            assert_eq!(mock.apply_count(), 0);
+        }
+        assert_eq!(mock.frames(), vec![vec![true, false, false, true]]);
+        let mut tx = shifter.deferred();
+        tx.set_pin_low(sr0, 3).unwrap();
+        assert_eq!(mock.apply_count(), 1);
+        tx.commit().unwrap();
+        assert_eq!(mock.outputs(), vec![true, false, false, false]);
+    }
+
+    #[test]
+    fn apply_if_changed_skips_clean() {
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(8);
+        shifter.set(sr0, 0b0000_0101, Apply::Later).unwrap();
+        assert!(shifter.apply_if_changed().unwrap().is_some());
+        assert_eq!(mock.apply_count(), 1);
+        assert!(!shifter.is_dirty());
+        assert!(shifter.apply_if_changed().unwrap().is_none());
+        assert_eq!(mock.apply_count(), 1);
+        shifter.set_pin_high(sr0, 7, Apply::Later).unwrap();
+        assert!(shifter.apply_if_changed().unwrap().is_some());
+        assert_eq!(mock.apply_count(), 2);
+    }
+
+    #[test]
+    fn pin_numbering() {
+        assert_eq!(PinNumbering::WiringPi.to_wiringpi(29).unwrap(), 29);
+        assert_eq!(PinNumbering::Bcm.to_wiringpi(21).unwrap(), 29);
+        assert_eq!(PinNumbering::Physical.to_wiringpi(40).unwrap(), 29);
+        assert_eq!(PinNumbering::Physical.to_wiringpi(3).unwrap(), 8);
+        assert!(PinNumbering::Physical.to_wiringpi(6).is_err()); // Ground
+        assert!(PinNumbering::Physical.to_wiringpi(0).is_err());
+        assert!(PinNumbering::Bcm.to_wiringpi(54).is_err());
+        assert!(PinNumbering::WiringPi.to_wiringpi(32).is_err());
+    }
+
+    #[test]
+    fn output_bits() {
+        let mut sr = ShiftRegister::new(0, 4);
+        sr.set(0b0011);
+        let out = |sr: &ShiftRegister, invert| (0..4).map(|i| sr.output_bit(i, invert)).collect::<Vec<_>>();
+        assert_eq!(out(&sr, false), vec![true, true, false, false]);
+        assert_eq!(out(&sr, true), vec![false, false, true, true]);
+        sr.bit_order = BitOrder::MsbFirst;
+        assert_eq!(out(&sr, false), vec![false, false, true, true]);
+        sr.bit_order = BitOrder::LsbFirst;
+        sr.remap = Some(vec![3, 2, 1, 0]); // Physical output 0 is driven by logical pin 3
+        assert_eq!(out(&sr, false), vec![false, false, true, true]);
+        sr.remap = None;
+        sr.invert = true;
+        sr.invert_mask.set(0, true); // Cancels out the register-wide inversion
+        assert_eq!(out(&sr, false), vec![true, false, true, true]);
+    }
+
+    #[test]
+    fn background_refresh() {
+        assert!(Shifter::mock().0.start_refresh(0.0).is_err());
+        assert!(matches!(Shifter::mock().0.start_refresh(1e-300), Err(ShifterError::InvalidRate(_))));
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(8);
+        let refresh = shifter.start_refresh(1000.0).unwrap();
+        let started = Instant::now();
+        refresh.lock().set(sr0, 0b00000001, Apply::Later).unwrap();
+        while mock.output(0) != Some(true) {
+            assert!(started.elapsed() < Duration::from_secs(5), "The refresh thread never applied");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        refresh.pause();
+        assert!(refresh.is_paused());
+        assert!(refresh.is_running());
+        let shifter = refresh.stop();
+        assert_eq!(shifter.get(sr0).unwrap(), 1);
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(8);
+        let shifter = shifter.into_shared();
+        let threads: Vec<_> = (0..8).map(|pin| {
+            let shifter = shifter.clone();
+            std::thread::spawn(move || shifter.set_pin_high(sr0, pin, Apply::Now).unwrap())
+        }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(shifter.get(sr0).unwrap(), 0b11111111);
+        assert_eq!(mock.apply_count(), 8);
+        assert_eq!(mock.outputs(), vec![true; 8]);
+        let other = shifter.clone();
+        let shifter = shifter.try_unwrap().unwrap_err();
+        drop(other);
+        assert!(shifter.try_unwrap().is_ok());
+    }
+
+    #[test]
+    fn split_registers() {
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(4);
+        shifter.add(4);
+        let parts = shifter.split();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].handle(), sr0);
+        let threads: Vec<_> = parts.into_iter().enumerate().map(|(i, part)| {
+            std::thread::spawn(move || part.set(1 << i, Apply::Now).unwrap())
+        }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(mock.outputs(), vec![true, false, false, false, false, true, false, false]);
+    }
+
+    #[test]
+    fn client_commands() {
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(4);
+        let (client, thread) = shifter.spawn().unwrap();
+        let other = client.clone();
+        other.send(Command::SetPin { sr: sr0, pin: 0, state: true, apply: Apply::Later }).unwrap();
+        client.post(Command::Set { sr: sr0, data: 0b0011, apply: Apply::Now }).unwrap();
+        assert_eq!(client.execute(move |shifter| shifter.get(sr0)).unwrap(), 0b0011);
+        assert!(client.send(Command::SetPin { sr: sr0, pin: 4, state: true, apply: Apply::Now }).is_err());
+        assert_eq!(mock.outputs(), vec![true, true, false, false]);
+        drop(client);
+        drop(other);
+        assert_eq!(thread.join().unwrap().get(sr0).unwrap(), 0b0011);
+    }
+
+    #[test]
+    fn coalesced_applies() {
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(4);
+        shifter.set_coalesce_window(Duration::from_millis(20));
+        shifter.set_pin_high(sr0, 0, Apply::Now).unwrap();
+        shifter.set_pin_high(sr0, 1, Apply::Now).unwrap();
+        assert_eq!(mock.apply_count(), 0);
+        let due = shifter.next_apply_due().unwrap();
+        assert!(!shifter.tick().unwrap());
+        std::thread::sleep(due.saturating_duration_since(Instant::now()));
+        assert!(shifter.tick().unwrap());
+        assert_eq!(mock.apply_count(), 1);
+        assert_eq!(mock.outputs(), vec![true, true, false, false]);
+        assert_eq!(shifter.next_apply_due(), None);
+        shifter.set_pin_high(sr0, 2, Apply::Now).unwrap();
+        shifter.apply().unwrap(); // Never held back
+        assert_eq!(mock.apply_count(), 2);
+        assert_eq!(shifter.next_apply_due(), None);
+    }
+
+    #[test]
+    fn rate_limited_applies() {
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(4);
+        assert!(shifter.set_max_apply_rate(0.0).is_err());
+        assert!(matches!(shifter.set_max_apply_rate(1e-300), Err(ShifterError::InvalidRate(_))));
+        shifter.set_max_apply_rate(f64::INFINITY).unwrap();
+        assert_eq!(shifter.max_apply_rate(), f64::INFINITY);
+        shifter.set_max_apply_rate(50.0).unwrap();
+        assert!((shifter.max_apply_rate() - 50.0).abs() < 1e-6);
+        shifter.set_pin_high(sr0, 0, Apply::Now).unwrap();
+        assert_eq!(mock.apply_count(), 1); // Nothing to wait for yet
+        shifter.set_pin_high(sr0, 1, Apply::Now).unwrap();
+        shifter.set_pin_high(sr0, 2, Apply::Now).unwrap();
+        assert_eq!(mock.apply_count(), 1);
+        let due = shifter.next_apply_due().unwrap();
+        std::thread::sleep(due.saturating_duration_since(Instant::now()));
+        assert!(shifter.tick().unwrap());
+        assert_eq!(mock.apply_count(), 2);
+        assert_eq!(mock.outputs(), vec![true, true, true, false]);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_shifter() {
+        use std::future::Future;
+        use std::pin::Pin;
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake};
+        use std::thread::{self, Thread};
+
+        // Just enough of an executor to wait for a single future
+        struct Unpark(Thread);
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) { self.0.unpark(); }
+        }
+        fn block_on<F: Future>(mut future: F) -> F::Output {
+            let waker = Arc::new(Unpark(thread::current())).into();
+            let mut cx = Context::from_waker(&waker);
+            let mut future = unsafe { Pin::new_unchecked(&mut future) };
+            loop {
+                match future.as_mut().poll(&mut cx) {
+                    Poll::Ready(output) => return output,
+                    Poll::Pending => thread::park(),
+                }
+            }
+        }
+
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(4);
+        let shifter = shifter.into_async().unwrap();
+        let set = shifter.set_pin_high(sr0, 1, Apply::Later);
+        let stats = shifter.apply();
+        assert_eq!(block_on(stats).unwrap().bits, 4);
+        block_on(set).unwrap();
+        assert!(block_on(shifter.get_pin(sr0, 1)).unwrap());
+        assert!(block_on(shifter.set_pin_high(sr0, 4, Apply::Now)).is_err());
+        assert_eq!(mock.outputs(), vec![false, true, false, false]);
+    }
+
+    #[test]
+    fn software_pwm() {
+        let (mut shifter, _) = Shifter::mock();
+        let sr0 = shifter.add(4);
+        shifter.set_pwm_steps(4);
+        assert!(shifter.set_brightness(sr0, 0, 1.5).is_err());
+        assert!(shifter.set_brightness(sr0, 4, 0.5).is_err());
+        shifter.set_brightness(sr0, 0, 0.25).unwrap();
+        shifter.set_brightness(sr0, 1, 0.75).unwrap();
+        shifter.set_brightness(sr0, 2, 1.0).unwrap();
+        assert_eq!(shifter.brightness(sr0, 0).unwrap(), 0.25);
+        assert_eq!(shifter.brightness(sr0, 2).unwrap(), 1.0);
+        assert_eq!(shifter.brightness(sr0, 3).unwrap(), 0.0);
+        let mut on = [0, 0, 0];
+        for _ in 0..8 {
+            assert_eq!(shifter.dimming_step().unwrap(), 1);
+            for (pin, count) in on.iter_mut().enumerate() {
+                if shifter.get_pin(sr0, pin as u8).unwrap() { *count += 1; }
+            }
+        }
+        assert_eq!(on, [2, 6, 8]);
+    }
+
+    #[test]
+    fn bit_angle_modulation() {
+        let (mut shifter, _) = Shifter::mock();
+        let sr0 = shifter.add(2);
+        shifter.set_pwm_steps(8); // 3 bits (levels 0 to 7)
+        shifter.set_dimming_mode(DimmingMode::Bam);
+        assert_eq!(shifter.dimming_mode(), DimmingMode::Bam);
+        shifter.set_brightness(sr0, 0, 0.625).unwrap(); // 5 of 8 steps -> 0b100
+        shifter.set_brightness(sr0, 1, 0.375).unwrap(); // 3 of 8 steps -> 0b011
+        let mut on = [0, 0];
+        let mut total = 0;
+        for _ in 0..3 {
+            let periods = shifter.dimming_step().unwrap();
+            total += periods;
+            for (pin, time) in on.iter_mut().enumerate() {
+                if shifter.get_pin(sr0, pin as u8).unwrap() { *time += periods; }
+            }
+        }
+        assert_eq!(total, 7);
+        assert_eq!(on, [4, 3]);
+    }
+
+    #[test]
+    fn global_brightness() {
+        struct Pwm(Arc<Mutex<f64>>);
+        impl PwmPin for Pwm {
+            fn set_duty_cycle(&mut self, duty: f64) -> Result<(), ShifterError> {
+                *self.0.lock().unwrap() = duty;
+                Ok(())
+            }
+        }
+        let (mut shifter, _) = Shifter::mock();
+        assert!(shifter.set_global_brightness(128).is_err());
+        let duty = Arc::new(Mutex::new(-1.0));
+        shifter.set_output_enable_pwm(Pwm(duty.clone()), Polarity::ActiveLow).unwrap();
+        assert_eq!(*duty.lock().unwrap(), 1.0); // Disabled
+        shifter.set_global_brightness(51).unwrap();
+        assert_eq!(shifter.global_brightness(), 51);
+        assert_eq!(*duty.lock().unwrap(), 1.0); // Still disabled
+        shifter.enable_outputs().unwrap();
+        assert!((*duty.lock().unwrap() - 0.8).abs() < 1e-9);
+        // Without hardware PWM the refresh thread toggles the pin instead:
+        let level = Arc::new(AtomicBool::new(false));
+        shifter.set_output_enable_pin(DataPin(level.clone()), Polarity::ActiveHigh).unwrap();
+        shifter.enable_outputs().unwrap();
+        shifter.set_pwm_steps(5);
+        let mut on = 0;
+        for _ in 0..10 {
+            shifter.dimming_step().unwrap();
+            if level.load(Ordering::SeqCst) { on += 1; }
+        }
+        assert_eq!(on, 2);
+        shifter.set_global_brightness(255).unwrap();
+        assert!(level.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn gamma_correction() {
+        let (mut shifter, _) = Shifter::mock();
+        let sr0 = shifter.add(2);
+        assert!(shifter.set_gamma(0.0).is_err());
+        shifter.set_pwm_steps(10);
+        shifter.set_gamma(2.0).unwrap();
+        assert_eq!(shifter.gamma(), 2.0);
+        shifter.set_brightness(sr0, 0, 0.5).unwrap(); // Really 0.25 -> 3 of 10 steps (rounded)
+        shifter.set_brightness(sr0, 1, 0.9).unwrap(); // Really 0.81 -> 8 of 10 steps
+        assert_eq!(shifter.brightness(sr0, 0).unwrap(), 0.5);
+        let mut on = [0, 0];
+        for _ in 0..10 {
+            shifter.dimming_step().unwrap();
+            for (pin, count) in on.iter_mut().enumerate() {
+                if shifter.get_pin(sr0, pin as u8).unwrap() { *count += 1; }
+            }
+        }
+        assert_eq!(on, [3, 8]);
+    }
+
+    #[test]
+    fn double_buffering() {
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(4);
+        let sr1 = shifter.add(4);
+        shifter.set(sr0, 0b0001, Apply::Now).unwrap();
+        {
+            let back = shifter.back_buffer();
+            assert_eq!(back.get(sr0).unwrap(), 0b0001); // Starts out as a copy
+            back.set(sr0, 0b1100).unwrap();
+            back.set_pin(sr1, 3, true).unwrap();
+            back.set_chain_pin(4, true).unwrap();
+            assert!(back.set_pin(sr1, 4, true).is_err());
+            assert!(back.set_chain_pin(8, true).is_err());
+        }
+        assert_eq!(shifter.get(sr0).unwrap(), 0b0001); // Front buffer untouched
+        shifter.swap_buffers(Apply::Now).unwrap();
+        assert_eq!(mock.outputs(), vec![false, false, true, true, true, false, false, true]);
+        assert_eq!(shifter.back_buffer().get(sr0).unwrap(), 0b0001); // The old front buffer
+        let frame = shifter.frame();
+        let sr2 = shifter.add(4);
+        assert!(shifter.set_frame(&frame, Apply::Now).is_err());
+        assert_eq!(shifter.back_buffer().total_pins(), 12); // Recreated for the new layout
+        assert_eq!(shifter.back_buffer().get(sr2).unwrap(), 0);
+    }
+
+    #[test]
+    fn frame_loop() {
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(4);
+        assert!(FrameLoop::new(0.0).is_err());
+        assert!(matches!(FrameLoop::new(1e-300), Err(ShifterError::InvalidRate(_))));
+        let mut frames = FrameLoop::new(200.0).unwrap().limit(6);
+        assert_eq!(frames.fps(), 200.0);
+        let start = Instant::now();
+        let mut count = 0;
+        frames.run(&mut shifter, |frame| {
+            frame.toggle_pin(sr0, 0).unwrap(); // Builds on the previous frame
+            count += 1;
+        }).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(25)); // 5 periods between 6 frames
+        assert_eq!((count, frames.frames()), (6, 6));
+        assert_eq!(mock.apply_count(), 6);
+        assert_eq!(shifter.get(sr0).unwrap(), 0); // Toggled an even number of times
+        let mut frames = FrameLoop::new(1000.0).unwrap();
+        let handle = frames.handle();
+        frames.run(&mut shifter, |_| handle.stop()).unwrap();
+        assert_eq!(frames.frames(), 1);
+    }
+
+    #[test]
+    fn animations() {
+        let (mut shifter, _) = Shifter::mock();
+        let sr0 = shifter.add(4);
+        shifter.define_group("ring", &[(sr0, 0), (sr0, 1), (sr0, 2)]).unwrap();
+        let fade = Animation::fade(Target::Pin(sr0, 3), 0.0, 1.0, Duration::from_millis(40));
+        assert_eq!(fade.duration(), Some(Duration::from_millis(40)));
+        shifter.add_animation("fade", fade).unwrap();
+        let chase = Animation::procedural(Target::Group("ring".to_string()), |_, i| if i == 1 { 1.0 } else { 0.0 });
+        shifter.add_animation("chase", chase).unwrap();
+        assert!(shifter.add_animation("bad", Animation::fade(Target::Named("nope".to_string()), 0.0, 1.0, Duration::from_millis(1))).is_err());
+        assert!(shifter.start_animation("nope").is_err());
+        assert!(matches!(shifter.set_animation_fps(0.0), Err(ShifterError::InvalidRate(_))));
+        assert!(matches!(shifter.set_animation_fps(1e-300), Err(ShifterError::InvalidRate(_))));
+        assert!(!shifter.tick().unwrap()); // Nothing running
+        shifter.start_animation("chase").unwrap();
+        shifter.start_animation("fade").unwrap();
+        assert!(shifter.tick().unwrap());
+        assert_eq!(shifter.get(sr0).unwrap(), 0b0010);
+        assert!(shifter.brightness(sr0, 3).unwrap() < 0.5);
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(shifter.tick().unwrap());
+        assert!(!shifter.is_animating("fade")); // Finished at full brightness
+        assert!(shifter.is_animating("chase"));
+        assert_eq!(shifter.get(sr0).unwrap(), 0b1010);
+        shifter.stop_animation("chase").unwrap();
+        assert!(shifter.next_animation_due().is_none());
+        assert!(shifter.remove_animation("chase").is_some());
+    }
+
+    #[test]
+    fn eased_fade() {
+        let (mut shifter, _) = Shifter::mock();
+        let sr0 = shifter.add(1);
+        let fade = Animation::fade(Target::Pin(sr0, 0), 0.0, 1.0, Duration::from_secs(10)).easing(Easing::CubicIn);
+        shifter.add_animation("fade", fade).unwrap();
+        shifter.start_animation("fade").unwrap();
+        shifter.tick().unwrap();
+        assert_eq!(shifter.brightness(sr0, 0).unwrap(), 0.0); // Barely started
+    }
+
+    #[test]
+    fn sequences() {
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(4);
+        let sequence = Sequence::new()
+            .frame(&[0b0101], Duration::from_millis(20))
+            .step(vec![
+                Command::AllLow(Apply::Now), // Doesn't apply on its own
+                Command::SetPin { sr: sr0, pin: 3, state: true, apply: Apply::Now },
+            ], Duration::from_millis(20))
+            .repeat(Repeat::Times(2));
+        assert_eq!(sequence.duration(), Duration::from_millis(40));
+        shifter.play(sequence).unwrap();
+        assert!(shifter.is_playing());
+        assert_eq!(shifter.get(sr0).unwrap(), 0b0101);
+        assert_eq!(mock.apply_count(), 1);
+        assert!(!shifter.tick().unwrap()); // Not due yet
+        std::thread::sleep(Duration::from_millis(25));
+        assert!(shifter.tick().unwrap());
+        assert_eq!(shifter.get(sr0).unwrap(), 0b1000);
+        assert_eq!(mock.apply_count(), 2);
+        std::thread::sleep(Duration::from_millis(80));
+        assert!(shifter.tick().unwrap()); // Both remaining steps at once
+        assert!(!shifter.is_playing());
+        assert_eq!(shifter.get(sr0).unwrap(), 0b1000);
+        shifter.play(Sequence::new().frame(&[0b0001], Duration::from_secs(1)).repeat(Repeat::Forever)).unwrap();
+        shifter.stop();
+        assert!(!shifter.is_playing());
+        assert!(shifter.play(Sequence::new().frame(&[0, 0], Duration::from_secs(1))).is_err());
+        assert!(!shifter.is_playing());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn sequence_files() {
+        let json = r#"{
+            "repeat": "forever",
+            "steps": [
+                { "chain": [5], "duration_ms": 500 },
+                { "all": false, "pins": { "porch": true }, "groups": { "pair": true }, "duration_ms": 250 }
+            ]
+        }"#;
+        let sequence = Sequence::from_json(json).unwrap();
+        assert_eq!(sequence.steps().len(), 2);
+        assert_eq!(sequence.duration(), Duration::from_millis(750));
+        assert_eq!(sequence.steps()[1].commands.len(), 3);
+        assert!(Sequence::from_json(r#"{ "steps": [{ "chain": [1] }] }"#).is_err()); // No duration
+        assert!(Sequence::from_json(r#"{ "repeat": "often", "steps": [] }"#).is_err());
+        assert!(Sequence::from_json(r#"{ "steps": [{ "duration_ms": 1, "bogus": 1 }] }"#).is_err());
+        let (mut shifter, _) = Shifter::mock();
+        let sr0 = shifter.add(4);
+        shifter.name_pin(sr0, 3, "porch").unwrap();
+        shifter.define_group("pair", &[(sr0, 0), (sr0, 1)]).unwrap();
+        shifter.play(sequence).unwrap();
+        assert_eq!(shifter.get(sr0).unwrap(), 0b0101);
+        let path = std::env::temp_dir().join(format!("cupi_shift_sequence_{}.json", std::process::id()));
+        std::fs::write(&path, r#"{ "steps": [{ "all": true, "duration_ms": 0 }] }"#).unwrap();
+        shifter.play_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(shifter.get(sr0).unwrap(), 0b1111);
+        assert!(!shifter.is_playing());
+    }
+
+    #[test]
+    fn schedules() {
+        assert_eq!("17:30".parse::<TimeOfDay>().unwrap(), TimeOfDay::new(17, 30, 0).unwrap());
+        assert_eq!("07:05:09".parse::<TimeOfDay>().unwrap().to_string(), "07:05:09");
+        assert!("24:00".parse::<TimeOfDay>().is_err());
+        assert!("noon".parse::<TimeOfDay>().is_err());
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(4);
+        let now = SystemTime::now();
+        let on = |pin| Command::SetPin { sr: sr0, pin: pin, state: true, apply: Apply::Now };
+        let schedule = Schedule::new()
+            .once(now - Duration::from_secs(60), on(0)) // Caught up
+            .once(now - Duration::from_secs(2 * 86400), on(1)) // Too long ago
+            .once(now + Duration::from_millis(1500), on(2))
+            .at(TimeOfDay::new(0, 0, 0).unwrap(), on(3)); // Sometime in the last 24 hours
+        shifter.set_schedule(schedule).unwrap();
+        assert_eq!(shifter.get(sr0).unwrap(), 0b1001);
+        assert_eq!(mock.apply_count(), 1);
+        assert!(shifter.next_wakeup().unwrap() <= Instant::now() + Duration::from_millis(1500));
+        assert!(!shifter.tick().unwrap());
+        std::thread::sleep(Duration::from_millis(1600));
+        assert!(shifter.tick().unwrap());
+        assert_eq!(shifter.get(sr0).unwrap(), 0b1101);
+        let next = shifter.schedule().unwrap().next_due().unwrap(); // Midnight UTC
+        assert_eq!(next.duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() % 86400, 0);
+        assert!(shifter.clear_schedule().is_some());
+        let schedule = Schedule::new().catch_up(false).once(now - Duration::from_secs(60), on(1));
+        shifter.set_schedule(schedule).unwrap();
+        assert!(!shifter.get_pin(sr0, 1).unwrap());
+    }
+
+    #[test]
+    fn recurrences() {
+        let at = |days: i64, h: i64, m: i64| days * 86400 + h * 3600 + m * 60;
+        // 2024-01-01 (19723 days after the epoch) was a Monday
+        assert_eq!(recurrence::civil_from_days(19723), (2024, 1, 1));
+        assert_eq!(recurrence::civil_from_days(19723 + 59), (2024, 2, 29));
+        let weekdays: Recurrence = "30 6 * * mon-fri".parse().unwrap();
+        assert_eq!(weekdays.next_after_local(at(19723, 7, 0)), Some(at(19724, 6, 30)));
+        assert_eq!(weekdays.next_after_local(at(19727, 7, 0)), Some(at(19730, 6, 30))); // Friday -> Monday
+        let built = Recurrence::daily(TimeOfDay::new(6, 30, 0).unwrap())
+            .on(&[Weekday::Monday, Weekday::Tuesday, Weekday::Wednesday, Weekday::Thursday, Weekday::Friday]);
+        assert_eq!(built, weekdays);
+        let quarterly: Recurrence = "*/15 8-9 1,15 * *".parse().unwrap();
+        assert_eq!(quarterly.next_after_local(at(19723, 9, 50)), Some(at(19737, 8, 0))); // The 15th
+        let leap: Recurrence = "0 0 29 feb *".parse().unwrap();
+        assert_eq!(leap.next_after_local(at(19723 + 60, 0, 0)), Some(at(19723 + 59 + 1461, 0, 0))); // 2028
+        assert!("0 0 30 2 *".parse::<Recurrence>().unwrap().next_after_local(0).is_none());
+        let either: Recurrence = "0 12 1 * sun".parse().unwrap(); // The 1st or any Sunday
+        assert_eq!(either.next_after_local(at(19723, 13, 0)), Some(at(19729, 12, 0)));
+        assert_eq!("0 0 * * 7".parse::<Recurrence>().unwrap(), "0 0 * * 0".parse().unwrap());
+        for bad in ["", "* * * *", "60 * * * *", "* * * * 8", "*/0 * * * *", "5-1 * * * *", "* * * foo *"].iter() {
+            assert!(bad.parse::<Recurrence>().is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn sun_events() {
+        let day = 19723 + 172; // 2024-06-21
+        let london = (51.5074, -0.1278);
+        let at = |event, after| sun::next_sun_event(event, london.0, london.1, after).unwrap();
+        let close = |time: i64, h: i64, m: i64| (time - (day * 86400 + h * 3600 + m * 60)).abs() <= 120;
+        assert!(close(at(SunEvent::Sunrise, day * 86400), 3, 43));
+        assert!(close(at(SunEvent::Sunset, day * 86400), 20, 21));
+        assert!(at(SunEvent::CivilDawn, day * 86400) < at(SunEvent::Sunrise, day * 86400));
+        // Already risen today so the next sunrise is tomorrow's:
+        assert!(at(SunEvent::Sunrise, day * 86400 + 6 * 3600) - at(SunEvent::Sunrise, day * 86400) > 86000);
+        // No sunrise in Tromsø until the polar night ends in mid January:
+        let december = 19723 + 335; // 2024-12-01
+        let sunrise = sun::next_sun_event(SunEvent::Sunrise, 69.65, 18.96, december * 86400).unwrap();
+        assert!(sunrise > (december + 40) * 86400 && sunrise < (december + 50) * 86400);
+
+        let command = Command::AllHigh(Apply::Now);
+        let (mut shifter, _) = Shifter::mock();
+        shifter.add(8);
+        let schedule = Schedule::new().at_sun(SunEvent::CivilDusk, -30, command.clone());
+        assert!(matches!(shifter.set_schedule(schedule.clone()), Err(ShifterError::InvalidLocation(_))));
+        assert!(matches!(shifter.set_schedule(schedule.clone().location(91.0, 0.0)), Err(ShifterError::InvalidLocation(_))));
+        shifter.set_schedule(schedule.location(london.0, london.1).catch_up(false)).unwrap();
+        let next = schedule::unix_time(shifter.schedule().unwrap().next_due().unwrap());
+        let dusk = sun::next_sun_event(SunEvent::CivilDusk, london.0, london.1, next + 30 * 60 - 1).unwrap();
+        assert_eq!(next, dusk - 30 * 60);
+    }
+
+    #[test]
+    fn drop_policies() {
+        let (mut shifter, mock) = Shifter::mock();
+        shifter.add(8);
+        shifter.set_chain(&[0xFF], Apply::Now).unwrap();
+        drop(shifter); // DropPolicy::Leave
+        assert_eq!(mock.apply_count(), 1);
+        assert_eq!(mock.outputs(), vec![true; 8]);
+
+        let (mut shifter, mock) = Shifter::mock();
+        shifter.add(8);
+        shifter.set_chain(&[0xFF], Apply::Now).unwrap();
+        shifter.set_drop_policy(DropPolicy::AllLow);
+        assert_eq!(shifter.drop_policy(), &DropPolicy::AllLow);
+        drop(shifter);
+        assert_eq!(mock.apply_count(), 2);
+        assert_eq!(mock.outputs(), vec![false; 8]);
+
+        let (mut shifter, mock) = Shifter::mock();
+        shifter.add(8);
+        shifter.set_drop_policy(DropPolicy::Chain(vec![0b00001111]));
+        drop(shifter);
+        assert_eq!(mock.outputs().iter().filter(|&&on| on).count(), 4);
+
+        let (mut shifter, mock) = Shifter::mock();
+        shifter.add(8);
+        shifter.set_drop_policy(DropPolicy::Chain(vec![1, 2])); // Wrong length
+        drop(shifter);
+        assert_eq!(mock.apply_count(), 0);
+    }
+
+    #[test]
+    fn panic_failsafe() {
+        // A Shifter owned by the thread that panics:
+        let (mut shifter, mock) = Shifter::mock();
+        shifter.add(8);
+        shifter.set_chain(&[0xFF], Apply::Now).unwrap();
+        shifter.set_failsafe(DropPolicy::Chain(vec![0b00000001]));
+        assert!(std::thread::spawn(move || {
+            let _shifter = shifter;
+            panic!("Mid-animation");
+        }).join().is_err());
+        assert_eq!(mock.apply_count(), 2);
+        assert_eq!(mock.outputs().iter().filter(|&&on| on).count(), 1);
+
+        // A SharedShifter:
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(8);
+        shifter.set_chain(&[0xFF], Apply::Now).unwrap();
+        shifter.set_brightness(sr0, 0, 0.5).unwrap();
+        let shifter = shifter.into_shared();
+        let animator = shifter.clone();
+        std::thread::spawn(move || {
+            let _failsafe = animator.failsafe(DropPolicy::AllLow);
+        }).join().unwrap(); // No panic, no change
+        assert_eq!(mock.apply_count(), 1);
+        let animator = shifter.clone();
+        assert!(std::thread::spawn(move || {
+            let _failsafe = animator.failsafe(DropPolicy::AllLow);
+            let _lock = animator.lock(); // Poisons the Mutex
+            panic!("Mid-animation");
+        }).join().is_err());
+        assert_eq!(mock.apply_count(), 2);
+        assert_eq!(mock.outputs(), vec![false; 8]);
+        assert_eq!(shifter.lock().brightness(sr0, 0).unwrap(), 0.0);
+    }
+
+    #[cfg(feature = "signals")]
+    #[test]
+    fn signal_shutdown() {
+        use super::SignalShutdown;
+        let (mut shifter, mock) = Shifter::mock();
+        shifter.add(8);
+        let oe = Arc::new(AtomicBool::new(false));
+        shifter.set_output_enable_pin(DataPin(oe.clone()), Polarity::ActiveLow).unwrap();
+        shifter.enable_outputs().unwrap();
+        shifter.set_chain(&[0xFF], Apply::Now).unwrap();
+        let shifter = shifter.into_shared();
+        SignalShutdown::new(DropPolicy::AllLow).install(&shifter).unwrap();
+        SignalShutdown::new(DropPolicy::AllLow).disable_outputs(true).shut_down(&shifter);
+        assert_eq!(mock.outputs(), vec![false; 8]);
+        assert!(!shifter.lock().outputs_enabled());
+        assert!(oe.load(Ordering::SeqCst)); // Active-low so HIGH is disabled
+    }
+
+    #[test]
+    fn watchdog() {
+        let (mut shifter, mock) = Shifter::mock();
+        let sr0 = shifter.add(8);
+        shifter.set_watchdog(Duration::from_millis(50));
+        assert_eq!(shifter.watchdog(), Duration::from_millis(50));
+        assert!(shifter.next_watchdog_due().is_none()); // Nothing applied yet
+        assert!(!shifter.tick().unwrap());
+        shifter.set(sr0, 0b10100101, Apply::Now).unwrap();
+        assert!(shifter.next_wakeup().unwrap() <= Instant::now() + Duration::from_millis(50));
+        assert!(!shifter.tick().unwrap());
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(shifter.tick().unwrap());
+        assert_eq!(mock.apply_count(), 2);
+        assert_eq!(mock.frames()[0], mock.frames()[1]);
+        assert!(!shifter.tick().unwrap()); // Not due again yet
+        shifter.set_watchdog(Duration::ZERO);
+        assert!(shifter.next_watchdog_due().is_none());
+    }
+
+    #[cfg(feature = "realtime")]
+    #[test]
+    fn realtime_refresh() {
+        let (mut shifter, _) = Shifter::mock();
+        shifter.add(8);
+        let refresh = shifter.start_refresh(100.0).unwrap();
+        assert!(matches!(refresh.set_realtime_priority(0), Err(ShifterError::InvalidPriority(0))));
+        assert!(matches!(refresh.set_realtime_priority(100), Err(ShifterError::InvalidPriority(100))));
+        // Whether this is allowed depends on who's running the tests:
+        assert!(refresh.set_realtime_priority(1).is_ok());
+        assert!(refresh.is_running());
+    }
+
+    #[test]
+    fn input_shift_registers() {
+        let (mut inputs, mock) = ShifterIn::mock();
+        let sr0 = inputs.add(8);
+        let sr1 = inputs.add(8);
+        assert_eq!(inputs.total_pins(), 16);
+        inputs.name_pin(sr1, 7, "start").unwrap();
+        mock.set_input(0, true);
+        mock.set_input(2, true);
+        mock.set_input(15, true);
+        assert_eq!(inputs.get(sr0).unwrap(), 0); // Nothing read yet
+        inputs.read().unwrap();
+        assert_eq!(inputs.get(sr0).unwrap(), 0b00000101);
+        assert_eq!(inputs.get(sr1).unwrap(), 0b10000000);
+        assert_eq!(inputs.get_chain(), vec![0b00000101, 0b10000000]);
+        assert!(inputs.get_chain_pin(15).unwrap());
+        assert!(inputs.get_pin(sr0, 2).unwrap());
+        assert!(inputs.get_named("start").unwrap());
+        assert!(inputs.get_named("stop").is_err());
+        assert!(inputs.get_pin(sr0, 8).is_err());
+        let (mut others, _) = ShifterIn::mock();
+        assert!(others.get(sr0).is_err()); // Handles don't work across chains
+        others.add(8);
+        assert!(others.get(sr0).is_err());
+
+        inputs.invert(); // Buttons that pull their inputs LOW
+        inputs.set_invert(sr1, true).unwrap(); // ...but not these
+        inputs.read().unwrap();
+        assert_eq!(inputs.get(sr0).unwrap(), 0b11111010);
+        assert_eq!(inputs.get(sr1).unwrap(), 0b10000000);
+        inputs.invert();
+        inputs.set_invert(sr1, false).unwrap();
+
+        mock.set_inputs(&[false; 16]);
+        mock.set_input(8, true);
+        inputs.set_order(Order::FirstToLast); // sr0 is nearest the Raspberry Pi
+        inputs.read().unwrap();
+        assert_eq!(inputs.get(sr0).unwrap(), 0b00000001);
+        assert_eq!(inputs.get(sr1).unwrap(), 0);
+    }
+
+    #[test]
+    fn mixed_chains() {
+        let (mut shifter, mock, mock_inputs) = Shifter::mock_with_inputs();
+        let leds = shifter.add(8);
+        let buttons = shifter.inputs_mut().unwrap().add(8);
+        shifter.inputs_mut().unwrap().name_pin(buttons, 3, "start").unwrap();
+        mock_inputs.set_inputs(&[false, false, false, true, false, false, true, false]);
+        shifter.set(leds, 0b00000101, Apply::Now).unwrap();
+        assert_eq!(shifter.inputs().unwrap().get(buttons).unwrap(), 0b01001000);
+        assert!(shifter.inputs().unwrap().get_named("start").unwrap());
+        assert_eq!(mock.outputs(), vec![true, false, true, false, false, false, false, false]);
+        assert_eq!(mock.apply_count(), 1);
+        // The inputs can't be read on their own since they don't own the clock
+        assert!(shifter.inputs_mut().unwrap().read().is_err());
+
+        mock_inputs.set_input(3, false);
+        shifter.set_clock_mode(ClockMode::Mode0);
+        assert_eq!(shifter.read_inputs().unwrap().bits, 8);
+        assert_eq!(shifter.inputs().unwrap().get(buttons).unwrap(), 0b01000000);
+        assert_eq!(mock.outputs(), vec![true, false, true, false, false, false, false, false]);
+
+        // More inputs than outputs:  The extra bits fall off the far end
+        let switches = shifter.inputs_mut().unwrap().add(8);
+        mock_inputs.set_input(15, true); // (Switches are nearer the Raspberry Pi)
+        assert_eq!(shifter.read_inputs().unwrap().bits, 16);
+        assert_eq!(shifter.inputs().unwrap().get(buttons).unwrap(), 0b01000000);
+        assert_eq!(shifter.inputs().unwrap().get(switches).unwrap(), 0b10000000);
+        assert_eq!(mock.outputs()[8..], [true, false, true, false, false, false, false, false]);
+
+        shifter.set_protocol(leds, Hc595).unwrap(); // Inputs can only be read by bit-banging
+        match shifter.apply() {
+            Err(ShifterError::Unsupported(_)) => {},
+            other => panic!("Expected Unsupported, got {:?}", other),
+        }
+
+        let (mut plain, _) = Shifter::mock();
+        plain.add(8);
+        match plain.read_inputs() {
+            Err(ShifterError::MissingPin(_)) => {},
+            other => panic!("Expected MissingPin, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn input_polling() {
+        let (mut inputs, mock) = ShifterIn::mock();
+        let sr0 = inputs.add(8);
+        mock.set_inputs(&[false; 8]);
+        mock.set_input(1, true);
+        inputs.read().unwrap(); // Already HIGH when polling starts so no event
+        assert!(ShifterIn::mock().0.start_polling(0.0).is_err());
+        assert!(matches!(ShifterIn::mock().0.start_polling(1e-300), Err(ShifterError::InvalidRate(_))));
+        let (poller, events) = inputs.start_polling(1000.0).unwrap();
+        mock.set_input(5, true);
+        let event = events.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!((event.register, event.pin, event.change), (sr0, 5, InputChange::Pressed));
+        mock.set_input(1, false);
+        let event = events.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!((event.register, event.pin, event.change), (sr0, 1, InputChange::Released));
+        assert!(events.recv_timeout(Duration::from_millis(20)).is_err());
+        assert_eq!(poller.lock().get(sr0).unwrap(), 0b00100000);
+        assert!(poller.is_running());
+        let inputs = poller.stop();
+        assert_eq!(inputs.get(sr0).unwrap(), 0b00100000);
+        assert!(events.recv().is_err()); // No more events once it's stopped
+    }
+
+    #[test]
+    fn debouncing() {
+        let (mut inputs, mock) = ShifterIn::mock();
+        let sr0 = inputs.add(8);
+        mock.set_inputs(&[false; 8]);
+        inputs.set_debounce(Debounce::Samples(5));
+        inputs.set_pin_debounce(sr0, 2, Debounce::Off).unwrap();
+        assert!(inputs.set_pin_debounce(sr0, 8, Debounce::Off).is_err());
+        assert_eq!(inputs.debounce(sr0, 0).unwrap(), Debounce::Samples(5));
+        assert_eq!(inputs.debounce(sr0, 2).unwrap(), Debounce::Off);
+        let (poller, events) = inputs.start_polling(1000.0).unwrap();
+        mock.set_input(2, true);
+        mock.set_input(4, true);
+        let first = events.recv_timeout(Duration::from_secs(1)).unwrap();
+        let second = events.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!((first.pin, second.pin), (2, 4)); // Pin 4 took a few more reads
+        assert!(second.at > first.at);
+        poller.stop();
+    }
+
+    #[test]
+    fn button_gestures() {
+        let (mut inputs, mock) = ShifterIn::mock();
+        let sr0 = inputs.add(8);
+        mock.set_inputs(&[false; 8]);
+        inputs.set_gestures(Gestures { hold: Some(Duration::from_millis(200)), double_click: None });
+        inputs.set_pin_gestures(sr0, 1, Gestures::default()).unwrap();
+        assert_eq!(inputs.gestures(sr0, 1).unwrap(), Gestures::default());
+        assert_eq!(inputs.gestures(sr0, 0).unwrap().hold, Some(Duration::from_millis(200)));
+        let (poller, events) = inputs.start_polling(1000.0).unwrap();
+        mock.set_input(0, true);
+        let next = || events.recv_timeout(Duration::from_secs(1)).unwrap().change;
+        assert_eq!(next(), InputChange::Pressed);
+        assert_eq!(next(), InputChange::Held);
+        mock.set_input(0, false);
+        assert_eq!(next(), InputChange::Released);
+        mock.set_input(0, true);
+        assert_eq!(next(), InputChange::Pressed);
+        mock.set_input(0, false); // Well before the hold time
+        assert_eq!(next(), InputChange::Released);
+        assert_eq!(next(), InputChange::Clicked);
+        mock.set_input(1, true);
+        assert_eq!(next(), InputChange::Pressed);
+        mock.set_input(1, false);
+        assert_eq!(next(), InputChange::Released);
+        assert!(events.recv_timeout(Duration::from_millis(20)).is_err()); // No gestures for pin 1
+        poller.stop();
+    }
+
+    #[test]
+    fn keypad_matrix() {
+        let (mut shifter, mock, mock_inputs) = Shifter::mock_with_inputs();
+        let rows = shifter.add(8);
+        let columns = shifter.inputs_mut().unwrap().add(8);
+        shifter.set_pin_high(rows, 7, Apply::Later).unwrap(); // Not a row; left alone
+        let mut keypad = Keypad::new(
+            (0..3).map(|pin| (rows, pin)).collect(),
+            (0..2).map(|pin| (columns, pin)).collect(),
+        );
+        assert_eq!((keypad.rows(), keypad.columns()), (3, 2));
+        let (mut plain, _) = Shifter::mock();
+        assert!(keypad.scan(&mut plain).is_err());
+
+        // The mock's inputs don't follow the rows so column 1 reads as
+        // pressed whichever row is selected
+        mock_inputs.set_inputs(&[false, true, false, false, false, false, false, false]);
+        mock.clear_frames();
+        let events = keypad.scan(&mut shifter).unwrap();
+        let frames = mock.frames();
+        assert_eq!(frames.len(), 4); // One per row plus one to deselect them
+        for (i, frame) in frames.iter().enumerate() {
+            assert_eq!(&frame[..3], &[i == 0, i == 1, i == 2][..]);
+            assert!(frame[7]);
+        }
+        let keys: Vec<(usize, usize, InputChange)> = events.iter().map(|e| (e.row, e.column, e.change)).collect();
+        assert_eq!(keys, vec![(0, 1, InputChange::Pressed), (1, 1, InputChange::Pressed), (2, 1, InputChange::Pressed)]);
+        assert!(keypad.is_pressed(2, 1));
+        assert!(!keypad.is_pressed(2, 0));
+        assert!(!keypad.is_pressed(3, 1));
+        assert!(keypad.scan(&mut shifter).unwrap().is_empty());
+
+        // Both columns on every row looks like ghosting so nothing changes...
+        mock_inputs.set_input(0, true);
+        assert!(keypad.scan(&mut shifter).unwrap().is_empty());
+        assert_eq!(keypad.pressed(), vec![(0, 1), (1, 1), (2, 1)]);
+        // ...unless the keys have diodes
+        keypad.set_diodes(true);
+        assert_eq!(keypad.scan(&mut shifter).unwrap().len(), 3);
+
+        keypad.set_diodes(false);
+        keypad.set_active_low(true); // Everything reads as released now
+        keypad.set_debounce(Debounce::Samples(2));
+        assert!(keypad.scan(&mut shifter).unwrap().is_empty());
+        assert_eq!(keypad.scan(&mut shifter).unwrap().len(), 6);
+        assert_eq!(&mock.outputs()[..3], &[true, true, true]); // Deselected rows are HIGH
     }
 }
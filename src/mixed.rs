@@ -0,0 +1,70 @@
+//! Chains that mix output shift registers (e.g. 74HC595s) with input shift
+//! registers (e.g. 74HC165s) on the same clock line.  See
+//! `Shifter.attach_inputs()`.
+
+use {Shifter, ShifterIn, ShifterError, ApplyStats, InputPin, OutputPin};
+
+impl Shifter {
+
+    /// Attaches a chain of input shift registers (e.g. 74HC165s) that shares
+    /// this `Shifter`'s clock pin and returns it so its shift registers can
+    /// be added (see `ShifterIn`):
+    ///
+    /// ```
+    /// let leds = shifter.add(8);
+    /// let inputs = shifter.attach_inputs(qh_pin, load_pin);
+    /// let buttons = inputs.add(8);
+    /// inputs.name_pin(buttons, 0, "start")?;
+    /// shifter.set(leds, 0b00000001, Apply::Now)?; // Reads the buttons too
+    /// if shifter.inputs().unwrap().get_named("start")? {
+    ///     println!("Start pressed");
+    /// }
+    /// ```
+    ///
+    /// *data* is connected to the serial output (QH) of the input shift
+    /// register nearest the Raspberry Pi and *load* to every input shift
+    /// register's parallel load (SH/LD) pin.  Their clock (CLK) pins go to
+    /// the same clock pin as the output shift registers'.
+    ///
+    /// From then on every `apply()` reads the inputs as well:  The inputs get
+    /// latched before the outputs start shifting and each one is read just
+    /// before the clock edge that shifts the output bits along (which is
+    /// what moves the next input onto QH) so one pass of the clock services
+    /// both.  If there are more inputs than outputs the extra clocks shift
+    /// out LOW bits first (which fall off the far end of the outputs).
+    /// `read_inputs()` does the same thing without changing anything.
+    ///
+    /// Inputs can only be read when bit-banging:  While inputs are attached
+    /// `apply()` returns a `ShifterError::Unsupported` if this `Shifter`
+    /// uses a `ShiftBus` or any of its shift registers have a
+    /// `ShiftProtocol`.  Attaching inputs again replaces the old ones.
+    pub fn attach_inputs<D, L>(&mut self, data: D, load: L) -> &mut ShifterIn
+        where D: InputPin + 'static, L: OutputPin + 'static
+    {
+        self.inputs.insert(ShifterIn::sharing_clock(Box::new(data), Box::new(load)))
+    }
+
+    /// Returns the input shift registers attached via `attach_inputs()` (if
+    /// any) as of the last `apply()`.
+    pub fn inputs(&self) -> Option<&ShifterIn> {
+        self.inputs.as_ref()
+    }
+
+    /// Returns the input shift registers attached via `attach_inputs()` (if
+    /// any) so shift registers can be added, pins named, etc.
+    pub fn inputs_mut(&mut self) -> Option<&mut ShifterIn> {
+        self.inputs.as_mut()
+    }
+
+    /// Reads the inputs attached via `attach_inputs()`.  Since the inputs
+    /// share the clock with the outputs this is an `apply()` (which shifts
+    /// out and latches the current state of the outputs again) so the two
+    /// never fight over the clock pin.  Returns a `ShifterError::MissingPin`
+    /// if no inputs are attached.
+    pub fn read_inputs(&mut self) -> Result<ApplyStats, ShifterError> {
+        if self.inputs.is_none() {
+            return Err(ShifterError::MissingPin("input data"));
+        }
+        self.apply()
+    }
+}
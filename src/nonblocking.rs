@@ -0,0 +1,174 @@
+//! A `Future`-based API for async applications.  See `AsyncShifter`.  Enabled
+//! via the `async` feature.
+//!
+//! Shifting out a long chain by bit-banging GPIO pins takes long enough that
+//! it shouldn't happen on an async runtime's worker threads so all the actual
+//! work happens on the `Shifter`'s own thread (see `Shifter.spawn()`) and the
+//! futures returned here just wait for it.  They don't depend on any
+//! particular runtime (tokio, async-std, smol, etc all work).
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use {Shifter, ShifterError, ShifterClient, Command, RegisterHandle, Apply, ApplyStats};
+
+// Where the shifter's thread leaves the result for a ShifterFuture
+struct Slot<T> {
+    result: Option<Result<T, ShifterError>>,
+    waker: Option<Waker>,
+    closed: bool, // The job was dropped without producing a result
+}
+
+/// The result of an `AsyncShifter` method that hasn't finished yet.
+/// Resolves to a `ShifterError::Disconnected` if the `Shifter`'s thread has
+/// stopped.
+#[must_use = "futures do nothing unless awaited (but the change is still made)"]
+pub struct ShifterFuture<T>(Arc<Mutex<Slot<T>>>);
+
+impl<T> Future for ShifterFuture<T> {
+    type Output = Result<T, ShifterError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut slot = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(result) = slot.result.take() {
+            Poll::Ready(result)
+        } else if slot.closed {
+            Poll::Ready(Err(ShifterError::Disconnected))
+        } else {
+            slot.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for ShifterFuture<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ShifterFuture").finish()
+    }
+}
+
+// The shifter's end of a ShifterFuture:  Wakes it up when the result is in
+// (or when the job gets dropped without running)
+struct Completer<T>(Arc<Mutex<Slot<T>>>);
+
+impl<T> Completer<T> {
+
+    fn complete(self, result: Result<T, ShifterError>) {
+        self.0.lock().unwrap_or_else(|err| err.into_inner()).result = Some(result);
+    }
+}
+
+impl<T> Drop for Completer<T> {
+    fn drop(&mut self) {
+        let mut slot = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        slot.closed = true;
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// A cheap, cloneable handle for controlling a `Shifter` from async code.
+/// Every method returns immediately with a `ShifterFuture` that resolves once
+/// the `Shifter`'s thread has done the work:
+///
+/// ```
+/// let shifter = shifter.into_async()?;
+/// shifter.set_pin_high(sr0, 3, Apply::Later).await?;
+/// let stats = shifter.apply().await?;
+/// ```
+///
+/// Changes are made in the order in which the methods are called (even if
+/// the futures are awaited in a different order or not at all).
+#[derive(Debug, Clone)]
+pub struct AsyncShifter {
+    client: ShifterClient,
+}
+
+impl AsyncShifter {
+
+    /// Runs *f* on the `Shifter`'s thread and resolves to whatever it
+    /// returns.  Use this for anything the other methods don't cover.
+    pub fn execute<F, R>(&self, f: F) -> ShifterFuture<R>
+        where F: FnOnce(&mut Shifter) -> Result<R, ShifterError> + Send + 'static, R: Send + 'static
+    {
+        let slot = Arc::new(Mutex::new(Slot { result: None, waker: None, closed: false }));
+        let completer = Completer(slot.clone());
+        // If the thread is gone the job (and the completer) gets dropped which
+        // resolves the future with an error:
+        let _ = self.client.submit(Box::new(move |shifter| completer.complete(f(shifter))));
+        ShifterFuture(slot)
+    }
+
+    /// Carries out the given *command* (see `ShifterClient.send()`).
+    pub fn send(&self, command: Command) -> ShifterFuture<()> {
+        self.execute(move |shifter| command.run(shifter))
+    }
+
+    /// See `Shifter.set()`.
+    pub fn set(&self, sr: RegisterHandle, data: usize, apply: Apply) -> ShifterFuture<()> {
+                self.execute(move |shifter| shifter.set(sr, data, apply))
+    }
+
+    /// See `Shifter.get()`.
+    pub fn get(&self, sr: RegisterHandle) -> ShifterFuture<usize> {
+        self.execute(move |shifter| shifter.get(sr))
+    }
+
+    /// See `Shifter.set_pin_high()`.
+    pub fn set_pin_high(&self, sr: RegisterHandle, pin: u8, apply: Apply) -> ShifterFuture<()> {
+                self.execute(move |shifter| shifter.set_pin_high(sr, pin, apply))
+    }
+
+    /// See `Shifter.set_pin_low()`.
+    pub fn set_pin_low(&self, sr: RegisterHandle, pin: u8, apply: Apply) -> ShifterFuture<()> {
+                self.execute(move |shifter| shifter.set_pin_low(sr, pin, apply))
+    }
+
+    /// See `Shifter.toggle_pin()`.
+    pub fn toggle_pin(&self, sr: RegisterHandle, pin: u8, apply: Apply) -> ShifterFuture<()> {
+                self.execute(move |shifter| shifter.toggle_pin(sr, pin, apply))
+    }
+
+    /// See `Shifter.get_pin()`.
+    pub fn get_pin(&self, sr: RegisterHandle, pin: u8) -> ShifterFuture<bool> {
+        self.execute(move |shifter| shifter.get_pin(sr, pin))
+    }
+
+    /// See `Shifter.set_chain()`.
+    pub fn set_chain(&self, bytes: Vec<u8>, apply: Apply) -> ShifterFuture<()> {
+                self.execute(move |shifter| shifter.set_chain(&bytes, apply))
+    }
+
+    /// See `Shifter.set_named()`.
+    pub fn set_named(&self, name: &str, state: bool, apply: Apply) -> ShifterFuture<()> {
+        let (name, apply) = (name.to_string(), apply);
+        self.execute(move |shifter| shifter.set_named(&name, state, apply))
+    }
+
+    /// See `Shifter.apply()`.
+    pub fn apply(&self) -> ShifterFuture<ApplyStats> {
+        self.execute(|shifter| shifter.apply())
+    }
+}
+
+impl From<ShifterClient> for AsyncShifter {
+    fn from(client: ShifterClient) -> AsyncShifter {
+        AsyncShifter { client: client }
+    }
+}
+
+impl Shifter {
+
+    /// Moves this `Shifter` to a thread of its own (see `spawn()`) and returns
+    /// an `AsyncShifter` for controlling it from async code.  The thread stops
+    /// (and the `Shifter` is dropped) once every clone of the `AsyncShifter`
+    /// is gone.
+    ///
+    /// Returns a `ShifterError::IoError` if the thread can't be spawned.
+    pub fn into_async(self) -> Result<AsyncShifter, ShifterError> {
+        let (client, _) = self.spawn()?;
+        Ok(AsyncShifter::from(client))
+    }
+}
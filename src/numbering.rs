@@ -0,0 +1,62 @@
+//! Translating between the different ways of numbering the Raspberry Pi's GPIO
+//! pins.  See `PinNumbering`.
+
+use ShifterError;
+
+// WiringPi pin -> BCM GPIO (same table CuPi uses)
+const WIRINGPI_TO_BCM: [u8; 32] = [
+    17, 18, 27, 22, 23, 24, 25, 4, 2, 3, 8, 7, 10, 9, 11, 14,
+    15, 28, 29, 30, 31, 5, 6, 13, 19, 26, 12, 16, 20, 21, 0, 1,
+];
+
+// Physical header pin (starting at 1) -> BCM GPIO.  None for the power and
+// ground pins.
+const PHYSICAL_TO_BCM: [Option<u8>; 40] = [
+    None, None, Some(2), None, Some(3), None, Some(4), Some(14),
+    None, Some(15), Some(17), Some(18), Some(27), None, Some(22), Some(23),
+    None, Some(24), Some(10), None, Some(9), Some(25), Some(11), Some(8),
+    None, Some(7), Some(0), Some(1), Some(5), None, Some(6), Some(12),
+    Some(13), None, Some(19), Some(16), Some(26), Some(20), None, Some(21),
+];
+
+/// The numbering scheme used for the data, latch, and clock pins passed to
+/// `Shifter::with_numbering()` or `ShifterBuilder.numbering()`.  Whichever you
+/// pick gets translated to the WiringPi numbers CuPi uses internally.
+///
+/// ```
+/// // All three of these refer to the very last pin on the header:
+/// assert_eq!(PinNumbering::Physical.to_wiringpi(40).unwrap(), 29);
+/// assert_eq!(PinNumbering::Bcm.to_wiringpi(21).unwrap(), 29);
+/// assert_eq!(PinNumbering::WiringPi.to_wiringpi(29).unwrap(), 29);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PinNumbering {
+    /// WiringPi numbers (what CuPi uses; the default).
+    WiringPi,
+    /// Broadcom GPIO numbers (e.g. GPIO 21 for pin 40 on the header).  This is
+    /// what most Raspberry Pi documentation (and rppal, gpio-cdev, etc) uses.
+    Bcm,
+    /// The position on the 40-pin header, starting at 1.
+    Physical,
+}
+
+impl PinNumbering {
+
+    /// Returns the WiringPi number of the given *pin* (numbered according to
+    /// this scheme).  Returns a `ShifterError::InvalidGpio` if there's no such
+    /// GPIO pin (e.g. physical pin 6 which is ground).
+    pub fn to_wiringpi(self, pin: usize) -> Result<usize, ShifterError> {
+        let bcm = match self {
+            PinNumbering::WiringPi if pin < WIRINGPI_TO_BCM.len() => return Ok(pin),
+            PinNumbering::WiringPi => None,
+            PinNumbering::Bcm => Some(pin),
+            PinNumbering::Physical => match pin.checked_sub(1).and_then(|i| PHYSICAL_TO_BCM.get(i)) {
+                Some(&bcm) => bcm.map(|bcm| bcm as usize),
+                None => None,
+            },
+        };
+        bcm.and_then(|bcm| WIRINGPI_TO_BCM.iter().position(|&b| b as usize == bcm))
+            .ok_or(ShifterError::InvalidGpio(pin))
+    }
+}
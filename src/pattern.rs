@@ -0,0 +1,74 @@
+//! Common bit patterns that can be filled onto a shift register (or a whole
+//! chain).  See `Shifter.fill()` and `Shifter.fill_chain()`.
+
+/// A pattern of HIGH/LOW pins that can be generated onto a shift register via
+/// `Shifter.fill()` or across every shift register in the chain via
+/// `Shifter.fill_chain()`:
+///
+/// ```
+/// shifter.fill(sr0, Pattern::Alternating, Apply::Now).unwrap(); // 0b01010101
+/// shifter.fill_chain(Pattern::Threshold(0.25), Apply::Now).unwrap(); // Bar graph
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pattern {
+    /// Every other pin HIGH starting with pin 0 (e.g. `0b01010101`).
+    Alternating,
+    /// Every other pin HIGH starting with pin 1 (e.g. `0b10101010`).
+    AlternatingInverse,
+    /// Every *n*th pin HIGH starting with pin 0 (e.g. `EveryNth(3)` gives
+    /// `0b01001001`).  `EveryNth(0)` sets every pin LOW.
+    EveryNth(usize),
+    /// Every pin below the given *level* (a fraction of the total number of
+    /// pins from `0.0` to `1.0`) HIGH and the rest LOW, like a bar graph or VU
+    /// meter (e.g. `Threshold(0.5)` gives `0b00001111`).
+    Threshold(f32),
+    /// The first *n* pins HIGH and the rest LOW (e.g. `FirstN(3)` gives
+    /// `0b00000111`).
+    FirstN(usize),
+}
+
+impl Pattern {
+
+    /// Returns the state of the given *pin* when this pattern is generated
+    /// onto *pins* pins.
+    pub fn state(&self, pin: usize, pins: usize) -> bool {
+        match *self {
+            Pattern::Alternating => pin.is_multiple_of(2),
+            Pattern::AlternatingInverse => !pin.is_multiple_of(2),
+            Pattern::EveryNth(n) => n > 0 && pin.is_multiple_of(n),
+            Pattern::Threshold(level) => (pin as f32) < level * pins as f32,
+            Pattern::FirstN(n) => pin < n,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pattern;
+
+    // Generates the given pattern onto 8 pins
+    fn word(pattern: Pattern) -> u8 {
+        (0..8).fold(0, |word, pin| {
+            if pattern.state(pin, 8) { word | 1 << pin } else { word }
+        })
+    }
+
+    #[test]
+    fn alternating() {
+        assert_eq!(word(Pattern::Alternating), 0b0101_0101);
+        assert_eq!(word(Pattern::AlternatingInverse), 0b1010_1010);
+    }
+
+    #[test]
+    fn every_nth() {
+        assert_eq!(word(Pattern::EveryNth(3)), 0b0100_1001);
+        assert_eq!(word(Pattern::EveryNth(0)), 0);
+    }
+
+    #[test]
+    fn threshold_and_first_n() {
+        assert_eq!(word(Pattern::Threshold(0.5)), 0b0000_1111);
+        assert_eq!(word(Pattern::Threshold(1.0)), 0b1111_1111);
+        assert_eq!(word(Pattern::FirstN(3)), 0b0000_0111);
+    }
+}
@@ -0,0 +1,106 @@
+//! Saving the state of the chain to disk so it survives a restart.  See
+//! `Shifter.save_state()` and `Shifter.restore_state()`.
+//!
+//! The file format is deliberately simple (one line per shift register with
+//! its pin count followed by its data as hex bytes) so it can be inspected or
+//! edited by hand:
+//!
+//! ```text
+//! # cupi_shift state
+//! 8 a5
+//! 16 ff 01
+//! ```
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use {Shifter, ShifterError, Apply};
+
+impl Shifter {
+
+    /// Writes the current data of every shift register in the chain to the
+    /// file at *path* so it can be put back with `restore_state()` (e.g. after
+    /// a reboot).  The file is written atomically:  The data goes to a
+    /// temporary file next to *path* which is then renamed over it, so a
+    /// power cut in the middle of saving won't leave you with a half-written
+    /// file.
+    pub fn save_state<P: AsRef<Path>>(&self, path: P) -> Result<(), ShifterError> {
+        let path = path.as_ref();
+        let registers: Vec<(u8, &[u8])> = self.shift_registers.iter()
+            .map(|sr| (sr.pins, &sr.data.bytes[..]))
+            .collect();
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        {
+            let mut file = File::create(&tmp_path)?;
+            file.write_all(encode(&registers).as_bytes())?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Reads the data saved by `save_state()` from the file at *path* and sets
+    /// every shift register accordingly.  The chain must have the same layout
+    /// as when the state was saved (the same number of shift registers with
+    /// the same number of pins) or you'll get a `ShifterError::LayoutMismatch`
+    /// and nothing will be changed.
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn restore_state<P: AsRef<Path>>(&mut self, path: P, apply: Apply) -> Result<(), ShifterError> {
+        let mut contents = String::new();
+        File::open(path)?.read_to_string(&mut contents)?;
+        let registers = decode(&contents)?;
+        if registers.len() != self.shift_registers.len() {
+            return Err(ShifterError::LayoutMismatch);
+        }
+        for (sr, &(pins, ref bytes)) in self.shift_registers.iter().zip(registers.iter()) {
+            if pins != sr.pins {
+                return Err(ShifterError::LayoutMismatch);
+            }
+            sr.check_bytes(bytes)?;
+        }
+        for (sr, register) in self.shift_registers.iter_mut().zip(registers.iter()) {
+            sr.data.set_bytes(&register.1);
+        }
+        self.dirty = true;
+        self.maybe_apply(apply)
+    }
+}
+
+pub(crate) fn encode(registers: &[(u8, &[u8])]) -> String {
+    let mut contents = String::from("# cupi_shift state\n");
+    for &(pins, bytes) in registers {
+        contents.push_str(&pins.to_string());
+        for byte in bytes {
+            contents.push_str(&format!(" {:02x}", byte));
+        }
+        contents.push('\n');
+    }
+    contents
+}
+
+pub(crate) fn decode(contents: &str) -> Result<Vec<(u8, Vec<u8>)>, ShifterError> {
+    let invalid = |line: &str| -> ShifterError {
+        io::Error::new(io::ErrorKind::InvalidData, format!("Invalid state line: {:?}", line)).into()
+    };
+    let mut registers = Vec::new();
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let pins = match fields.next().map(str::parse) {
+            Some(Ok(pins)) => pins,
+            _ => return Err(invalid(line)),
+        };
+        let mut bytes = Vec::new();
+        for field in fields {
+            match u8::from_str_radix(field, 16) {
+                Ok(byte) => bytes.push(byte),
+                Err(_) => return Err(invalid(line)),
+            }
+        }
+        registers.push((pins, bytes));
+    }
+    Ok(registers)
+}
@@ -0,0 +1,248 @@
+//! Reading a chain of input shift registers continuously from a background
+//! thread and reporting every change.  See `ShifterIn.start_polling()`.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use {ShifterIn, ShifterError, RegisterHandle, Debounce, Gestures, period_from_hz};
+use debounce::InputFilter;
+use gesture::GestureState;
+
+/// What happened to an input (see `InputEvent`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputChange {
+    /// The input went HIGH (after any inversion; see `ShifterIn.invert()`).
+    Pressed,
+    /// The input went LOW (after any inversion).
+    Released,
+    /// The input was pressed and released (sent after `Released`; see
+    /// `Gestures`).
+    Clicked,
+    /// The input was pressed again soon after a click (sent after the
+    /// second `Pressed`; see `Gestures.double_click`).
+    DoubleClicked,
+    /// The input has been pressed for a while (see `Gestures.hold`).
+    Held,
+}
+
+/// A change to one of the inputs of a `ShifterIn` that's being polled (see
+/// `ShifterIn.start_polling()`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    /// The shift register the input belongs to.
+    pub register: RegisterHandle,
+    /// The input's pin number on that shift register.
+    pub pin: u8,
+    /// Whether it was pressed or released.
+    pub change: InputChange,
+    /// When the read that noticed the change happened (i.e. after any
+    /// debouncing; see `ShifterIn.set_debounce()`).
+    pub at: Instant,
+}
+
+// Everything the polling thread shares with its InputPoller handle
+struct Shared {
+    inputs: Mutex<ShifterIn>,
+    stopping: AtomicBool,
+    error: Mutex<Option<ShifterError>>,
+}
+
+impl Shared {
+
+    fn lock(&self) -> MutexGuard<'_, ShifterIn> {
+        self.inputs.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// A handle to a `ShifterIn` that's being read at a fixed rate by a
+/// background thread.  Returned by `ShifterIn.start_polling()` along with the
+/// `Receiver` its `InputEvent`s arrive on.
+///
+/// Use `lock()` to look at the state of every input (as of the last read) or
+/// to add shift registers, name pins, etc.  Dropping an `InputPoller` stops
+/// the thread (and drops the `ShifterIn`), which ends the stream of events.
+pub struct InputPoller {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl InputPoller {
+
+    /// Locks the `ShifterIn` (waiting for the current read to finish, if
+    /// any).  Don't hold on to the lock for long; no reads happen while it's
+    /// held.
+    pub fn lock(&self) -> MutexGuard<'_, ShifterIn> {
+        self.shared.lock()
+    }
+
+    /// Returns `true` unless the thread has stopped because a read failed
+    /// (see `take_error()`).
+    pub fn is_running(&self) -> bool {
+        self.thread.as_ref().is_some_and(|thread| !thread.is_finished())
+    }
+
+    /// Returns (and forgets) the error that stopped the polling thread, if
+    /// any.
+    pub fn take_error(&self) -> Option<ShifterError> {
+        self.shared.error.lock().unwrap_or_else(|err| err.into_inner()).take()
+    }
+
+    /// Stops the polling thread (waiting for it to finish whatever read it's
+    /// in the middle of) and returns the `ShifterIn`.
+    pub fn stop(mut self) -> ShifterIn {
+        self.join();
+        let shared = self.shared.clone();
+        drop(self);
+        match Arc::try_unwrap(shared) {
+            Ok(shared) => shared.inputs.into_inner().unwrap_or_else(|err| err.into_inner()),
+            Err(_) => unreachable!("the polling thread has exited"),
+        }
+    }
+
+    fn join(&mut self) {
+        self.shared.stopping.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for InputPoller {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+impl std::fmt::Debug for InputPoller {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("InputPoller")
+            .field("running", &self.is_running())
+            .finish()
+    }
+}
+
+// One input as of the latest read along with how it's to be treated
+struct Reading {
+    register: RegisterHandle,
+    pin: u8,
+    high: bool,
+    debounce: Debounce,
+    gestures: Gestures,
+}
+
+// The body of the polling thread
+fn run(shared: Arc<Shared>, period: Duration, events: Sender<InputEvent>) {
+    let mut states: Vec<(InputFilter, GestureState)> = shared.lock().levels().iter()
+        .map(|&(_, _, high)| (InputFilter::new(high), GestureState::default()))
+        .collect();
+    let mut next = Instant::now();
+    while !shared.stopping.load(Ordering::SeqCst) {
+        let now = Instant::now();
+        if now < next {
+            thread::park_timeout(next - now);
+            continue;
+        }
+        let result = {
+            let mut inputs = shared.lock();
+            inputs.read().map(|_| {
+                inputs.levels().into_iter()
+                    .map(|(sr, pin, high)| Reading {
+                        register: sr,
+                        pin: pin,
+                        high: high,
+                        debounce: inputs.pin_debounce_or_default(sr, pin),
+                        gestures: inputs.pin_gestures_or_default(sr, pin),
+                    })
+                    .collect::<Vec<_>>()
+            })
+        };
+        let at = Instant::now();
+        match result {
+            Ok(readings) => {
+                for (i, reading) in readings.iter().enumerate() {
+                    if i == states.len() {
+                        // A shift register added since polling started
+                        states.push((InputFilter::new(false), GestureState::default()));
+                    }
+                    let (ref mut filter, ref mut gestures) = states[i];
+                    let mut changes = Vec::new();
+                    changes.extend(gestures.poll(at, reading.gestures));
+                    match filter.update(reading.high, at, reading.debounce) {
+                        Some(true) => {
+                            changes.push(InputChange::Pressed);
+                            changes.extend(gestures.press(at, reading.gestures));
+                        },
+                        Some(false) => {
+                            changes.push(InputChange::Released);
+                            changes.extend(gestures.release(at, reading.gestures));
+                        },
+                        None => {},
+                    }
+                    for change in changes {
+                        let event = InputEvent { register: reading.register, pin: reading.pin, change: change, at: at };
+                        // Nobody listening isn't a reason to stop reading
+                        let _ = events.send(event);
+                    }
+                }
+            },
+            Err(err) => {
+                *shared.error.lock().unwrap_or_else(|err| err.into_inner()) = Some(err);
+                return;
+            },
+        }
+        next += period;
+        if next < Instant::now() {
+            // Reading takes longer than the period; don't try to catch up
+            next = Instant::now();
+        }
+    }
+}
+
+impl ShifterIn {
+
+    /// Moves this `ShifterIn` to a background thread that reads the chain
+    /// *hz* times per second and returns an `InputPoller` handle along with
+    /// a `Receiver` that gets an `InputEvent` whenever an input changes:
+    ///
+    /// ```
+    /// let (poller, events) = inputs.start_polling(100.0)?;
+    /// for event in events {
+    ///     if event.register == buttons && event.change == InputChange::Pressed {
+    ///         println!("Button {} pressed", event.pin);
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// Changes are relative to the inputs as they were when polling started
+    /// (i.e. as of the last `read()`, or all LOW if there wasn't one) so an
+    /// input that's already HIGH doesn't produce an event until it changes.
+    /// Mechanical switches bounce so unless they're debounced in hardware
+    /// see `set_debounce()`.  For clicks, double clicks, and holds see
+    /// `set_gestures()`.
+    ///
+    /// Returns a `ShifterError::InvalidRate` if *hz* isn't a positive number
+    /// or a `ShifterError::IoError` if the thread can't be spawned.  If a
+    /// read fails the thread stops (see `InputPoller.take_error()`) and the
+    /// `Receiver` stops getting events.
+    pub fn start_polling(self, hz: f64) -> Result<(InputPoller, Receiver<InputEvent>), ShifterError> {
+        let period = period_from_hz(hz)?;
+        let shared = Arc::new(Shared {
+            inputs: Mutex::new(self),
+            stopping: AtomicBool::new(false),
+            error: Mutex::new(None),
+        });
+        let (sender, receiver) = mpsc::channel();
+        let thread_shared = shared.clone();
+        let thread = thread::Builder::new()
+            .name("cupi_shift inputs".to_string())
+            .spawn(move || run(thread_shared, period, sender))?;
+        let poller = InputPoller {
+            shared: shared,
+            thread: Some(thread),
+        };
+        Ok((poller, receiver))
+    }
+}
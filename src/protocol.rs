@@ -0,0 +1,100 @@
+//! Chip-specific ways of turning a shift register's state into bits on the
+//! wire.  See `ShiftProtocol` and `Shifter.set_protocol()`.  Like `register`
+//! this only relies on `core` and `alloc`.
+
+use alloc::vec::Vec;
+
+/// Describes how a particular kind of chip wants its bits shifted out.  By
+/// default every shift register in the chain is treated like a 74HC595 (its
+/// pins get shifted out once and then everything is latched) but some chips
+/// need something else:  The MAX7219, for example, takes 16-bit commands and
+/// can only update one digit per latch pulse.
+///
+/// `apply()` asks every shift register's protocol for the *frames* it needs
+/// (the bits to shift into it before each latch pulse).  If some shift
+/// registers need more frames than others the rest are given their
+/// `idle_frame()` so the whole chain can be latched together.
+pub trait ShiftProtocol: Send {
+    /// Returns the bits (first bit first) that need to be shifted into the
+    /// shift register before each latch pulse given the state of its
+    /// *outputs*.  *outputs* already has inversion, remapping, and bit order
+    /// taken care of (so with the default settings `outputs[n]` is pin *n*).
+    fn frames(&self, outputs: &[bool]) -> Vec<Vec<bool>>;
+
+    /// Returns the bits to shift into the shift register for a frame where
+    /// it has nothing to do (because other shift registers in the chain need
+    /// more frames).  Defaults to the last of `frames()` (i.e. re-sending the
+    /// same state).
+    fn idle_frame(&self, outputs: &[bool]) -> Vec<bool> {
+        self.frames(outputs).pop().unwrap_or_default()
+    }
+
+    /// Returns any frames that need to be sent once (before the first
+    /// `apply()` after the protocol is set) to set the chip up.  None by
+    /// default.
+    fn init_frames(&self) -> Vec<Vec<bool>> {
+        Vec::new()
+    }
+}
+
+/// The 74HC595 (and anything else that works the same way like the TPIC6B595
+/// or TLC5916):  The pins get shifted out once and then latched.  This is
+/// what shift registers without a protocol do anyway.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Hc595;
+
+impl ShiftProtocol for Hc595 {
+    fn frames(&self, outputs: &[bool]) -> Vec<Vec<bool>> {
+        vec![outputs.to_vec()]
+    }
+}
+
+/// The MAX7219/MAX7221 LED driver.  Add it as a 64-pin shift register where
+/// pin `digit * 8 + segment` is segment *segment* (0 being segment G and 7
+/// being the decimal point, as per the datasheet) of digit *digit*.  Updating
+/// all eight digits takes eight 16-bit frames.
+#[derive(Debug, Clone, Copy)]
+pub struct Max7219 {
+    /// The brightness (0 to 15).
+    pub intensity: u8,
+}
+
+impl Default for Max7219 {
+    fn default() -> Max7219 {
+        Max7219 { intensity: 7 }
+    }
+}
+
+impl Max7219 {
+
+    // Returns the bits of a 16-bit command (register address then data, most
+    // significant bit first)
+    fn command(address: u8, data: u8) -> Vec<bool> {
+        let word = (address as u16) << 8 | data as u16;
+        (0..16).rev().map(|bit| word & (1 << bit) != 0).collect()
+    }
+}
+
+impl ShiftProtocol for Max7219 {
+    fn frames(&self, outputs: &[bool]) -> Vec<Vec<bool>> {
+        outputs.chunks(8).take(8).enumerate().map(|(digit, segments)| {
+            let data = segments.iter().enumerate()
+                .fold(0, |data, (segment, &on)| if on { data | 1 << segment } else { data });
+            Max7219::command(digit as u8 + 1, data)
+        }).collect()
+    }
+
+    fn idle_frame(&self, _outputs: &[bool]) -> Vec<bool> {
+        Max7219::command(0x00, 0) // No-op
+    }
+
+    fn init_frames(&self) -> Vec<Vec<bool>> {
+        vec![
+            Max7219::command(0x0F, 0), // Display test off
+            Max7219::command(0x09, 0), // No BCD decoding
+            Max7219::command(0x0A, self.intensity.min(15)),
+            Max7219::command(0x0B, 7), // Scan all eight digits
+            Max7219::command(0x0C, 1), // Normal operation (not shutdown)
+        ]
+    }
+}
@@ -0,0 +1,241 @@
+//! Recurring schedule rules:  Either built up in code or parsed from cron
+//! expressions.  See `Recurrence` and `Schedule.recurring()`.
+
+use std::str::FromStr;
+use {ShifterError, TimeOfDay};
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// A day of the week.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Weekday {
+    Sunday,
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+}
+
+impl Weekday {
+
+    // Days since Sunday (like cron)
+    fn number(&self) -> u8 {
+        match *self {
+            Weekday::Sunday => 0,
+            Weekday::Monday => 1,
+            Weekday::Tuesday => 2,
+            Weekday::Wednesday => 3,
+            Weekday::Thursday => 4,
+            Weekday::Friday => 5,
+            Weekday::Saturday => 6,
+        }
+    }
+}
+
+/// A rule for when something recurs, like "every weekday at 06:30" or "the
+/// 1st and 15th of every month at noon".  Build one up in code:
+///
+/// ```
+/// let watering = Recurrence::daily(TimeOfDay::new(6, 30, 0)?)
+///     .on(&[Weekday::Monday, Weekday::Wednesday, Weekday::Friday]);
+/// ```
+///
+/// Or parse a standard five-field cron expression (minute, hour, day of
+/// month, month, day of week) with ranges (`1-5`), lists (`1,15`), steps
+/// (`*/15`), and three-letter month and day names:
+///
+/// ```
+/// let watering: Recurrence = "30 6 * * mon,wed,fri".parse()?;
+/// let quarter_hourly: Recurrence = "*/15 8-17 * * *".parse()?;
+/// ```
+///
+/// Just like cron, if both the days of the month and the days of the week are
+/// restricted a day only has to match one of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Recurrence {
+    second: u8,
+    minutes: u64, // Bit n set means minute n
+    hours: u32,
+    days: u32, // Days of the month (bit 1 being the 1st)
+    months: u16, // Bit 1 being January
+    weekdays: u8, // Bit 0 being Sunday
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl Recurrence {
+
+    /// Returns a `Recurrence` for every day at the given *time*.
+    pub fn daily(time: TimeOfDay) -> Recurrence {
+        Recurrence {
+            second: time.second(),
+            minutes: 1 << time.minute(),
+            hours: 1 << time.hour(),
+            days: ALL_DAYS,
+            months: ALL_MONTHS,
+            weekdays: ALL_WEEKDAYS,
+            days_restricted: false,
+            weekdays_restricted: false,
+        }
+    }
+
+    /// Restricts this recurrence to the given days of the week.
+    pub fn on(mut self, weekdays: &[Weekday]) -> Recurrence {
+        self.weekdays = weekdays.iter().fold(0, |bits, day| bits | 1 << day.number());
+        self.weekdays_restricted = true;
+        self
+    }
+
+    /// Restricts this recurrence to the given *days* of the month (1 to 31;
+    /// anything else is ignored).
+    pub fn on_days(mut self, days: &[u8]) -> Recurrence {
+        self.days = days.iter().filter(|&&day| (1..=31).contains(&day)).fold(0, |bits, &day| bits | 1 << day);
+        self.days_restricted = true;
+        self
+    }
+
+    /// Restricts this recurrence to the given *months* (1 to 12; anything
+    /// else is ignored).
+    pub fn in_months(mut self, months: &[u8]) -> Recurrence {
+        self.months = months.iter().filter(|&&month| (1..=12).contains(&month)).fold(0, |bits, &month| bits | 1 << month);
+        self
+    }
+
+    /// Also recurs at the given *time* on every day this recurrence does (so
+    /// `Recurrence::daily(morning).and_at(evening)` recurs twice a day).
+    /// Bear in mind that this recurs at every combination of the hours and
+    /// minutes given.
+    pub fn and_at(mut self, time: TimeOfDay) -> Recurrence {
+        self.minutes |= 1 << time.minute();
+        self.hours |= 1 << time.hour();
+        self
+    }
+
+    // Returns true if this recurrence happens at all on the given date
+    fn matches_date(&self, month: u8, day: u8, weekday: u8) -> bool {
+        if self.months & 1 << month == 0 {
+            return false;
+        }
+        let day_ok = self.days & 1 << day != 0;
+        let weekday_ok = self.weekdays & 1 << weekday != 0;
+        if self.days_restricted && self.weekdays_restricted {
+            day_ok || weekday_ok
+        } else {
+            day_ok && weekday_ok
+        }
+    }
+
+    /// Returns the first time this recurs after *after* (in local seconds
+    /// since the Unix epoch, i.e. with the UTC offset already added) or
+    /// `None` if it never does (e.g. "February 30th").
+    pub(crate) fn next_after_local(&self, after: i64) -> Option<i64> {
+        let first_day = after.div_euclid(DAY);
+        // Eight years covers every combination of leap years and weekdays:
+        for days in first_day..first_day + 8 * 366 {
+            let (_, month, day) = civil_from_days(days);
+            let weekday = (days + 4).rem_euclid(7) as u8; // 1970-01-01 was a Thursday
+            if !self.matches_date(month, day, weekday) {
+                continue;
+            }
+            for hour in (0..24).filter(|hour| self.hours & 1 << hour != 0) {
+                for minute in (0..60).filter(|minute| self.minutes & 1 << minute != 0) {
+                    let time = days * DAY + hour * 3600 + minute * 60 + self.second as i64;
+                    if time > after {
+                        return Some(time);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+const ALL_DAYS: u32 = 0xFFFF_FFFE; // 1 to 31
+const ALL_MONTHS: u16 = 0x1FFE; // 1 to 12
+const ALL_WEEKDAYS: u8 = 0x7F;
+
+// Converts days since the Unix epoch into a (year, month, day) date in the
+// proleptic Gregorian calendar (see http://howardhinnant.github.io/date_algorithms.html)
+pub(crate) fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097); // Day of era
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365; // Year of era
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100); // Day of year (starting in March)
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+impl FromStr for Recurrence {
+    type Err = ShifterError;
+
+    fn from_str(s: &str) -> Result<Recurrence, ShifterError> {
+        let invalid = || ShifterError::InvalidRecurrence(s.to_string());
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(invalid());
+        }
+        const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+        const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+        let minutes = parse_field(fields[0], 0, 59, &[], 0).ok_or_else(invalid)?;
+        let hours = parse_field(fields[1], 0, 23, &[], 0).ok_or_else(invalid)?;
+        let days = parse_field(fields[2], 1, 31, &[], 0).ok_or_else(invalid)?;
+        let months = parse_field(fields[3], 1, 12, &MONTHS, 1).ok_or_else(invalid)?;
+        // Both 0 and 7 mean Sunday:
+        let weekdays = parse_field(fields[4], 0, 7, &WEEKDAYS, 0).ok_or_else(invalid)?;
+        let weekdays = (weekdays | weekdays >> 7) & ALL_WEEKDAYS as u64;
+        Ok(Recurrence {
+            second: 0,
+            minutes: minutes,
+            hours: hours as u32,
+            days: days as u32,
+            months: months as u16,
+            weekdays: weekdays as u8,
+            days_restricted: fields[2] != "*",
+            weekdays_restricted: fields[4] != "*",
+        })
+    }
+}
+
+// Parses one field of a cron expression into a set of bits.  *names* (if
+// any) are accepted in place of numbers, the first one being *first*.
+fn parse_field(field: &str, min: u64, max: u64, names: &[&str], first: u64) -> Option<u64> {
+    let number = |value: &str| -> Option<u64> {
+        let lower = value.to_lowercase();
+        let n = match names.iter().position(|&name| name == lower) {
+            Some(index) => index as u64 + first,
+            None => value.parse().ok()?,
+        };
+        if n >= min && n <= max { Some(n) } else { None }
+    };
+    let mut bits = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.find('/') {
+            Some(slash) => (&item[..slash], item[slash + 1..].parse::<u64>().ok()?),
+            None => (item, 1),
+        };
+        if step == 0 {
+            return None;
+        }
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some(dash) = range.find('-') {
+            (number(&range[..dash])?, number(&range[dash + 1..])?)
+        } else {
+            let n = number(range)?;
+            (n, if step > 1 { max } else { n }) // "5/10" means 5, 15, 25...
+        };
+        if start > end {
+            return None;
+        }
+        for n in (start..=end).step_by(step as usize) {
+            bits |= 1 << n;
+        }
+    }
+    Some(bits)
+}
@@ -0,0 +1,260 @@
+//! Re-shifting the chain continuously from a background thread (for
+//! multiplexed displays, software PWM, or just guarding against glitches).
+//! See `Shifter.start_refresh()`.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+use {Shifter, ShifterError, period_from_hz};
+
+// Everything the refresh thread shares with its Refresh handle
+struct Shared {
+    shifter: Mutex<Shifter>,
+    paused: AtomicBool,
+    stopping: AtomicBool,
+    error: Mutex<Option<ShifterError>>,
+}
+
+impl Shared {
+
+    fn lock(&self) -> MutexGuard<'_, Shifter> {
+        // A panic while the lock was held can't leave the Shifter in a state
+        // that's any worse than a failed apply() would so carry on regardless:
+        self.shifter.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// A handle to a `Shifter` that's being re-applied at a fixed rate by a
+/// background thread.  Returned by `Shifter.start_refresh()`.
+///
+/// Use `lock()` to change the state of the chain; the refresh thread picks up
+/// the changes the next time it applies.  The `Shifter` is locked for the
+/// duration of every refresh so a refresh never sees half of a set of changes
+/// made while holding the lock:
+///
+/// ```
+/// let refresh = shifter.start_refresh(200.0)?;
+/// {
+///     let mut shifter = refresh.lock();
+///     shifter.set(sr0, 0b00001111, Apply::Later)?;
+///     shifter.set(sr1, 0b11110000, Apply::Later)?;
+/// } // Both show up together in the next refresh
+/// let shifter = refresh.stop(); // Get the Shifter back
+/// ```
+///
+/// Dropping a `Refresh` stops the thread (and drops the `Shifter`).
+pub struct Refresh {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Refresh {
+
+    /// Locks the `Shifter` (waiting for the current refresh to finish, if
+    /// any) so its state can be changed.  Don't hold on to the lock for long;
+    /// no refreshes happen while it's held.
+    pub fn lock(&self) -> MutexGuard<'_, Shifter> {
+        self.shared.lock()
+    }
+
+    /// Stops refreshing (without stopping the thread) until `resume()` is
+    /// called.  Whatever was last shifted out stays latched in the meantime.
+    pub fn pause(&self) {
+        self.shared.paused.store(true, Ordering::SeqCst);
+    }
+
+    /// Starts refreshing again after a `pause()`.
+    pub fn resume(&self) {
+        self.shared.paused.store(false, Ordering::SeqCst);
+        self.wake();
+    }
+
+    /// Returns `true` if refreshing is currently paused.
+    pub fn is_paused(&self) -> bool {
+        self.shared.paused.load(Ordering::SeqCst)
+    }
+
+    /// Returns `true` unless the thread has stopped because a refresh failed
+    /// (see `take_error()`).
+    pub fn is_running(&self) -> bool {
+        self.thread.as_ref().is_some_and(|thread| !thread.is_finished())
+    }
+
+    /// Returns (and forgets) the error that stopped the refresh thread, if
+    /// any.
+    pub fn take_error(&self) -> Option<ShifterError> {
+        self.shared.error.lock().unwrap_or_else(|err| err.into_inner()).take()
+    }
+
+    /// Stops the refresh thread (waiting for it to finish whatever refresh
+    /// it's in the middle of) and returns the `Shifter`.
+    pub fn stop(mut self) -> Shifter {
+        self.join();
+        let shared = self.shared.clone();
+        drop(self);
+        match Arc::try_unwrap(shared) {
+            Ok(shared) => shared.shifter.into_inner().unwrap_or_else(|err| err.into_inner()),
+            Err(_) => unreachable!("the refresh thread has exited"),
+        }
+    }
+
+    /// Switches the refresh thread to the `SCHED_FIFO` real-time scheduling
+    /// policy at the given *priority* (1 to 99 on Linux; higher runs first)
+    /// so other processes can't preempt it and make multiplexed displays or
+    /// dimmed pins flicker:
+    ///
+    /// ```
+    /// let refresh = shifter.start_refresh(800.0)?;
+    /// if !refresh.set_realtime_priority(50)? {
+    ///     println!("Not allowed to use real-time scheduling; expect some flicker");
+    /// }
+    /// ```
+    ///
+    /// Needs root (or `CAP_SYS_NICE`, or an `rtprio` limit in
+    /// `/etc/security/limits.conf`).  Without permission the thread carries
+    /// on at its normal priority and this returns `Ok(false)`.  Returns a
+    /// `ShifterError::InvalidPriority` if *priority* is out of range or a
+    /// `ShifterError::IoError` if anything else goes wrong.  Keep the refresh
+    /// rate sensible:  A real-time thread that never sleeps can starve the
+    /// rest of the system.
+    #[cfg(feature = "realtime")]
+    pub fn set_realtime_priority(&self, priority: i32) -> Result<bool, ShifterError> {
+        use std::io;
+        use std::os::unix::thread::JoinHandleExt;
+        let (min, max) = unsafe {
+            (libc::sched_get_priority_min(libc::SCHED_FIFO), libc::sched_get_priority_max(libc::SCHED_FIFO))
+        };
+        if priority < min || priority > max {
+            return Err(ShifterError::InvalidPriority(priority));
+        }
+        let thread = match self.thread {
+            Some(ref thread) => thread.as_pthread_t(),
+            None => return Err(ShifterError::Disconnected),
+        };
+        let param = libc::sched_param { sched_priority: priority };
+        match unsafe { libc::pthread_setschedparam(thread, libc::SCHED_FIFO, &param) } {
+            0 => Ok(true),
+            libc::EPERM => Ok(false),
+            err => Err(io::Error::from_raw_os_error(err).into()),
+        }
+    }
+
+    fn wake(&self) {
+        if let Some(ref thread) = self.thread {
+            thread.thread().unpark();
+        }
+    }
+
+    fn join(&mut self) {
+        self.shared.stopping.store(true, Ordering::SeqCst);
+        self.wake();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Refresh {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+impl std::fmt::Debug for Refresh {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Refresh")
+            .field("paused", &self.is_paused())
+            .field("running", &self.is_running())
+            .finish()
+    }
+}
+
+// The body of the refresh thread
+fn run<F>(shared: Arc<Shared>, period: Duration, mut before: F)
+    where F: FnMut(&mut Shifter) -> Result<(), ShifterError>
+{
+    let mut next = Instant::now();
+    while !shared.stopping.load(Ordering::SeqCst) {
+        let now = Instant::now();
+        if now < next {
+            thread::park_timeout(next - now);
+            continue;
+        }
+        if shared.paused.load(Ordering::SeqCst) {
+            thread::park();
+            next = Instant::now();
+            continue;
+        }
+        let result = {
+            let mut shifter = shared.lock();
+            let now = Instant::now();
+            shifter.animate(now);
+            shifter.play_steps(now)
+                .and_then(|_| shifter.run_schedule(SystemTime::now()))
+                .and_then(|_| shifter.dimming_step())
+                .and_then(|periods| before(&mut shifter).map(|_| periods))
+                .and_then(|periods| shifter.apply().map(|_| periods))
+        };
+        match result {
+            Ok(periods) => next += period * periods,
+            Err(err) => {
+                *shared.error.lock().unwrap_or_else(|err| err.into_inner()) = Some(err);
+                return;
+            },
+        }
+        if next < Instant::now() {
+            // Refreshing takes longer than the period; don't try to catch up
+            next = Instant::now();
+        }
+    }
+}
+
+impl Shifter {
+
+    /// Moves this `Shifter` to a background thread that re-applies its state
+    /// *hz* times per second and returns a `Refresh` handle for changing the
+    /// state, pausing, and getting the `Shifter` back.  Every refresh shifts
+    /// out the whole chain whether anything changed or not (and takes care of
+    /// any pins dimmed via `set_brightness()`).
+    ///
+    /// Returns a `ShifterError::InvalidRate` if *hz* isn't a positive number
+    /// or a `ShifterError::IoError` if the thread can't be spawned.  If a
+    /// refresh fails the thread stops (see `Refresh.take_error()`).
+    pub fn start_refresh(self, hz: f64) -> Result<Refresh, ShifterError> {
+        self.start_refresh_with(hz, |_| Ok(()))
+    }
+
+    /// Just like `start_refresh()` but calls *before* (with the `Shifter`
+    /// locked) right before every refresh.  Use this to change the state in
+    /// step with the refreshes, e.g. to select the next row of a multiplexed
+    /// display:
+    ///
+    /// ```
+    /// let mut row = 0;
+    /// let refresh = shifter.start_refresh_with(800.0, move |shifter| {
+    ///     row = (row + 1) % 8;
+    ///     shifter.set(rows, 1 << row, Apply::Later)?;
+    ///     shifter.set(columns, frame[row], Apply::Later)
+    /// })?;
+    /// ```
+    pub fn start_refresh_with<F>(self, hz: f64, before: F) -> Result<Refresh, ShifterError>
+        where F: FnMut(&mut Shifter) -> Result<(), ShifterError> + Send + 'static
+    {
+        let period = period_from_hz(hz)?;
+        let shared = Arc::new(Shared {
+            shifter: Mutex::new(self),
+            paused: AtomicBool::new(false),
+            stopping: AtomicBool::new(false),
+            error: Mutex::new(None),
+        });
+        let thread_shared = shared.clone();
+        let thread = thread::Builder::new()
+            .name("cupi_shift refresh".to_string())
+            .spawn(move || run(thread_shared, period, before))?;
+        Ok(Refresh {
+            shared: shared,
+            thread: Some(thread),
+        })
+    }
+}
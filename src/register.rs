@@ -0,0 +1,327 @@
+//! The pure state-tracking side of things:  The data, inversion, bit order,
+//! and pin remapping of each shift register along with the logic for working
+//! out what gets shifted out.  Nothing in here touches the GPIO pins (that's
+//! what `Shifter` and the `OutputPin` backends are for) and it only relies on
+//! `core` and `alloc` so it can be tested anywhere (no hardware required) and
+//! builds without `std` (see `nostd-check/`).
+
+use core::{cmp, fmt, mem};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use protocol::ShiftProtocol;
+
+// The number of bits in a usize (the type used by `Shifter.set()` and friends)
+pub(crate) const WORD_BITS: usize = mem::size_of::<usize>() * 8;
+
+// A fixed-length vector of bits (pin 0 being the least significant bit of the
+// first byte).  This is how shift register data gets stored so that a single
+// shift register (or a whole daisy chain modeled as one) can have more pins
+// than fit in a usize.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Bits {
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) len: usize,
+}
+
+impl Bits {
+
+    pub(crate) fn new(len: usize) -> Bits {
+        Bits { bytes: vec![0; len.div_ceil(8)], len: len }
+    }
+
+    pub(crate) fn get(&self, n: usize) -> bool {
+        self.bytes[n / 8] >> (n % 8) & 1 == 1
+    }
+
+    pub(crate) fn set(&mut self, n: usize, state: bool) {
+        if state {
+            self.bytes[n / 8] |= 1 << (n % 8);
+        } else {
+            self.bytes[n / 8] &= !(1 << (n % 8));
+        }
+    }
+
+    pub(crate) fn fill(&mut self, state: bool) {
+        for n in 0..self.len {
+            self.set(n, state);
+        }
+    }
+
+    // Returns the first WORD_BITS bits as a usize
+    pub(crate) fn word(&self) -> usize {
+        let mut word = 0;
+        for n in 0..cmp::min(self.len, WORD_BITS) {
+            if self.get(n) { word |= 1 << n; }
+        }
+        word
+    }
+
+    // Sets the first WORD_BITS bits to those in *word* (ignoring any that don't
+    // fit) but only where *mask* is set
+    pub(crate) fn set_word_masked(&mut self, word: usize, mask: usize) {
+        for n in 0..cmp::min(self.len, WORD_BITS) {
+            if mask >> n & 1 == 1 {
+                self.set(n, word >> n & 1 == 1);
+            }
+        }
+    }
+
+    // Sets every bit from *word*; bits beyond WORD_BITS get cleared
+    pub(crate) fn set_word(&mut self, word: usize) {
+        for byte in self.bytes.iter_mut() { *byte = 0; }
+        self.set_word_masked(word, !0);
+    }
+
+    // Replaces every bit with those in *bytes* (which must be the right length).
+    // Any bits in the last byte beyond our length are ignored.
+    pub(crate) fn set_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.copy_from_slice(bytes);
+        self.clear_unused();
+    }
+
+    // Rotates every bit *n* places towards the end (bits that fall off the end
+    // wrap around to the start)
+    pub(crate) fn rotate_left(&mut self, n: usize) {
+        if self.len == 0 { return; }
+        let old = self.clone();
+        for i in 0..self.len {
+            self.set((i + n) % self.len, old.get(i));
+        }
+    }
+
+    // Moves every bit one place towards the end (dropping the last one) and
+    // sets the first bit to *state* (just like a real shift register)
+    pub(crate) fn shift_in(&mut self, state: bool) {
+        if self.len == 0 { return; }
+        for i in (1..self.len).rev() {
+            let prev = self.get(i - 1);
+            self.set(i, prev);
+        }
+        self.set(0, state);
+    }
+
+    pub(crate) fn resize(&mut self, len: usize) {
+        self.bytes.resize(len.div_ceil(8), 0);
+        self.len = len;
+        self.clear_unused(); // In case we shrank
+    }
+
+    // Zeroes any bits in the last byte that are beyond our length
+    pub(crate) fn clear_unused(&mut self) {
+        let extra = self.bytes.len() * 8 - self.len;
+        if extra > 0 {
+            let last = self.bytes.len() - 1;
+            self.bytes[last] &= 0xFF >> extra;
+        }
+    }
+}
+
+/// The state of one of the shift registers being tracked by a `Shifter`.  See
+/// `Shifter.registers()`.
+pub struct ShiftRegister {
+    pub(crate) id: usize, // Unique (per Shifter) ID used by RegisterHandle
+    pub(crate) data: Bits, // e.g. 0b01010101
+    pub(crate) pins: u8, // Not aware of any shift registers that have more than 255 output pins
+    pub(crate) invert: bool,
+    pub(crate) invert_mask: Bits, // Individual pins to invert
+    // If the pins are wired in a scrambled order this maps each physical
+    // output (index) to the logical pin (value) that should drive it:
+    pub(crate) remap: Option<Vec<u8>>,
+    pub(crate) bit_order: BitOrder,
+    pub(crate) name: Option<String>,
+    // How to shift this register's bits out if it isn't a plain old 74HC595
+    // (and whether the protocol's init_frames() still need to be sent):
+    pub(crate) protocol: Option<Box<dyn ShiftProtocol>>,
+    pub(crate) needs_init: bool,
+}
+
+/// The order in which a shift register's data gets shifted out.  See
+/// `Shifter.set_bit_order()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BitOrder {
+    /// Pin 0 gets shifted out first (the default).
+    LsbFirst,
+    /// The highest pin gets shifted out first.
+    MsbFirst,
+}
+
+/// The complete configuration of a shift register for use with
+/// `Shifter.add_with()`.  Use `RegisterConfig::new()` or
+/// `RegisterConfig::default()` (an 8-pin shift register) to fill in the
+/// fields you don't care about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RegisterConfig {
+    /// The number of output pins.
+    pub pins: u8,
+    /// Whether or not the shift register's logic is inverted (see
+    /// `Shifter.set_invert()`).
+    pub invert: bool,
+    /// The order in which the data gets shifted out (see
+    /// `Shifter.set_bit_order()`).
+    pub bit_order: BitOrder,
+    /// An optional name for looking up the shift register later (see
+    /// `Shifter.register_named()`).
+    pub name: Option<String>,
+    /// The data the shift register starts out with (see `Shifter.set()`).
+    pub initial_data: usize,
+}
+
+impl RegisterConfig {
+    /// Returns the default configuration for a shift register with the given
+    /// number of *pins*.
+    pub fn new(pins: u8) -> RegisterConfig {
+        RegisterConfig { pins: pins, ..RegisterConfig::default() }
+    }
+}
+
+impl Default for RegisterConfig {
+    fn default() -> RegisterConfig {
+        RegisterConfig {
+            pins: 8,
+            invert: false,
+            bit_order: BitOrder::LsbFirst,
+            name: None,
+            initial_data: 0,
+        }
+    }
+}
+
+// This is great for debugging; displays the Shift Register data in binary:
+impl fmt::Display for ShiftRegister {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut string = String::with_capacity(self.pins as usize);
+        for n in (0..self.pins as usize).rev() {
+            string.push(if self.data.get(n) { '1' } else { '0' });
+        }
+        f.write_str("0b")?;
+        f.pad_integral(true, "", &string)
+    }
+}
+
+impl fmt::Debug for ShiftRegister {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ShiftRegister")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("pins", &self.pins)
+            .field("data", &format_args!("{}", self))
+            .field("invert", &self.invert)
+            .field("bit_order", &self.bit_order)
+            .finish()
+    }
+}
+
+impl ShiftRegister {
+
+    pub(crate) fn new(id: usize, pins: u8) -> ShiftRegister {
+        ShiftRegister {
+            id: id,
+            data: Bits::new(pins as usize),
+            pins: pins,
+            invert: false,
+            invert_mask: Bits::new(pins as usize),
+            remap: None,
+            bit_order: BitOrder::LsbFirst,
+            name: None,
+            protocol: None,
+            needs_init: false,
+        }
+    }
+
+    pub(crate) fn from_config(id: usize, config: RegisterConfig) -> ShiftRegister {
+        let mut sr = ShiftRegister::new(id, config.pins);
+        sr.set(config.initial_data);
+        sr.invert = config.invert;
+        sr.bit_order = config.bit_order;
+        sr.name = config.name;
+        sr
+    }
+
+    /// Returns the number of output pins on this shift register.
+    pub fn pins(&self) -> u8 {
+        self.pins
+    }
+
+    /// Returns the data currently being tracked for this shift register.  If
+    /// the shift register has more pins than fit in a `usize` only the first
+    /// 64 (or 32 on 32-bit platforms) are included; use `bytes()` to get all
+    /// of them.
+    pub fn data(&self) -> usize {
+        self.data.word()
+    }
+
+    /// Returns the data currently being tracked for this shift register as
+    /// bytes (pin 0 being the least significant bit of the first byte).
+    pub fn bytes(&self) -> &[u8] {
+        &self.data.bytes
+    }
+
+    /// Returns `true` if this shift register's logic is inverted (see
+    /// `Shifter.set_invert()`).
+    pub fn is_inverted(&self) -> bool {
+        self.invert
+    }
+
+    /// Returns the mask of individual pins whose logic is inverted (see
+    /// `Shifter.set_invert_mask()`).
+    pub fn invert_mask(&self) -> usize {
+        self.invert_mask.word()
+    }
+
+    /// Returns the order in which this shift register's data gets shifted out
+    /// (see `Shifter.set_bit_order()`).
+    pub fn bit_order(&self) -> BitOrder {
+        self.bit_order
+    }
+
+    /// Returns the name given to this shift register (via `RegisterConfig`),
+    /// if any.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub(crate) fn set(&mut self, data: usize) {
+        self.data.set_word(data);
+    }
+
+    // Returns the state of the given (logical) pin
+    pub(crate) fn pin(&self, pin: u8) -> bool {
+        self.data.get(pin as usize)
+    }
+
+    pub(crate) fn set_pin(&mut self, pin: u8, state: bool) {
+        self.data.set(pin as usize, state);
+    }
+
+    pub(crate) fn resize(&mut self, pins: u8) {
+        self.pins = pins;
+        self.data.resize(pins as usize);
+        self.invert_mask.resize(pins as usize);
+    }
+
+    // Returns the level that should be put on the data line for the *i*th bit
+    // that gets shifted out of this shift register with bit order, remapping,
+    // and inversion (*invert* being the Shifter-wide setting) taken into
+    // account
+    pub(crate) fn output_bit(&self, i: u8, invert: bool) -> bool {
+        let n = match self.bit_order {
+            BitOrder::LsbFirst => i,
+            BitOrder::MsbFirst => self.pins - 1 - i,
+        };
+        let pin = match self.remap {
+            Some(ref remap) => remap[n as usize],
+            None => n,
+        };
+        // Global, per-register, and per-pin inversion all cancel each other out:
+        let flip = (invert != self.invert) != self.invert_mask.get(pin as usize);
+        self.pin(pin) != flip
+    }
+
+    // Returns every bit apply() would shift out (in order)
+    pub(crate) fn output_bits(&self, invert: bool) -> Vec<bool> {
+        (0..self.pins).map(|i| self.output_bit(i, invert)).collect()
+    }
+}
@@ -0,0 +1,360 @@
+//! Carrying out `Command`s at set times of day, on a recurring basis, or at
+//! sunrise/sunset (e.g. switching relays or lights on and off).  See
+//! `Schedule` and `Shifter.set_schedule()`.
+//!
+//! Times are worked out from the system clock in seconds since the Unix epoch
+//! so there's no dependency on a time zone database:  Give the schedule your
+//! UTC offset (see `Schedule.utc_offset()`) and remember to update it if your
+//! clocks change for daylight saving time.
+
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use {Shifter, ShifterError, Command, Apply, Recurrence, SunEvent};
+use sun::next_sun_event;
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// A time of day (in the schedule's time zone; see `Schedule.utc_offset()`).
+/// Can be parsed from `"HH:MM"` or `"HH:MM:SS"` (24-hour clock):
+///
+/// ```
+/// let dusk: TimeOfDay = "17:30".parse()?;
+/// assert_eq!(dusk, TimeOfDay::new(17, 30, 0)?);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimeOfDay {
+    seconds: u32, // Since midnight
+}
+
+impl TimeOfDay {
+
+    /// Returns a new `TimeOfDay` or a `ShifterError::InvalidTime` if *hour*,
+    /// *minute*, or *second* is out of range.
+    pub fn new(hour: u8, minute: u8, second: u8) -> Result<TimeOfDay, ShifterError> {
+        if hour > 23 || minute > 59 || second > 59 {
+            return Err(ShifterError::InvalidTime(format!("{:02}:{:02}:{:02}", hour, minute, second)));
+        }
+        Ok(TimeOfDay { seconds: hour as u32 * 3600 + minute as u32 * 60 + second as u32 })
+    }
+
+    /// Returns the hour (0 to 23).
+    pub fn hour(&self) -> u8 {
+        (self.seconds / 3600) as u8
+    }
+
+    /// Returns the minute (0 to 59).
+    pub fn minute(&self) -> u8 {
+        (self.seconds / 60 % 60) as u8
+    }
+
+    /// Returns the second (0 to 59).
+    pub fn second(&self) -> u8 {
+        (self.seconds % 60) as u8
+    }
+}
+
+impl FromStr for TimeOfDay {
+    type Err = ShifterError;
+
+    fn from_str(s: &str) -> Result<TimeOfDay, ShifterError> {
+        let invalid = || ShifterError::InvalidTime(s.to_string());
+        let fields = s.trim().split(':')
+            .map(|field| field.parse::<u8>().map_err(|_| invalid()))
+            .collect::<Result<Vec<u8>, ShifterError>>()?;
+        match fields[..] {
+            [hour, minute] => TimeOfDay::new(hour, minute, 0),
+            [hour, minute, second] => TimeOfDay::new(hour, minute, second),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl std::fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:02}:{:02}:{:02}", self.hour(), self.minute(), self.second())
+    }
+}
+
+// When a scheduled command gets carried out
+#[derive(Debug, Clone, PartialEq)]
+enum Rule {
+    Daily(TimeOfDay),
+    Once(i64), // Unix time
+    Recurring(Recurrence),
+    Sun(SunEvent, i64), // Offset in seconds
+}
+
+impl Rule {
+
+    // Returns the first time (Unix time) this rule fires after *after* given
+    // the schedule's UTC *offset* (in seconds) and *location*
+    fn next_after(&self, after: i64, offset: i64, location: Option<(f64, f64)>) -> Option<i64> {
+        match *self {
+            Rule::Daily(time) => {
+                let local = after + offset;
+                let mut next = local.div_euclid(DAY) * DAY + time.seconds as i64;
+                if next <= local {
+                    next += DAY;
+                }
+                Some(next - offset)
+            },
+            Rule::Once(at) => if at > after { Some(at) } else { None },
+            Rule::Recurring(ref recurrence) => recurrence.next_after_local(after + offset).map(|next| next - offset),
+            Rule::Sun(event, delay) => {
+                let (latitude, longitude) = location?;
+                next_sun_event(event, latitude, longitude, after - delay).map(|next| next + delay)
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    rule: Rule,
+    command: Command,
+}
+
+/// A list of `Command`s to carry out at set times (see
+/// `Shifter.set_schedule()`):
+///
+/// ```
+/// let schedule = Schedule::new()
+///     .utc_offset(-5 * 60) // US Eastern (standard time)
+///     .at("17:30".parse()?, Command::SetGroup { name: "porch".into(), state: true, apply: Apply::Now })
+///     .at("23:00".parse()?, Command::SetGroup { name: "porch".into(), state: false, apply: Apply::Now });
+/// shifter.set_schedule(schedule)?;
+/// let (client, thread) = shifter.spawn()?; // Runs the schedule from here on
+/// ```
+///
+/// Every command that comes due at the same time goes out in a single apply
+/// (regardless of the commands' own *apply* fields).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    entries: Vec<Entry>,
+    offset: i64, // Seconds east of UTC
+    location: Option<(f64, f64)>, // Latitude and longitude
+    catch_up: bool,
+    checked: i64, // Everything up to this Unix time has been carried out
+    next: Option<i64>, // When the next command is due
+}
+
+impl Schedule {
+
+    /// Returns a new (empty) `Schedule` that works in UTC.
+    pub fn new() -> Schedule {
+        Schedule {
+            entries: Vec::new(),
+            offset: 0,
+            location: None,
+            catch_up: true,
+            checked: 0,
+            next: None,
+        }
+    }
+
+    /// Carries out *command* every day at the given *time*.
+    pub fn at(self, time: TimeOfDay, command: Command) -> Schedule {
+        self.add(Rule::Daily(time), command)
+    }
+
+    /// Carries out *command* whenever the given *recurrence* comes around
+    /// (e.g. every weekday morning; see `Recurrence`):
+    ///
+    /// ```
+    /// let schedule = Schedule::new()
+    ///     .recurring("0 6 * * mon-fri".parse()?, Command::SetNamed { name: "valve".into(), state: true, apply: Apply::Now })
+    ///     .recurring("20 6 * * mon-fri".parse()?, Command::SetNamed { name: "valve".into(), state: false, apply: Apply::Now });
+    /// ```
+    pub fn recurring(self, recurrence: Recurrence, command: Command) -> Schedule {
+        self.add(Rule::Recurring(recurrence), command)
+    }
+
+    /// Carries out *command* every day when the given sun *event* happens
+    /// plus *minutes* (which can be negative) at the schedule's location
+    /// (which has to be set; see `location()`).  On days the event doesn't
+    /// happen at all (e.g. no sunset during a polar summer) the command
+    /// isn't carried out.  Holiday lights that come on at dusk and go off at
+    /// 23:00:
+    ///
+    /// ```
+    /// let schedule = Schedule::new()
+    ///     .location(40.7, -74.0)
+    ///     .utc_offset(-5 * 60)
+    ///     .at_sun(SunEvent::CivilDusk, 0, Command::SetGroup { name: "tree".into(), state: true, apply: Apply::Now })
+    ///     .at("23:00".parse()?, Command::SetGroup { name: "tree".into(), state: false, apply: Apply::Now });
+    /// ```
+    pub fn at_sun(self, event: SunEvent, minutes: i32, command: Command) -> Schedule {
+        self.add(Rule::Sun(event, minutes as i64 * 60), command)
+    }
+
+    /// Carries out *command* once at the given *time*.
+    pub fn once(self, time: SystemTime, command: Command) -> Schedule {
+        self.add(Rule::Once(unix_time(time)), command)
+    }
+
+    /// Sets the offset from UTC (in minutes; east being positive) that times
+    /// of day are given in.
+    pub fn utc_offset(mut self, minutes: i32) -> Schedule {
+        self.offset = minutes as i64 * 60;
+        self
+    }
+
+    /// Sets where the schedule is being run (in degrees; north and east being
+    /// positive) so sun events can be worked out (see `at_sun()`).
+    pub fn location(mut self, latitude: f64, longitude: f64) -> Schedule {
+        self.location = Some((latitude, longitude));
+        self
+    }
+
+    /// Sets whether the schedule should carry out everything that would have
+    /// come due in the 24 hours before it was set (in order) so the chain
+    /// starts out in the state it would be in had the schedule been running
+    /// all along (`true`, the default).  So if the porch lights go on at
+    /// 17:30 and off at 23:00 setting the schedule at 18:00 turns them on
+    /// right away.
+    pub fn catch_up(mut self, catch_up: bool) -> Schedule {
+        self.catch_up = catch_up;
+        self
+    }
+
+    /// Returns when the next command is due (or `None` if nothing is left
+    /// to do).  Only meaningful once the schedule has been set (see
+    /// `Shifter.set_schedule()`).
+    pub fn next_due(&self) -> Option<SystemTime> {
+        self.next.map(system_time)
+    }
+
+    fn add(mut self, rule: Rule, command: Command) -> Schedule {
+        self.entries.push(Entry { rule: rule, command: command });
+        self
+    }
+
+    // Starts keeping track of time from *now*
+    fn start(&mut self, now: i64) {
+        self.checked = if self.catch_up { now - DAY } else { now };
+        self.update_next();
+    }
+
+    // Returns an error if there are sun events without a (valid) location
+    fn check_location(&self) -> Result<(), ShifterError> {
+        if !self.entries.iter().any(|entry| matches!(entry.rule, Rule::Sun(..))) {
+            return Ok(());
+        }
+        match self.location {
+            Some((latitude, longitude)) if latitude.abs() <= 90.0 && longitude.abs() <= 180.0 => Ok(()),
+            Some((latitude, longitude)) => Err(ShifterError::InvalidLocation(format!("{}, {}", latitude, longitude))),
+            None => Err(ShifterError::InvalidLocation("none set".to_string())),
+        }
+    }
+
+    fn update_next(&mut self) {
+        let (checked, offset, location) = (self.checked, self.offset, self.location);
+        self.next = self.entries.iter().filter_map(|entry| entry.rule.next_after(checked, offset, location)).min();
+    }
+
+    // Returns every command that came due after the last check up until
+    // *now* (in the order in which they came due)
+    fn due(&mut self, now: i64) -> Vec<Command> {
+        if self.next.is_none_or(|next| next > now) {
+            return Vec::new();
+        }
+        let mut due = Vec::new();
+        for (index, entry) in self.entries.iter().enumerate() {
+            let mut after = self.checked;
+            while let Some(at) = entry.rule.next_after(after, self.offset, self.location) {
+                if at > now {
+                    break;
+                }
+                due.push((at, index));
+                after = at;
+            }
+        }
+        due.sort(); // By time (then by the order in which they were added)
+        self.checked = now;
+        self.update_next();
+        due.into_iter().map(|(_, index)| self.entries[index].command.clone()).collect()
+    }
+}
+
+impl Default for Schedule {
+    fn default() -> Schedule {
+        Schedule::new()
+    }
+}
+
+// Converts a SystemTime into (whole) seconds since the Unix epoch
+pub(crate) fn unix_time(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_secs() as i64,
+        Err(err) => -(err.duration().as_secs() as i64),
+    }
+}
+
+pub(crate) fn system_time(unix: i64) -> SystemTime {
+    if unix >= 0 {
+        UNIX_EPOCH + Duration::from_secs(unix as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(unix.unsigned_abs())
+    }
+}
+
+impl Shifter {
+
+    /// Sets the `Schedule` of commands to carry out at set times (replacing
+    /// any other schedule).  Scheduled commands are carried out by `tick()`,
+    /// the refresh thread if the chain is being refreshed (see
+    /// `start_refresh()`), or the `Shifter`'s own thread if it has one (see
+    /// `spawn()`, which is the easiest way to keep a schedule running in the
+    /// background).  If the schedule catches up (see `Schedule.catch_up()`)
+    /// that's done (and applied) right away.
+    ///
+    /// Returns a `ShifterError::InvalidLocation` if the schedule has sun
+    /// events (see `Schedule.at_sun()`) but no valid location.
+    pub fn set_schedule(&mut self, mut schedule: Schedule) -> Result<(), ShifterError> {
+        schedule.check_location()?;
+        let now = SystemTime::now();
+        schedule.start(unix_time(now));
+        self.schedule = Some(schedule);
+        if self.run_schedule(now)? {
+            self.apply()?;
+        }
+        Ok(())
+    }
+
+    /// Removes (and returns) the `Schedule` set with `set_schedule()`.
+    pub fn clear_schedule(&mut self) -> Option<Schedule> {
+        self.schedule.take()
+    }
+
+    /// Returns the `Schedule` set with `set_schedule()` (if any).
+    pub fn schedule(&self) -> Option<&Schedule> {
+        self.schedule.as_ref()
+    }
+
+    // Returns when the next scheduled command is due as an Instant (so it can
+    // be waited for)
+    pub(crate) fn next_scheduled(&self) -> Option<Instant> {
+        let next = self.schedule.as_ref()?.next_due()?;
+        let wait = next.duration_since(SystemTime::now()).unwrap_or_default();
+        Some(Instant::now() + wait)
+    }
+
+    // Carries out every scheduled command that's due as of *now* (without
+    // applying).  Returns true if there were any.  If one fails the rest are
+    // still carried out and the first error is returned.
+    pub(crate) fn run_schedule(&mut self, now: SystemTime) -> Result<bool, ShifterError> {
+        let due = match self.schedule {
+            Some(ref mut schedule) => schedule.due(unix_time(now)),
+            None => return Ok(false),
+        };
+        let mut result = Ok(!due.is_empty());
+        for command in due {
+            if let Err(err) = command.with_apply(Apply::Later).run(self) {
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+        result
+    }
+}
@@ -0,0 +1,177 @@
+//! Playing back timed sequences of changes (test patterns, simple light
+//! shows, etc).  See `Sequence` and `Shifter.play()`.
+
+use std::time::{Duration, Instant};
+use {Shifter, ShifterError, Command, Apply};
+
+/// How many times a `Sequence` plays through (see `Sequence.repeat()`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    /// Play through the given number of times (`Times(1)` being the default;
+    /// `Times(0)` is treated the same).
+    Times(u32),
+    /// Keep starting over until `Shifter.stop()` is called.
+    Forever,
+}
+
+/// One step of a `Sequence`:  Some changes to make and how long to wait
+/// before moving on to the next step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// The changes to make (in order).  They all go out together in a single
+    /// apply regardless of their own *apply* fields.
+    pub commands: Vec<Command>,
+    /// How long the result stays up before the next step.
+    pub duration: Duration,
+}
+
+/// An ordered list of `Step`s to play back on a `Shifter` (see
+/// `Shifter.play()`):
+///
+/// ```
+/// let test_pattern = Sequence::new()
+///     .frame(&[0b01010101, 0b01010101], Duration::from_millis(500))
+///     .frame(&[0b10101010, 0b10101010], Duration::from_millis(500))
+///     .step(vec![Command::AllLow(Apply::Later)], Duration::from_secs(1))
+///     .repeat(Repeat::Times(3));
+/// shifter.play(test_pattern)?;
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sequence {
+    steps: Vec<Step>,
+    repeat: Repeat,
+}
+
+impl Sequence {
+
+    /// Returns a new (empty) `Sequence` that plays through once.
+    pub fn new() -> Sequence {
+        Sequence {
+            steps: Vec::new(),
+            repeat: Repeat::Times(1),
+        }
+    }
+
+    /// Adds a step that carries out the given *commands* and then waits for
+    /// *duration*.
+    pub fn step(mut self, commands: Vec<Command>, duration: Duration) -> Sequence {
+        self.steps.push(Step { commands: commands, duration: duration });
+        self
+    }
+
+    /// Adds a step that sets the whole chain from *bytes* (see
+    /// `Shifter.set_chain()`) and then waits for *duration*.
+    pub fn frame(self, bytes: &[u8], duration: Duration) -> Sequence {
+        self.step(vec![Command::SetChain { bytes: bytes.to_vec(), apply: Apply::Later }], duration)
+    }
+
+    /// Sets how many times the sequence plays through.
+    pub fn repeat(mut self, repeat: Repeat) -> Sequence {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Returns the steps in this sequence.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// Returns how long it takes to play the sequence through once.
+    pub fn duration(&self) -> Duration {
+        self.steps.iter().map(|step| step.duration).sum()
+    }
+}
+
+impl Default for Sequence {
+    fn default() -> Sequence {
+        Sequence::new()
+    }
+}
+
+// A sequence that's being played along with where we're at in it
+#[derive(Debug)]
+pub(crate) struct Player {
+    sequence: Sequence,
+    step: usize,
+    passes: u32, // How many times we've played through so far
+    next: Instant, // When the next step is due
+}
+
+impl Shifter {
+
+    /// Starts playing back the given *sequence* (replacing any sequence
+    /// that's already playing).  The first step is carried out and applied
+    /// right away.  After that the steps are carried out on time by `tick()`,
+    /// the refresh thread if the chain is being refreshed (see
+    /// `start_refresh()`), or the `Shifter`'s own thread if it has one (see
+    /// `spawn()`).
+    ///
+    /// If a step fails playback stops and the error is returned by whatever
+    /// carried out the step.
+    pub fn play(&mut self, sequence: Sequence) -> Result<(), ShifterError> {
+        let now = Instant::now();
+        self.player = Some(Player { sequence: sequence, step: 0, passes: 0, next: now });
+        if self.play_steps(now)? {
+            self.apply()?;
+        }
+        Ok(())
+    }
+
+    /// Stops the sequence started by `play()` (if any), leaving the chain in
+    /// whatever state the last step left it in.
+    pub fn stop(&mut self) {
+        self.player = None;
+    }
+
+    /// Returns `true` if a sequence is playing (see `play()`).
+    pub fn is_playing(&self) -> bool {
+        self.player.is_some()
+    }
+
+    /// Returns when the next step of the sequence that's playing is due (or
+    /// `None` if there isn't one).
+    pub fn next_step_due(&self) -> Option<Instant> {
+        self.player.as_ref().map(|player| player.next)
+    }
+
+    // Carries out every step of the playing sequence that's due as of *now*
+    // (without applying).  Returns true if any were.
+    pub(crate) fn play_steps(&mut self, now: Instant) -> Result<bool, ShifterError> {
+        let mut player = match self.player.take() {
+            Some(player) => player,
+            None => return Ok(false),
+        };
+        let mut played = false;
+        // Never carry out more than one pass worth of steps at once (so a
+        // sequence with no duration can't keep us here forever):
+        for _ in 0..player.sequence.steps.len() {
+            if player.next > now {
+                break;
+            }
+            let step = &player.sequence.steps[player.step];
+            for command in step.commands.iter().cloned() {
+                // (Stops playback if it fails)
+                command.with_apply(Apply::Later).run(self)?;
+            }
+            played = true;
+            player.next += step.duration;
+            player.step += 1;
+            if player.step == player.sequence.steps.len() {
+                player.step = 0;
+                player.passes += 1;
+                if let Repeat::Times(times) = player.sequence.repeat {
+                    if player.passes >= times {
+                        return Ok(true); // Finished (leaving the last step up)
+                    }
+                }
+            }
+        }
+        if player.next <= now {
+            player.next = now; // Fell behind; carry on from here
+        }
+        if !player.sequence.steps.is_empty() {
+            self.player = Some(player);
+        }
+        Ok(played)
+    }
+}
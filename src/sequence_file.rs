@@ -0,0 +1,166 @@
+//! Loading `Sequence`s from JSON or RON files so light shows can be written
+//! without touching any Rust.  Enabled via the `json` and `ron` features.
+//!
+//! A sequence file contains a list of steps (carried out in order) and,
+//! optionally, how many times to play through them (a number or
+//! `"forever"`; 1 if left out):
+//!
+//! ```json
+//! {
+//!     "repeat": 3,
+//!     "steps": [
+//!         { "chain": [85, 170], "duration_ms": 500 },
+//!         { "all": false, "pins": { "porch": true }, "duration_ms": 250 },
+//!         { "groups": { "ring": true, "star": false }, "duration_ms": 1000 }
+//!     ]
+//! }
+//! ```
+//!
+//! Or the same thing in RON:
+//!
+//! ```ron
+//! (
+//!     repeat: 3,
+//!     steps: [
+//!         (chain: [85, 170], duration_ms: 500),
+//!         (all: false, pins: {"porch": true}, duration_ms: 250),
+//!         (groups: {"ring": true, "star": false}, duration_ms: 1000),
+//!     ],
+//! )
+//! ```
+//!
+//! Each step can have any combination of these (applied in this order):
+//!
+//! * `all`:  Sets every pin HIGH (`true`) or LOW (`false`).
+//! * `chain`:  Sets the whole chain from bytes (see `Shifter.set_chain()`).
+//! * `groups`:  Sets every pin in each named group (see
+//!   `Shifter.define_group()`) HIGH or LOW.
+//! * `pins`:  Sets each named pin (see `Shifter.name_pin()`) HIGH or LOW.
+//!
+//! `duration_ms` (required) is how long the step stays up in milliseconds.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::Duration;
+use {Shifter, ShifterError, Sequence, Repeat, Command, Apply};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SequenceFile {
+    #[serde(default)]
+    repeat: RepeatSpec,
+    steps: Vec<StepSpec>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RepeatSpec {
+    Times(u32),
+    Keyword(String), // Only "forever"
+}
+
+impl Default for RepeatSpec {
+    fn default() -> RepeatSpec {
+        RepeatSpec::Times(1)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StepSpec {
+    #[serde(default)]
+    all: Option<bool>,
+    #[serde(default)]
+    chain: Option<Vec<u8>>,
+    #[serde(default)]
+    groups: BTreeMap<String, bool>,
+    #[serde(default)]
+    pins: BTreeMap<String, bool>,
+    duration_ms: u64,
+}
+
+impl SequenceFile {
+
+    fn into_sequence(self) -> Result<Sequence, ShifterError> {
+        let repeat = match self.repeat {
+            RepeatSpec::Times(times) => Repeat::Times(times),
+            RepeatSpec::Keyword(ref word) if word == "forever" => Repeat::Forever,
+            RepeatSpec::Keyword(word) => return Err(invalid(format!("Invalid repeat: {:?}", word))),
+        };
+        let mut sequence = Sequence::new().repeat(repeat);
+        for step in self.steps {
+            let mut commands = Vec::new();
+            match step.all {
+                Some(true) => commands.push(Command::AllHigh(Apply::Later)),
+                Some(false) => commands.push(Command::AllLow(Apply::Later)),
+                None => {},
+            }
+            if let Some(bytes) = step.chain {
+                commands.push(Command::SetChain { bytes: bytes, apply: Apply::Later });
+            }
+            for (name, state) in step.groups {
+                commands.push(Command::SetGroup { name: name, state: state, apply: Apply::Later });
+            }
+            for (name, state) in step.pins {
+                commands.push(Command::SetNamed { name: name, state: state, apply: Apply::Later });
+            }
+            sequence = sequence.step(commands, Duration::from_millis(step.duration_ms));
+        }
+        Ok(sequence)
+    }
+}
+
+// Sequence files that can't be parsed are reported the same way as a
+// corrupt state file (see persist.rs)
+fn invalid<E: ToString>(err: E) -> ShifterError {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string()).into()
+}
+
+impl Sequence {
+
+    /// Parses a `Sequence` from JSON (see the format above).  Returns a
+    /// `ShifterError::IoError` (of kind `InvalidData`) if it isn't valid.
+    #[cfg(feature = "json")]
+    pub fn from_json(json: &str) -> Result<Sequence, ShifterError> {
+        serde_json::from_str::<SequenceFile>(json).map_err(invalid)?.into_sequence()
+    }
+
+    /// Parses a `Sequence` from RON (see the format above).  Optional values
+    /// don't need to be wrapped in `Some()`.  Returns a
+    /// `ShifterError::IoError` (of kind `InvalidData`) if it isn't valid.
+    #[cfg(feature = "ron")]
+    pub fn from_ron(text: &str) -> Result<Sequence, ShifterError> {
+        let options = ron::Options::default().with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME);
+        options.from_str::<SequenceFile>(text).map_err(invalid)?.into_sequence()
+    }
+
+    /// Reads a `Sequence` from the file at *path*, which is parsed as RON if
+    /// its name ends in `.ron` and as JSON otherwise (whichever of the two
+    /// features is enabled).
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Sequence, ShifterError> {
+        let path = path.as_ref();
+        let mut contents = String::new();
+        File::open(path)?.read_to_string(&mut contents)?;
+        let is_ron = path.extension().is_some_and(|ext| ext == "ron");
+        match is_ron {
+            #[cfg(feature = "ron")]
+            true => Sequence::from_ron(&contents),
+            #[cfg(feature = "json")]
+            false => Sequence::from_json(&contents),
+            #[allow(unreachable_patterns)]
+            _ => Err(invalid(format!("Support for {} files isn't enabled", if is_ron { "RON" } else { "JSON" }))),
+        }
+    }
+}
+
+impl Shifter {
+
+    /// Loads the sequence file at *path* (see `Sequence::load()`) and starts
+    /// playing it (see `play()`).
+    pub fn play_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), ShifterError> {
+        let sequence = Sequence::load(path)?;
+        self.play(sequence)
+    }
+}
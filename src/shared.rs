@@ -0,0 +1,322 @@
+//! Sharing a `Shifter` between threads.  See `SharedShifter` and
+//! `Shifter.split()`.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use {Shifter, ShifterError, RegisterHandle, Apply, ApplyStats, Batch, Pattern};
+
+/// A `Shifter` that can be shared between threads (e.g. a web server's
+/// handler threads flipping relays while an animation thread runs).  Cloning
+/// a `SharedShifter` is cheap and every clone controls the same `Shifter`:
+///
+/// ```
+/// let shifter = shifter.into_shared();
+/// let relays = shifter.clone();
+/// thread::spawn(move || relays.set_pin_high(sr1, 3, Apply::Now));
+/// shifter.set(sr0, 0b10101010, Apply::Now)?;
+/// ```
+///
+/// Each method locks the `Shifter` for as long as the call takes so every
+/// call is atomic:  An `apply()` (whether called directly or via an *apply*
+/// argument) always shifts out the state as it was when it started and no
+/// other thread can change anything (or start another `apply()`) until it's
+/// done.  Use `batch()` or `lock()` to make several changes that other
+/// threads should never see half of.
+#[derive(Clone)]
+pub struct SharedShifter(Arc<Mutex<Shifter>>);
+
+impl SharedShifter {
+
+    /// Wraps the given *shifter* so it can be shared (same as
+    /// `Shifter.into_shared()`).
+    pub fn new(shifter: Shifter) -> SharedShifter {
+        SharedShifter(Arc::new(Mutex::new(shifter)))
+    }
+
+    /// Locks the `Shifter` (waiting for any other thread using it to finish)
+    /// and returns a guard with full access to it.  Nothing else can use the
+    /// `Shifter` until the guard is dropped.
+    pub fn lock(&self) -> MutexGuard<'_, Shifter> {
+        // A panic in another thread can't leave the Shifter any worse off
+        // than a failed apply() would so there's no need to poison everyone:
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns the `Shifter` if this is the last clone of the
+    /// `SharedShifter` (otherwise gives it back unchanged).
+    pub fn try_unwrap(self) -> Result<Shifter, SharedShifter> {
+        match Arc::try_unwrap(self.0) {
+            Ok(mutex) => Ok(mutex.into_inner().unwrap_or_else(|err| err.into_inner())),
+            Err(arc) => Err(SharedShifter(arc)),
+        }
+    }
+
+    /// Returns a `SplitRegister` for every shift register in the chain (in
+    /// the order in which they were added).  See `Shifter.split()`.
+    pub fn split(&self) -> Vec<SplitRegister> {
+        let handles: Vec<RegisterHandle> = self.lock().registers().map(|(sr, _)| sr).collect();
+        handles.into_iter().map(|sr| SplitRegister { shifter: self.clone(), sr: sr }).collect()
+    }
+
+    /// See `Shifter.set()`.
+    pub fn set(&self, sr: RegisterHandle, data: usize, apply: Apply) -> Result<(), ShifterError> {
+        self.lock().set(sr, data, apply)
+    }
+
+    /// See `Shifter.set_masked()`.
+    pub fn set_masked(&self, sr: RegisterHandle, data: usize, mask: usize, apply: Apply) -> Result<(), ShifterError> {
+        self.lock().set_masked(sr, data, mask, apply)
+    }
+
+    /// See `Shifter.get()`.
+    pub fn get(&self, sr: RegisterHandle) -> Result<usize, ShifterError> {
+        self.lock().get(sr)
+    }
+
+    /// See `Shifter.set_bytes()`.
+    pub fn set_bytes(&self, sr: RegisterHandle, bytes: &[u8], apply: Apply) -> Result<(), ShifterError> {
+        self.lock().set_bytes(sr, bytes, apply)
+    }
+
+    /// See `Shifter.get_bytes()`.
+    pub fn get_bytes(&self, sr: RegisterHandle) -> Result<Vec<u8>, ShifterError> {
+        self.lock().get_bytes(sr)
+    }
+
+    /// See `Shifter.get_pin()`.
+    pub fn get_pin(&self, sr: RegisterHandle, pin: u8) -> Result<bool, ShifterError> {
+        self.lock().get_pin(sr, pin)
+    }
+
+    /// See `Shifter.set_pin_high()`.
+    pub fn set_pin_high(&self, sr: RegisterHandle, pin: u8, apply: Apply) -> Result<(), ShifterError> {
+        self.lock().set_pin_high(sr, pin, apply)
+    }
+
+    /// See `Shifter.set_pin_low()`.
+    pub fn set_pin_low(&self, sr: RegisterHandle, pin: u8, apply: Apply) -> Result<(), ShifterError> {
+        self.lock().set_pin_low(sr, pin, apply)
+    }
+
+    /// See `Shifter.set_pin_range()`.
+    pub fn set_pin_range(&self, sr: RegisterHandle, start: u8, end: u8, state: bool, apply: Apply) -> Result<(), ShifterError> {
+        self.lock().set_pin_range(sr, start, end, state, apply)
+    }
+
+    /// See `Shifter.toggle_pin()`.
+    pub fn toggle_pin(&self, sr: RegisterHandle, pin: u8, apply: Apply) -> Result<(), ShifterError> {
+        self.lock().toggle_pin(sr, pin, apply)
+    }
+
+    /// See `Shifter.set_chain()`.
+    pub fn set_chain(&self, bytes: &[u8], apply: Apply) -> Result<(), ShifterError> {
+        self.lock().set_chain(bytes, apply)
+    }
+
+    /// See `Shifter.get_chain()`.
+    pub fn get_chain(&self) -> Vec<u8> {
+        self.lock().get_chain()
+    }
+
+    /// See `Shifter.set_chain_pin()`.
+    pub fn set_chain_pin(&self, index: usize, state: bool, apply: Apply) -> Result<(), ShifterError> {
+        self.lock().set_chain_pin(index, state, apply)
+    }
+
+    /// See `Shifter.get_chain_pin()`.
+    pub fn get_chain_pin(&self, index: usize) -> Result<bool, ShifterError> {
+        self.lock().get_chain_pin(index)
+    }
+
+    /// See `Shifter.set_named()`.
+    pub fn set_named(&self, name: &str, state: bool, apply: Apply) -> Result<(), ShifterError> {
+        self.lock().set_named(name, state, apply)
+    }
+
+    /// See `Shifter.get_named()`.
+    pub fn get_named(&self, name: &str) -> Result<bool, ShifterError> {
+        self.lock().get_named(name)
+    }
+
+    /// See `Shifter.set_group()`.
+    pub fn set_group(&self, name: &str, state: bool, apply: Apply) -> Result<(), ShifterError> {
+        self.lock().set_group(name, state, apply)
+    }
+
+    /// See `Shifter.toggle_group()`.
+    pub fn toggle_group(&self, name: &str, apply: Apply) -> Result<(), ShifterError> {
+        self.lock().toggle_group(name, apply)
+    }
+
+    /// See `Shifter.fill()`.
+    pub fn fill(&self, sr: RegisterHandle, pattern: Pattern, apply: Apply) -> Result<(), ShifterError> {
+        self.lock().fill(sr, pattern, apply)
+    }
+
+    /// See `Shifter.fill_chain()`.
+    pub fn fill_chain(&self, pattern: Pattern, apply: Apply) -> Result<(), ShifterError> {
+        self.lock().fill_chain(pattern, apply)
+    }
+
+    /// See `Shifter.all_high()`.
+    pub fn all_high(&self, apply: Apply) -> Result<(), ShifterError> {
+        self.lock().all_high(apply)
+    }
+
+    /// See `Shifter.all_low()`.
+    pub fn all_low(&self, apply: Apply) -> Result<(), ShifterError> {
+        self.lock().all_low(apply)
+    }
+
+    /// See `Shifter.batch()`.  The `Shifter` stays locked until the changes
+    /// have been applied so other threads never see them half done.
+    pub fn batch<F, R>(&self, f: F) -> Result<R, ShifterError>
+        where F: FnOnce(&mut Batch) -> Result<R, ShifterError>
+    {
+        self.lock().batch(f)
+    }
+
+    /// See `Shifter.apply()`.
+    pub fn apply(&self) -> Result<ApplyStats, ShifterError> {
+        self.lock().apply()
+    }
+
+    /// See `Shifter.apply_if_changed()`.
+    pub fn apply_if_changed(&self) -> Result<Option<ApplyStats>, ShifterError> {
+        self.lock().apply_if_changed()
+    }
+
+    /// See `Shifter.is_dirty()`.
+    pub fn is_dirty(&self) -> bool {
+        self.lock().is_dirty()
+    }
+}
+
+impl From<Shifter> for SharedShifter {
+    fn from(shifter: Shifter) -> SharedShifter {
+        SharedShifter::new(shifter)
+    }
+}
+
+impl std::fmt::Debug for SharedShifter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_tuple("SharedShifter").field(&*self.lock()).finish()
+    }
+}
+
+/// One shift register of a `Shifter` that's been `split()` up so that each
+/// part of your program can own the shift register(s) it controls (and move
+/// them to its own thread) instead of everything passing around the whole
+/// `Shifter`:
+///
+/// ```
+/// let mut parts = shifter.split().into_iter();
+/// let (lights, relays) = (parts.next().unwrap(), parts.next().unwrap());
+/// thread::spawn(move || relays.set_pin_high(3, Apply::Now));
+/// lights.set(0b10101010, Apply::Now)?;
+/// ```
+///
+/// The shifting itself is serialized:  Only one `apply()` runs at a time and
+/// each one shifts out the current state of the *whole* chain (including any
+/// changes to other shift registers that haven't been applied yet).
+#[derive(Debug, Clone)]
+pub struct SplitRegister {
+    shifter: SharedShifter,
+    sr: RegisterHandle,
+}
+
+impl SplitRegister {
+
+    /// Returns the handle of this shift register (for use with the
+    /// `SharedShifter`).
+    pub fn handle(&self) -> RegisterHandle {
+        self.sr
+    }
+
+    /// Returns the `SharedShifter` this shift register belongs to.
+    pub fn shifter(&self) -> &SharedShifter {
+        &self.shifter
+    }
+
+    /// See `Shifter.pin_count()`.
+    pub fn pin_count(&self) -> Result<u8, ShifterError> {
+        self.shifter.lock().pin_count(self.sr)
+    }
+
+    /// See `Shifter.set()`.
+    pub fn set(&self, data: usize, apply: Apply) -> Result<(), ShifterError> {
+        self.shifter.set(self.sr, data, apply)
+    }
+
+    /// See `Shifter.set_masked()`.
+    pub fn set_masked(&self, data: usize, mask: usize, apply: Apply) -> Result<(), ShifterError> {
+        self.shifter.set_masked(self.sr, data, mask, apply)
+    }
+
+    /// See `Shifter.get()`.
+    pub fn get(&self) -> Result<usize, ShifterError> {
+        self.shifter.get(self.sr)
+    }
+
+    /// See `Shifter.set_bytes()`.
+    pub fn set_bytes(&self, bytes: &[u8], apply: Apply) -> Result<(), ShifterError> {
+        self.shifter.set_bytes(self.sr, bytes, apply)
+    }
+
+    /// See `Shifter.get_bytes()`.
+    pub fn get_bytes(&self) -> Result<Vec<u8>, ShifterError> {
+        self.shifter.get_bytes(self.sr)
+    }
+
+    /// See `Shifter.get_pin()`.
+    pub fn get_pin(&self, pin: u8) -> Result<bool, ShifterError> {
+        self.shifter.get_pin(self.sr, pin)
+    }
+
+    /// See `Shifter.set_pin_high()`.
+    pub fn set_pin_high(&self, pin: u8, apply: Apply) -> Result<(), ShifterError> {
+        self.shifter.set_pin_high(self.sr, pin, apply)
+    }
+
+    /// See `Shifter.set_pin_low()`.
+    pub fn set_pin_low(&self, pin: u8, apply: Apply) -> Result<(), ShifterError> {
+        self.shifter.set_pin_low(self.sr, pin, apply)
+    }
+
+    /// See `Shifter.set_pin_range()`.
+    pub fn set_pin_range(&self, start: u8, end: u8, state: bool, apply: Apply) -> Result<(), ShifterError> {
+        self.shifter.set_pin_range(self.sr, start, end, state, apply)
+    }
+
+    /// See `Shifter.toggle_pin()`.
+    pub fn toggle_pin(&self, pin: u8, apply: Apply) -> Result<(), ShifterError> {
+        self.shifter.toggle_pin(self.sr, pin, apply)
+    }
+
+    /// See `Shifter.fill()`.
+    pub fn fill(&self, pattern: Pattern, apply: Apply) -> Result<(), ShifterError> {
+        self.shifter.fill(self.sr, pattern, apply)
+    }
+
+    /// Shifts out the whole chain (see `Shifter.apply()`).
+    pub fn apply(&self) -> Result<ApplyStats, ShifterError> {
+        self.shifter.apply()
+    }
+}
+
+impl Shifter {
+
+    /// Splits this `Shifter` into a `SplitRegister` for every shift register
+    /// in the chain (in the order in which they were added) that can each be
+    /// owned (and used) independently, even from different threads.  Add all
+    /// your shift registers first; the `Shifter` can still be reached via
+    /// `SplitRegister.shifter()`.
+    pub fn split(self) -> Vec<SplitRegister> {
+        self.into_shared().split()
+    }
+
+    /// Turns this `Shifter` into a `SharedShifter` that can be cloned and
+    /// used from any number of threads at once.
+    pub fn into_shared(self) -> SharedShifter {
+        SharedShifter::new(self)
+    }
+}
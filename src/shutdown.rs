@@ -0,0 +1,148 @@
+//! What happens to the outputs when a `Shifter` goes away or something
+//! panics.  See `DropPolicy`, `Shifter.set_drop_policy()`, and
+//! `Shifter.set_failsafe()`.
+
+use std::thread;
+use {Shifter, SharedShifter, Apply};
+
+/// What a `Shifter` does to the chain when it's dropped (e.g. when the
+/// program exits normally or the thread that owns it finishes) or a panic
+/// trips a failsafe.  See `Shifter.set_drop_policy()` and
+/// `Shifter.set_failsafe()`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum DropPolicy {
+    /// Leave whatever was last applied latched (the default).
+    #[default]
+    Leave,
+    /// Set every pin LOW and apply before letting go of the pins.  Pins are
+    /// LOW in the logical sense so with `invert()` (e.g. an active-low relay
+    /// board) they end up HIGH on the wire, i.e. off.
+    AllLow,
+    /// Set the whole chain from the given bytes (see `Shifter.set_chain()`)
+    /// and apply before letting go of the pins.  Ignored if the number of
+    /// bytes doesn't match the chain.
+    Chain(Vec<u8>),
+}
+
+impl Shifter {
+
+    /// Sets what happens to the chain when this `Shifter` is dropped (see
+    /// `DropPolicy`).  Use `DropPolicy::AllLow` to keep relays and the like
+    /// from staying energized after your program exits:
+    ///
+    /// ```
+    /// shifter.set_drop_policy(DropPolicy::AllLow);
+    /// ```
+    ///
+    /// Either way the GPIO pins are released once the final state (if any)
+    /// has been applied:  rppal puts them back the way it found them and
+    /// sysfs pins this `Shifter` exported get unexported so other programs
+    /// (or another `Shifter`) can use them.  Note that `Drop` doesn't run if
+    /// the program is killed by a signal or calls `std::process::exit()`.
+    pub fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.drop_policy = policy;
+    }
+
+    /// Returns the current `DropPolicy` (see `set_drop_policy()`).
+    pub fn drop_policy(&self) -> &DropPolicy {
+        &self.drop_policy
+    }
+
+    /// Sets the safe state the chain is driven to if this `Shifter` gets
+    /// dropped because the thread that owns it panicked (e.g. partway
+    /// through an animation).  Overrides the `DropPolicy` (see
+    /// `set_drop_policy()`) during a panic unless it's `DropPolicy::Leave`
+    /// (the default).  For a `SharedShifter` (which a panicking thread
+    /// doesn't own) see `SharedShifter.failsafe()`.
+    ///
+    /// Failsafes only work if panics unwind:  With `panic = "abort"` in your
+    /// `Cargo.toml` (as in this crate's release profile) the process ends
+    /// without dropping anything.
+    pub fn set_failsafe(&mut self, policy: DropPolicy) {
+        self.failsafe = policy;
+    }
+
+    /// Returns the failsafe state (see `set_failsafe()`).
+    pub fn failsafe(&self) -> &DropPolicy {
+        &self.failsafe
+    }
+
+    // Stops everything that could change the outputs on its own (animations,
+    // sequences, schedules, blinking, and dimming) and then drives the chain
+    // to the state given by *policy* (unless it's DropPolicy::Leave)
+    pub(crate) fn enter_safe_state(&mut self, policy: DropPolicy) {
+        let bytes = match policy {
+            DropPolicy::Leave => return,
+            DropPolicy::AllLow => None,
+            DropPolicy::Chain(bytes) => {
+                if bytes.len() != self.total_pins().div_ceil(8) {
+                    return;
+                }
+                Some(bytes)
+            },
+        };
+        self.animations.stop_all();
+        self.player = None;
+        self.schedule = None;
+        self.blinks.clear();
+        self.dimmer.clear_levels();
+        let result = match bytes {
+            Some(bytes) => self.set_chain(&bytes, Apply::Later),
+            None => self.all_low(Apply::Later),
+        };
+        // There's nobody left to report errors to so they're ignored
+        if result.is_ok() {
+            let _ = self.apply();
+        }
+    }
+}
+
+impl Drop for Shifter {
+    fn drop(&mut self) {
+        let policy = if thread::panicking() && self.failsafe != DropPolicy::Leave {
+            std::mem::take(&mut self.failsafe)
+        } else {
+            std::mem::take(&mut self.drop_policy)
+        };
+        self.enter_safe_state(policy);
+    }
+}
+
+/// Drives a `SharedShifter`'s chain to a safe state if the thread holding it
+/// panics (see `SharedShifter.failsafe()`).  Does nothing if it's dropped
+/// normally.
+#[must_use = "the failsafe only works until it's dropped"]
+pub struct Failsafe {
+    shifter: SharedShifter,
+    policy: DropPolicy,
+}
+
+impl Drop for Failsafe {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            let policy = std::mem::take(&mut self.policy);
+            self.shifter.lock().enter_safe_state(policy);
+        }
+    }
+}
+
+impl SharedShifter {
+
+    /// Returns a guard that drives the chain to the state given by *policy*
+    /// if the current thread panics while it's alive.  Everything that
+    /// changes the outputs on its own (animations, sequences, the schedule,
+    /// blinking, and dimming) gets stopped as well so another thread (e.g.
+    /// the refresh thread) doesn't bring the outputs back:
+    ///
+    /// ```
+    /// let shifter = shifter.into_shared();
+    /// let animator = shifter.clone();
+    /// thread::spawn(move || {
+    ///     let _failsafe = animator.failsafe(DropPolicy::AllLow);
+    ///     run_light_show(&animator); // If this panics everything goes dark
+    /// });
+    /// ```
+    pub fn failsafe(&self, policy: DropPolicy) -> Failsafe {
+        Failsafe { shifter: self.clone(), policy: policy }
+    }
+}
@@ -0,0 +1,88 @@
+//! Putting the chain into a safe state when the process is told to stop
+//! (Ctrl-C, `kill`, systemd stopping a service, etc).  Enabled via the
+//! `signals` feature.  See `SignalShutdown`.
+
+use std::process;
+use std::thread;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use {ShifterError, SharedShifter, DropPolicy, Apply};
+
+/// Installs handlers for SIGINT (Ctrl-C) and SIGTERM that put the chain into
+/// a safe state before the process exits (which it otherwise does without
+/// dropping anything so a `DropPolicy` never gets a chance to run):
+///
+/// ```
+/// let shifter = shifter.into_shared();
+/// SignalShutdown::new(DropPolicy::AllLow)
+///     .disable_outputs(true)
+///     .install(&shifter)?;
+/// ```
+///
+/// The signals are handled on a thread of their own which waits for the
+/// `Shifter` to be free, stops everything that changes the outputs on its
+/// own (animations, sequences, the schedule, blinking, and dimming), applies
+/// the safe state, and then exits with the usual status for the signal
+/// (128 + its number).  Errors along the way are ignored since the process
+/// is on its way out regardless.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignalShutdown {
+    policy: DropPolicy,
+    hard_clear: bool,
+    disable_outputs: bool,
+}
+
+impl SignalShutdown {
+
+    /// Returns a new `SignalShutdown` that drives the chain to the state
+    /// given by *policy* (see `DropPolicy`).
+    pub fn new(policy: DropPolicy) -> SignalShutdown {
+        SignalShutdown {
+            policy: policy,
+            hard_clear: false,
+            disable_outputs: false,
+        }
+    }
+
+    /// Sets whether the clear pin (see `Shifter.set_clear_pin()`) should be
+    /// pulsed afterwards so every output is physically LOW whatever *policy*
+    /// says (see `Shifter.hard_clear()`).
+    pub fn hard_clear(mut self, hard_clear: bool) -> SignalShutdown {
+        self.hard_clear = hard_clear;
+        self
+    }
+
+    /// Sets whether the outputs should be disabled afterwards via the output
+    /// enable pin (see `Shifter.disable_outputs()`).
+    pub fn disable_outputs(mut self, disable_outputs: bool) -> SignalShutdown {
+        self.disable_outputs = disable_outputs;
+        self
+    }
+
+    /// Installs the signal handlers for the given *shifter*.  Returns a
+    /// `ShifterError::IoError` if they can't be installed.
+    pub fn install(self, shifter: &SharedShifter) -> Result<(), ShifterError> {
+        let mut signals = Signals::new([SIGINT, SIGTERM])?;
+        let shifter = shifter.clone();
+        thread::Builder::new()
+            .name("cupi_shift signals".to_string())
+            .spawn(move || {
+                if let Some(signal) = signals.forever().next() {
+                    self.shut_down(&shifter);
+                    process::exit(128 + signal);
+                }
+            })?;
+        Ok(())
+    }
+
+    pub(crate) fn shut_down(self, shifter: &SharedShifter) {
+        let mut shifter = shifter.lock();
+        shifter.enter_safe_state(self.policy);
+        if self.hard_clear {
+            let _ = shifter.hard_clear(Apply::Now);
+        }
+        if self.disable_outputs {
+            let _ = shifter.disable_outputs();
+        }
+    }
+}
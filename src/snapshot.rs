@@ -0,0 +1,121 @@
+//! Snapshots of a `Shifter`'s chain layout and state.  See
+//! `Shifter.snapshot()` and `Shifter.restore()`.
+
+use {Shifter, ShifterError, ShiftRegister, Order, BitOrder, Apply, physical_to_logical};
+
+/// A copy of the layout, settings, and current data of every shift register
+/// in a `Shifter`'s chain.  With the `serde` feature enabled this can be
+/// serialized into whatever format you like (JSON, TOML, etc) to be sent over
+/// the network or stored alongside your app's configuration:
+///
+/// ```
+/// let json = serde_json::to_string(&shifter.snapshot()).unwrap();
+/// // ...later on:
+/// let snapshot: Snapshot = serde_json::from_str(&json).unwrap();
+/// shifter.restore(&snapshot, Apply::Now).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Snapshot {
+    /// See `Shifter.invert()`.
+    pub invert: bool,
+    /// See `Shifter.set_order()`.
+    pub order: Order,
+    /// Every shift register in the order in which they were added.
+    pub registers: Vec<RegisterSnapshot>,
+}
+
+/// A copy of the settings and current data of a single shift register.  See
+/// `Snapshot`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RegisterSnapshot {
+    /// The number of output pins.
+    pub pins: u8,
+    /// The name given via `RegisterConfig` (if any).
+    pub name: Option<String>,
+    /// The current data (see `Shifter.get_bytes()`).
+    pub data: Vec<u8>,
+    /// See `Shifter.set_invert()`.
+    pub invert: bool,
+    /// The pins that are individually inverted (see `Shifter.set_invert_mask()`)
+    /// in the same format as *data*.
+    pub invert_mask: Vec<u8>,
+    /// The pin remapping table (see `Shifter.set_remap()`).
+    pub remap: Option<Vec<u8>>,
+    /// See `Shifter.set_bit_order()`.
+    pub bit_order: BitOrder,
+}
+
+impl RegisterSnapshot {
+
+    fn new(sr: &ShiftRegister) -> RegisterSnapshot {
+        RegisterSnapshot {
+            pins: sr.pins,
+            name: sr.name.clone(),
+            data: sr.data.bytes.clone(),
+            invert: sr.invert,
+            invert_mask: sr.invert_mask.bytes.clone(),
+            // Convert back to the logical -> physical form set_remap() takes:
+            remap: sr.remap.as_ref().map(|table| {
+                let mut remap = vec![0; table.len()];
+                for (physical, &logical) in table.iter().enumerate() {
+                    remap[logical as usize] = physical as u8;
+                }
+                remap
+            }),
+            bit_order: sr.bit_order,
+        }
+    }
+}
+
+impl Shifter {
+
+    /// Returns a `Snapshot` of the layout, settings, and current data of every
+    /// shift register in the chain.  Use `restore()` to put it back.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            invert: self.invert,
+            order: self.order,
+            registers: self.shift_registers.iter().map(RegisterSnapshot::new).collect(),
+        }
+    }
+
+    /// Restores the settings and data from the given *snapshot* (see
+    /// `snapshot()`).  The chain must have the same layout as when the
+    /// snapshot was taken (the same number of shift registers with the same
+    /// number of pins) or you'll get a `ShifterError::LayoutMismatch` and
+    /// nothing will be changed.
+    /// If *apply* is `Apply::Now` the change will be applied immediately.
+    pub fn restore(&mut self, snapshot: &Snapshot, apply: Apply) -> Result<(), ShifterError> {
+        if snapshot.registers.len() != self.shift_registers.len() {
+            return Err(ShifterError::LayoutMismatch);
+        }
+        // Validate everything before changing anything:
+        let mut remaps = Vec::with_capacity(snapshot.registers.len());
+        for (sr, saved) in self.shift_registers.iter().zip(snapshot.registers.iter()) {
+            if saved.pins != sr.pins {
+                return Err(ShifterError::LayoutMismatch);
+            }
+            sr.check_bytes(&saved.data)?;
+            sr.check_bytes(&saved.invert_mask)?;
+            remaps.push(match saved.remap {
+                Some(ref remap) => Some(physical_to_logical(remap, sr.pins)?),
+                None => None,
+            });
+        }
+        let saved = snapshot.registers.iter().zip(remaps);
+        for (sr, (saved, remap)) in self.shift_registers.iter_mut().zip(saved) {
+            sr.name = saved.name.clone();
+            sr.data.set_bytes(&saved.data);
+            sr.invert = saved.invert;
+            sr.invert_mask.set_bytes(&saved.invert_mask);
+            sr.remap = remap;
+            sr.bit_order = saved.bit_order;
+        }
+        self.invert = snapshot.invert;
+        self.order = snapshot.order;
+        self.dirty = true;
+        self.maybe_apply(apply)
+    }
+}
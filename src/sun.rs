@@ -0,0 +1,81 @@
+//! Working out when the sun rises and sets (and when twilight begins and
+//! ends) so scheduled commands can track the seasons.  See `SunEvent` and
+//! `Schedule.at_sun()`.
+//!
+//! Uses the sunrise equation from NOAA's solar calculator, which is good to
+//! within a minute or two anywhere outside the polar circles.
+
+use std::f64::consts::PI;
+
+const DAY: i64 = 24 * 60 * 60;
+const J2000: f64 = 2_451_545.0; // Julian date of 2000-01-01 12:00 UTC
+const UNIX_EPOCH_JD: f64 = 2_440_587.5; // Julian date of 1970-01-01 00:00 UTC
+
+/// Something the sun does every day (see `Schedule.at_sun()`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SunEvent {
+    /// When the sun's upper edge comes up over the horizon.
+    Sunrise,
+    /// When the sun's upper edge goes down below the horizon.
+    Sunset,
+    /// When it starts getting light enough to see outside without lights
+    /// (the sun 6° below the horizon in the morning).
+    CivilDawn,
+    /// When it gets too dark to see outside without lights (the sun 6° below
+    /// the horizon in the evening).
+    CivilDusk,
+}
+
+impl SunEvent {
+
+    // How far above the horizon the sun's center is (in degrees) when this
+    // happens.  Sunrise and sunset allow for refraction and the sun's radius.
+    fn altitude(&self) -> f64 {
+        match *self {
+            SunEvent::Sunrise | SunEvent::Sunset => -0.833,
+            SunEvent::CivilDawn | SunEvent::CivilDusk => -6.0,
+        }
+    }
+
+    fn is_morning(&self) -> bool {
+        match *self {
+            SunEvent::Sunrise | SunEvent::CivilDawn => true,
+            SunEvent::Sunset | SunEvent::CivilDusk => false,
+        }
+    }
+}
+
+// Returns when *event* happens (Unix time) on the UTC day *days* (since the
+// Unix epoch) at the given *latitude* and *longitude* (degrees; north and
+// east being positive) or None if it doesn't happen that day (e.g. the sun
+// never sets during a polar summer).
+fn sun_event_on(event: SunEvent, days: i64, latitude: f64, longitude: f64) -> Option<f64> {
+    let radians = PI / 180.0;
+    let n = days as f64 + UNIX_EPOCH_JD + 0.5 - J2000 + 0.0008; // Days since J2000 (at noon)
+    let mean_noon = n - longitude / 360.0;
+    let anomaly = (357.5291 + 0.985_600_28 * mean_noon).rem_euclid(360.0) * radians;
+    let center = 1.9148 * anomaly.sin() + 0.02 * (2.0 * anomaly).sin() + 0.0003 * (3.0 * anomaly).sin();
+    let ecliptic = (anomaly / radians + center + 180.0 + 102.9372).rem_euclid(360.0) * radians;
+    let transit = J2000 + mean_noon + 0.0053 * anomaly.sin() - 0.0069 * (2.0 * ecliptic).sin();
+    let declination = (ecliptic.sin() * (23.4397 * radians).sin()).asin();
+    let latitude = latitude * radians;
+    let cos_hour_angle = ((event.altitude() * radians).sin() - latitude.sin() * declination.sin())
+        / (latitude.cos() * declination.cos());
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None; // The sun stays above (or below) that altitude all day
+    }
+    let hour_angle = cos_hour_angle.acos() / radians / 360.0; // In days
+    let julian = if event.is_morning() { transit - hour_angle } else { transit + hour_angle };
+    Some((julian - UNIX_EPOCH_JD) * DAY as f64)
+}
+
+// Returns the first time (Unix time) *event* happens after *after* at the
+// given *latitude* and *longitude* or None if it doesn't happen within the
+// next year.
+pub(crate) fn next_sun_event(event: SunEvent, latitude: f64, longitude: f64, after: i64) -> Option<i64> {
+    let first_day = after.div_euclid(DAY) - 1; // (Events can spill over into the next UTC day)
+    (first_day..first_day + 368)
+        .filter_map(|days| sun_event_on(event, days, latitude, longitude))
+        .map(|time| time.round() as i64)
+        .find(|&time| time > after)
+}
@@ -0,0 +1,150 @@
+//! Deliberate pauses between the edges `apply()` generates, for chips (or long
+//! cables) that can't keep up with back-to-back GPIO toggles, along with the
+//! other knobs for tuning the waveform.  See `Shifter.set_timing()`,
+//! `Shifter.set_delay()`, `Shifter.set_latch_polarity()`,
+//! `Shifter.set_latch_mode()`, and `Shifter.set_clock_mode()`.
+
+use std::time::{Duration, Instant};
+
+/// Something that can wait for (very) short amounts of time.  `apply()` uses
+/// this to insert the pauses configured via `Shifter.set_timing()`.  Any
+/// `FnMut(Duration) + Send` closure works:
+///
+/// ```
+/// shifter.set_delay(|duration| my_timer.wait(duration));
+/// ```
+pub trait Delay: Send {
+    /// Waits for (at least) the given *duration*.
+    fn delay(&mut self, duration: Duration);
+}
+
+impl<F: FnMut(Duration) + Send> Delay for F {
+    fn delay(&mut self, duration: Duration) {
+        self(duration)
+    }
+}
+
+/// Busy-waits (which is the only way to get anywhere near nanosecond
+/// precision; `thread::sleep()` takes at least tens of microseconds).  This is
+/// the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SpinDelay;
+
+impl Delay for SpinDelay {
+    fn delay(&mut self, duration: Duration) {
+        let start = Instant::now();
+        while start.elapsed() < duration {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+/// Doesn't wait at all.  Handy for tests that use a `Shifter` with real-world
+/// timing settings but don't want to sit through the delays.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoDelay;
+
+impl Delay for NoDelay {
+    fn delay(&mut self, _duration: Duration) {}
+}
+
+/// How long `apply()` pauses between edges.  Everything defaults to zero (no
+/// pauses at all; as fast as the GPIO backend can go).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Timing {
+    /// How long the data line is held steady before the clock goes HIGH (aka
+    /// setup time).
+    pub setup: Duration,
+    /// How long the clock stays HIGH before moving on to the next bit (aka
+    /// hold time).
+    pub hold: Duration,
+    /// How long the latch is held at its active level (see `Polarity`) before
+    /// `apply()` returns (aka latch pulse width).
+    pub latch: Duration,
+}
+
+/// Which level of a control pin counts as "active" (see
+/// `Shifter.set_latch_polarity()` and `Shifter.set_output_enable_pin()`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Polarity {
+    /// HIGH is active.  For the latch this means it's held LOW while shifting
+    /// and the shift registers latch when it goes HIGH (like the 74HC595's
+    /// RCLK; the default).  A CD4094's OE pin is also active-high.
+    ActiveHigh,
+    /// LOW is active.  For the latch this means it's held HIGH while shifting
+    /// and the shift registers latch when it goes LOW.  A 74HC595's OE pin is
+    /// active-low.
+    ActiveLow,
+}
+
+/// How `apply()` drives the latch (aka strobe) pin (see
+/// `Shifter.set_latch_mode()`).  "Active" means HIGH or LOW depending on the
+/// latch `Polarity`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum LatchMode {
+    /// The latch is inactive while shifting and becomes active afterwards
+    /// (where it stays until the next `apply()`).  This is the default and
+    /// suits parts that latch on an edge like the 74HC595.
+    Edge,
+    /// The latch is inactive while shifting then pulsed active for
+    /// `Timing.latch` and made inactive again.  Use this for parts with a
+    /// level-sensitive strobe like the CD4094 (whose outputs follow the shift
+    /// register for as long as STROBE is HIGH).
+    Pulse,
+    /// The latch is held active the whole time, even while shifting.  With a
+    /// CD4094 this means the outputs ripple as the bits go by (just as if
+    /// STROBE were tied HIGH) but they're updated without a separate strobe.
+    Held,
+    /// The latch pin isn't touched at all.  For parts without a storage
+    /// register like the 74HC164 (see `Shifter::without_latch()`).
+    None,
+}
+
+/// The clock polarity (CPOL) and phase (CPHA) used when bit-banging, named
+/// after the equivalent SPI modes (see `Shifter.set_clock_mode()`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ClockMode {
+    /// CPOL=0, CPHA=0:  The clock idles LOW and data is sampled on the rising
+    /// edge.
+    Mode0,
+    /// CPOL=0, CPHA=1:  The clock idles LOW and data is sampled on the falling
+    /// edge.
+    Mode1,
+    /// CPOL=1, CPHA=0:  The clock idles HIGH and data is sampled on the
+    /// falling edge.
+    Mode2,
+    /// CPOL=1, CPHA=1:  The clock idles HIGH and data is sampled on the rising
+    /// edge.  This is the default (it's what `apply()` has always done and it
+    /// suits the 74HC595 just fine).
+    Mode3,
+}
+
+impl ClockMode {
+
+    /// Returns the `ClockMode` with the given clock polarity (*cpol*; `true`
+    /// if the clock idles HIGH) and phase (*cpha*; `true` if data is sampled
+    /// on the second edge of each clock pulse).
+    pub fn new(cpol: bool, cpha: bool) -> ClockMode {
+        match (cpol, cpha) {
+            (false, false) => ClockMode::Mode0,
+            (false, true) => ClockMode::Mode1,
+            (true, false) => ClockMode::Mode2,
+            (true, true) => ClockMode::Mode3,
+        }
+    }
+
+    /// Returns `true` if the clock idles HIGH (CPOL=1).
+    pub fn cpol(self) -> bool {
+        self == ClockMode::Mode2 || self == ClockMode::Mode3
+    }
+
+    /// Returns `true` if data is sampled on the second (trailing) edge of each
+    /// clock pulse (CPHA=1).
+    pub fn cpha(self) -> bool {
+        self == ClockMode::Mode1 || self == ClockMode::Mode3
+    }
+}